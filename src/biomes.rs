@@ -0,0 +1,235 @@
+use fastanvil::biome::Biome;
+
+use crate::{colors::ColorOverrides, palette::Palette};
+
+/// Number of biome ids, every [`Biome`] fitting in a byte.
+pub const BIOMES: usize = 256;
+
+/// Biome of the cells a chunk has no biome for.
+pub const DEFAULT_BIOME: Biome = Biome::Plains;
+
+/// Compact id of a biome, stored in a byte on the GPU.
+pub fn biome_id(biome: Biome) -> u8 {
+    i32::from(biome) as u8
+}
+
+/// Which of its biome's colors a block's tinted faces are multiplied by.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tint {
+    /// Tinted by the block's own color, if at all.
+    None = 0,
+    Grass = 1,
+    Foliage = 2,
+    Water = 3,
+}
+
+impl Tint {
+    /// The biome tint vanilla applies to a block name. Spruce and birch leaves
+    /// have a fixed color instead.
+    pub fn of(name: &str) -> Self {
+        let Some(name) = name.strip_prefix("minecraft:") else {
+            return Self::None;
+        };
+
+        match name {
+            "grass_block" | "grass" | "short_grass" | "tall_grass" | "fern" | "large_fern"
+            | "potted_fern" | "sugar_cane" => Self::Grass,
+            "oak_leaves" | "jungle_leaves" | "acacia_leaves" | "dark_oak_leaves"
+            | "mangrove_leaves" | "vine" => Self::Foliage,
+            "water" | "bubble_column" | "water_cauldron" => Self::Water,
+            _ => Self::None,
+        }
+    }
+}
+
+/// Extends the [`Tint`]s indexed by palette id with the blocks of `palette` they
+/// are missing. Blocks with a color in `overrides` keep it instead of taking
+/// their biome's.
+pub fn extend_block_tints(tints: &mut Vec<u32>, palette: &Palette, overrides: &ColorOverrides) {
+    let new_tints = (tints.len() as u16..palette.len() as u16).map(|id| {
+        if overrides.contains(palette, id) {
+            Tint::None as u32
+        } else {
+            Tint::of(palette.name(id)) as u32
+        }
+    });
+    tints.extend(new_tints);
+}
+
+/// Grass or foliage colors by climate, looked up like vanilla's colormap
+/// textures: hotter to the left and wetter to the top, with the humidity
+/// scaled by the temperature so that only the lower left triangle is used.
+#[derive(Clone, Debug)]
+pub enum Colormap {
+    /// Colors of the hot and wet, hot and dry, and cold corners of the
+    /// triangle, blended in between.
+    Builtin([[u8; 3]; 3]),
+    /// 256×256 RGBA8 pixels of a resource pack's colormap.
+    Image(Vec<u8>),
+}
+
+impl Colormap {
+    /// About the colors of vanilla's `grass.png`.
+    pub const GRASS: Self = Self::Builtin([[71, 205, 51], [191, 183, 85], [128, 180, 151]]);
+    /// About the colors of vanilla's `foliage.png`.
+    pub const FOLIAGE: Self = Self::Builtin([[26, 191, 0], [174, 164, 42], [96, 161, 123]]);
+
+    pub fn color(&self, temperature: f32, downfall: f32) -> [u8; 3] {
+        let temperature = temperature.clamp(0.0, 1.0);
+        let downfall = downfall.clamp(0.0, 1.0) * temperature;
+
+        match self {
+            Self::Builtin(corners) => {
+                let weights = [downfall, temperature - downfall, 1.0 - temperature];
+
+                std::array::from_fn(|i| {
+                    let channel: f32 = (0..3).map(|j| weights[j] * corners[j][i] as f32).sum();
+                    channel.round() as u8
+                })
+            }
+            Self::Image(pixels) => {
+                let x = ((1.0 - temperature) * 255.0) as usize;
+                let y = ((1.0 - downfall) * 255.0) as usize;
+                let i = (y * 256 + x) * 4;
+
+                [pixels[i], pixels[i + 1], pixels[i + 2]]
+            }
+        }
+    }
+}
+
+/// `[grass, foliage, water]` colors of every biome id, as packed RGBA8 values
+/// like the block colors.
+pub fn biome_colors(grass: &Colormap, foliage: &Colormap) -> Vec<[u32; 3]> {
+    (0..BIOMES as i32)
+        .map(|id| {
+            let biome = match Biome::try_from(id) {
+                Ok(Biome::Unknown) | Err(_) => DEFAULT_BIOME,
+                Ok(biome) => biome,
+            };
+            let climate = biome.climate();
+            let (temperature, downfall) = (climate.temperature as f32, climate.rainfall as f32);
+
+            let mut grass = grass.color(temperature, downfall);
+            let mut foliage = foliage.color(temperature, downfall);
+
+            use Biome::*;
+            match biome {
+                Swamp | SwampHills => {
+                    grass = rgb(0x6a7039);
+                    foliage = rgb(0x6a7039);
+                }
+                MangroveSwamp => {
+                    grass = rgb(0x6a7039);
+                    foliage = rgb(0x8db127);
+                }
+                // Averaged with a dark green.
+                DarkForest | DarkForestHills => {
+                    let dark = rgb(0x28340a);
+                    grass = std::array::from_fn(|i| {
+                        (((grass[i] & 0xfe) as u16 + dark[i] as u16) / 2) as u8
+                    });
+                }
+                Badlands
+                | BadlandsPlateau
+                | ErodedBadlands
+                | WoodedBadlands
+                | WoodedBadlandsPlateau
+                | ModifiedBadlandsPlateau
+                | ModifiedWoodedBadlandsPlateau => {
+                    grass = rgb(0x90814d);
+                    foliage = rgb(0x9e814d);
+                }
+                _ => {}
+            }
+
+            let water = match biome {
+                Swamp | SwampHills => rgb(0x617b64),
+                MangroveSwamp => rgb(0x3a7a6a),
+                WarmOcean | DeepWarmOcean => rgb(0x43d5ee),
+                LukewarmOcean | DeepLukewarmOcean => rgb(0x45adf2),
+                ColdOcean | DeepColdOcean | SnowyTaiga | SnowyBeach => rgb(0x3d57d6),
+                FrozenOcean | DeepFrozenOcean | FrozenRiver => rgb(0x3938c9),
+                Meadow => rgb(0x0e4ecf),
+                _ => rgb(0x3f76e4),
+            };
+
+            [grass, foliage, water].map(|[r, g, b]| u32::from_le_bytes([r, g, b, 255]))
+        })
+        .collect()
+}
+
+fn rgb(hex: u32) -> [u8; 3] {
+    let [b, g, r, _] = hex.to_le_bytes();
+    [r, g, b]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CORNERS: [[u8; 3]; 3] = [[10, 20, 30], [40, 50, 60], [70, 80, 90]];
+
+    fn pack([r, g, b]: [u8; 3]) -> u32 {
+        u32::from_le_bytes([r, g, b, 255])
+    }
+
+    #[test]
+    fn builtin_colormaps_blend_their_corners() {
+        let colormap = Colormap::Builtin(CORNERS);
+
+        assert_eq!(colormap.color(1.0, 1.0), CORNERS[0]);
+        assert_eq!(colormap.color(1.0, 0.0), CORNERS[1]);
+        assert_eq!(colormap.color(0.0, 1.0), CORNERS[2]);
+        // Halfway between the hot and dry and the cold corners.
+        assert_eq!(colormap.color(0.5, 0.0), [55, 65, 75]);
+    }
+
+    #[test]
+    fn climates_are_clamped() {
+        let colormap = Colormap::Builtin(CORNERS);
+
+        assert_eq!(colormap.color(2.0, -1.0), colormap.color(1.0, 0.0));
+    }
+
+    #[test]
+    fn image_colormaps_are_hotter_left_and_wetter_up() {
+        // Each pixel holds its own coordinates.
+        let pixels = (0..256 * 256)
+            .flat_map(|i| [(i % 256) as u8, (i / 256) as u8, 0, 255])
+            .collect();
+        let colormap = Colormap::Image(pixels);
+
+        assert_eq!(colormap.color(1.0, 1.0), [0, 0, 0]);
+        assert_eq!(colormap.color(1.0, 0.0), [0, 255, 0]);
+        assert_eq!(colormap.color(0.0, 1.0), [255, 255, 0]);
+    }
+
+    #[test]
+    fn biomes_are_colored_by_their_climate() {
+        let colors = biome_colors(&Colormap::GRASS, &Colormap::FOLIAGE);
+        let climate = Biome::Plains.climate();
+        let (temperature, downfall) = (climate.temperature as f32, climate.rainfall as f32);
+
+        assert_eq!(colors.len(), BIOMES);
+        assert_eq!(
+            colors[biome_id(Biome::Plains) as usize],
+            [
+                pack(Colormap::GRASS.color(temperature, downfall)),
+                pack(Colormap::FOLIAGE.color(temperature, downfall)),
+                pack([0x3f, 0x76, 0xe4]),
+            ]
+        );
+    }
+
+    #[test]
+    fn swamps_have_fixed_colors() {
+        let colors = biome_colors(&Colormap::GRASS, &Colormap::FOLIAGE);
+
+        assert_eq!(
+            colors[biome_id(Biome::Swamp) as usize],
+            [[0x6a, 0x70, 0x39], [0x6a, 0x70, 0x39], [0x61, 0x7b, 0x64]].map(pack)
+        );
+    }
+}
@@ -1,27 +1,110 @@
 @group(0)
 @binding(0)
-var<storage> blocks: array<u32>;
+var<storage> blocks: array<Block>;
 @group(0)
 @binding(1)
-var<storage> chunks: array<vec2<u32>>;
-@group(0)
-@binding(2)
-var<storage> chunks_len: u32;
+var<storage> chunks: array<Chunk>;
 @group(0)
 @binding(3)
 var<storage, read_write> chunk_cursor: atomic<u32>;
 @group(0)
 @binding(4)
-var<storage, read_write> faces: array<vec2<u32>>;
+var<storage, read_write> faces: array<Face>;
 @group(0)
 @binding(5)
-var<storage, read_write> face_cursor: atomic<u32>;
+var<storage, read_write> face_cursors: FaceCursors;
 @group(0)
 @binding(6)
-var<storage> eye: vec3<f32>;
+var<uniform> eye: vec3<f32>;
 @group(0)
 @binding(7)
-var<storage> clip_from_world_with_margin: mat4x4<f32>;
+var<uniform> clip_from_world_with_margin: mat4x4<f32>;
+// The opaque draw then the translucent one of each page.
+@group(0)
+@binding(8)
+var<storage, read_write> draw_indirect: array<DrawIndirect>;
+@group(0)
+@binding(9)
+var<uniform> origin: vec3<i32>;
+@group(0)
+@binding(10)
+var<storage> model_ranges: array<vec2<u32>>;
+@group(0)
+@binding(11)
+var<storage> quads: array<Quad>;
+// `[opaque, translucent]` faces generated for each page, including the ones
+// that didn't fit, for the pages to grow.
+@group(0)
+@binding(12)
+var<storage, read_write> face_counts: array<vec2<u32>>;
+// Declared with the other bindings, in order, as derived layouts list them in
+// the order they are declared, which the GL backend expects to match the order
+// they are bound in.
+@group(0)
+@binding(13)
+var<uniform> face_page: FacePage;
+
+const CULLED_BIT = 0u;
+const LOD_BIT = 1u;
+const LOD_LEN_BITS = 10u;
+const TRANSLUCENT_BIT = 18u;
+const WIDTH_BIT = 19u;
+const HEIGHT_BIT = 23u;
+const MODEL_FACE = 7u;
+const NO_CULL_FACE = 6u;
+
+struct Chunk {
+    pos: vec3<i32>,
+    flags: u32,
+    block_start: u32,
+    block_len: u32,
+    lod_lens: u32,
+}
+
+struct Quad {
+    corners: array<vec4<f32>, 4>,
+    uvs: array<vec2<f32>, 4>,
+    normal: vec3<f32>,
+    block_id: u32,
+    texture: u32,
+    cull_face: u32,
+}
+
+// Laid out like the records of `Section::blocks` in `region.rs`. `shading`
+// holds 16 bits per face: the ambient occlusion of its corners, then the block
+// light and sky light in front of it.
+struct Block {
+    packed: u32,
+    id: u32,
+    shading: array<u32, 3>,
+}
+
+// Laid out like a `vec3<u32>` without padding. `x` holds the chunk index and
+// the size of merged faces, `y` the block's position, the face index and the
+// block id, and `z` the face's shading.
+struct Face {
+    x: u32,
+    y: u32,
+    z: u32,
+}
+
+// Opaque faces are written from the start of `faces` and translucent ones from
+// its end, so that both passes can be drawn from the same buffer. The cursors
+// keep counting the faces that don't fit, which are dropped.
+struct FaceCursors {
+    opaque: atomic<u32>,
+    translucent: atomic<u32>,
+}
+
+// Which of `WorldBuffers::face_pages` is bound as `faces`, and the chunks it
+// holds the faces of. The first `translucent_start` faces are opaque and the
+// rest translucent.
+struct FacePage {
+    index: u32,
+    chunk_start: u32,
+    chunk_end: u32,
+    translucent_start: u32,
+}
 
 fn blockPos(block: u32) -> vec3<u32> {
     return vec3(
@@ -31,37 +114,128 @@ fn blockPos(block: u32) -> vec3<u32> {
     );
 }
 
-fn newFace(pos: vec3<u32>, i: u32) -> vec2<u32> {
-    var face = vec2(0u);
+// Size of a block's faces, larger than the block along the face's plane for
+// faces merged by greedy meshing, which only ever have a single face.
+fn blockSize(block: u32) -> vec3<f32> {
+    let axis = firstTrailingBit(extractBits(block, 12u, 6u)) >> 1;
+
+    var size = array(1.0, 1.0, 1.0);
+    size[(axis + 1) % 3] = f32(extractBits(block, WIDTH_BIT, 4u) + 1);
+    size[(axis + 2) % 3] = f32(extractBits(block, HEIGHT_BIT, 4u) + 1);
+
+    return vec3(size[0], size[1], size[2]);
+}
+
+fn chunkLod(chunk: Chunk) -> u32 {
+    return extractBits(chunk.flags, LOD_BIT, 2u);
+}
+
+// Start and length of the blocks of a chunk at the level of detail it is drawn
+// at. Each level's cells follow the previous level's.
+fn lodBlocks(chunk: Chunk) -> vec2<u32> {
+    var start = chunk.block_start;
+    var len = chunk.block_len;
+
+    for (var lod = 1u; lod <= chunkLod(chunk); lod++) {
+        start += len;
+        len = extractBits(chunk.lod_lens, (lod - 1) * LOD_LEN_BITS, LOD_LEN_BITS);
+    }
+
+    return vec2(start, len);
+}
+
+// Faces reference their chunk instead of storing an absolute position, so
+// that they can be placed anywhere in the world. The chunk index leaves room
+// for the width and height of merged faces.
+fn newFace(chunk_index: u32, block: Block, i: u32) -> Face {
+    var face = Face(chunk_index, 0u, 0u);
 
-    face.x = insertBits(face.x, pos.x, 0u, 9u);
-    face.x = insertBits(face.x, pos.y, 9u, 9u);
-    face.x = insertBits(face.x, pos.z, 18u, 9u);
-    face.y = i;
+    face.x = insertBits(face.x, extractBits(block.packed, WIDTH_BIT, 8u), 24u, 8u);
+
+    face.y = insertBits(face.y, block.packed, 0u, 12u);
+    face.y = insertBits(face.y, i, 12u, 3u);
+    face.y = insertBits(face.y, block.id, 16u, 16u);
+
+    var shading = block.shading;
+    face.z = extractBits(shading[i / 2], (i % 2) * 16, 16u);
+
+    return face;
+}
+
+// Model faces are marked with `MODEL_FACE` as face index and reference their
+// quad, which knows its block.
+fn newModelFace(chunk_index: u32, block: Block, quad_index: u32) -> Face {
+    var face = Face(chunk_index, 0u, 0u);
+
+    face.y = insertBits(face.y, block.packed, 0u, 12u);
+    face.y = insertBits(face.y, MODEL_FACE, 12u, 3u);
+    face.y = insertBits(face.y, quad_index, 15u, 17u);
+
+    // Models have the same shading on every face.
+    face.z = extractBits(block.shading[0], 0u, 16u);
 
     return face;
 }
 
 const WORKGROUP_SIZE = 256u;
 const FACES_LEN = WORKGROUP_SIZE * 3;
+const TRANSLUCENT_FACES_LEN = WORKGROUP_SIZE;
 
 var<workgroup> workgroup_face_cursor: atomic<u32>;
-var<workgroup> workgroup_faces: array<vec2<u32>, FACES_LEN>;
+var<workgroup> workgroup_translucent_face_cursor: atomic<u32>;
+var<workgroup> workgroup_faces: array<Face, FACES_LEN>;
+var<workgroup> workgroup_translucent_faces: array<Face, TRANSLUCENT_FACES_LEN>;
 
 var<workgroup> broadcast: u32;
+var<workgroup> translucent_broadcast: u32;
+
+// Stages a face in workgroup memory, or writes it out directly when models
+// produce more faces than it can hold.
+fn pushFace(face: Face, translucent: bool) {
+    if translucent {
+        let face_index = atomicAdd(&workgroup_translucent_face_cursor, 1u);
+        if face_index < TRANSLUCENT_FACES_LEN {
+            workgroup_translucent_faces[face_index] = face;
+        } else {
+            let global_index = atomicAdd(&face_cursors.translucent, 1u);
+            if global_index < arrayLength(&faces) - face_page.translucent_start {
+                faces[arrayLength(&faces) - 1 - global_index] = face;
+            }
+        }
+    } else {
+        let face_index = atomicAdd(&workgroup_face_cursor, 1u);
+        if face_index < FACES_LEN {
+            workgroup_faces[face_index] = face;
+        } else {
+            let global_index = atomicAdd(&face_cursors.opaque, 1u);
+            if global_index < face_page.translucent_start {
+                faces[global_index] = face;
+            }
+        }
+    }
+}
 
-fn genChunkFaces(chunk: vec2<u32>, block_index: u32, local_index: u32) {
+fn genChunkFaces(chunk_index: u32, chunk_block_index: u32, local_index: u32) {
     if local_index == 0 {
         atomicStore(&workgroup_face_cursor, 0u);
+        atomicStore(&workgroup_translucent_face_cursor, 0u);
     }
 
     workgroupBarrier();
 
-    if block_index < chunk.x {
-        let block = blocks[block_index];
-        let unpacked = unpack4xU8(chunk.y);
-        let pos = blockPos(block) + unpacked.xyz * vec3(16);
-        let mid = vec3<f32>(pos) + vec3(0.5);
+    let chunk = chunks[chunk_index];
+    let lod = chunkLod(chunk);
+    let lod_blocks = lodBlocks(chunk);
+
+    if chunk_block_index < lod_blocks.y {
+        let block = blocks[lod_blocks.x + chunk_block_index];
+        let chunk_pos = chunk.pos - origin;
+        let pos = vec3<i32>(blockPos(block.packed)) + chunk_pos * vec3(16);
+        // Cells of coarser levels of detail are drawn as cubes `1 << lod` blocks
+        // wide.
+        let scale = f32(1u << lod);
+        let size = blockSize(block.packed) * scale;
+        let mid = vec3<f32>(pos) + 0.5 * size;
 
         let clip_mid_h = clip_from_world_with_margin * vec4(mid, 1.0);
         let clip_mid = clip_mid_h.xyz / clip_mid_h.w;
@@ -71,19 +245,36 @@ fn genChunkFaces(chunk: vec2<u32>, block_index: u32, local_index: u32) {
             max(abs(clip_mid.y), abs(clip_mid.z)),
         );
 
-        if max_dist <= 1.0 {
+        let translucent = bool(extractBits(block.packed, TRANSLUCENT_BIT, 1u));
+        let model_range = model_ranges[block.id];
+
+        if max_dist <= 1.0 && model_range.y > 0 && lod == 0 {
+            for (var i = model_range.x; i < model_range.x + model_range.y; i++) {
+                let quad = quads[i];
+                let visible = quad.cull_face == NO_CULL_FACE
+                    || bool(extractBits(block.packed, quad.cull_face + 12, 1u));
+                let center = vec3<f32>(pos) + 0.25 * (
+                    quad.corners[0].xyz + quad.corners[1].xyz + quad.corners[2].xyz + quad.corners[3].xyz
+                );
+
+                if visible && dot(eye - center, quad.normal) > 0.0 {
+                    pushFace(newModelFace(chunk_index, block, i), translucent);
+                }
+            }
+        } else if max_dist <= 1.0 || any(size > vec3(1.0)) {
+            // Merged faces and cells can be seen while their middle is well off
+            // screen.
             for (var i = 0u; i < 6; i++) {
-                let has_face = bool(extractBits(block, i + 12, 1u));
+                let has_face = bool(extractBits(block.packed, i + 12, 1u));
                 if has_face {
                     var axis_array = array(0.0, 0.0, 0.0);
                     axis_array[i >> 1] = select(-1.0, 1.0, bool(i & 1));
 
                     let axis = vec3(axis_array[0], axis_array[1], axis_array[2]);
-                    let origin = fma(axis, vec3(0.5), mid);
+                    let origin = fma(axis, 0.5 * size, mid);
 
                     if dot(normalize(eye - origin), axis) > 0.0 {
-                        let face_index = atomicAdd(&workgroup_face_cursor, 1u);
-                        workgroup_faces[face_index] = newFace(pos, i);
+                        pushFace(newFace(chunk_index, block, i), translucent);
                     }
                 }
             }
@@ -92,22 +283,33 @@ fn genChunkFaces(chunk: vec2<u32>, block_index: u32, local_index: u32) {
 
     workgroupBarrier();
 
-    let len = atomicLoad(&workgroup_face_cursor);
+    let len = min(atomicLoad(&workgroup_face_cursor), FACES_LEN);
+    let translucent_len = min(
+        atomicLoad(&workgroup_translucent_face_cursor),
+        TRANSLUCENT_FACES_LEN,
+    );
 
     if local_index == 0 {
-        broadcast = atomicAdd(&face_cursor, len);
+        broadcast = atomicAdd(&face_cursors.opaque, len);
+        translucent_broadcast = atomicAdd(&face_cursors.translucent, translucent_len);
     }
 
     workgroupBarrier();
 
     let face_start = broadcast;
+    let translucent_face_start = translucent_broadcast;
 
     for (var stride = 0u; stride < FACES_LEN; stride += WORKGROUP_SIZE) {
         let index = local_index + stride;
-        if index < len {
+        if index < len && index + face_start < face_page.translucent_start {
             let face_index = index + face_start;
             faces[face_index] = workgroup_faces[index];
         }
+        let translucent_capacity = arrayLength(&faces) - face_page.translucent_start;
+        if index < translucent_len && index + translucent_face_start < translucent_capacity {
+            let face_index = arrayLength(&faces) - 1 - (index + translucent_face_start);
+            faces[face_index] = workgroup_translucent_faces[index];
+        }
     }
 }
 
@@ -123,28 +325,22 @@ fn genFaces(@builtin(local_invocation_index) local_index: u32) {
 
         workgroupBarrier();
 
-        let chunk_index = broadcast;
+        let chunk_index = face_page.chunk_start + broadcast;
 
-        if chunk_index >= chunks_len {
+        if chunk_index >= face_page.chunk_end {
             break;
         }
 
         let chunk = chunks[chunk_index];
-        let chunk_start = select(
-            chunks[chunk_index - 1].x,
-            0u,
-            chunk_index == 0,
-        );
-        let chunk_len = chunk.x - chunk_start;
 
-        let unpacked = unpack4xU8(chunk.y);
-        if unpacked.w == 1 {
+        if bool(extractBits(chunk.flags, CULLED_BIT, 1u)) {
             continue;
         }
 
-        for (var stride = 0u; stride < chunk_len; stride += WORKGROUP_SIZE) {
-            let block_index = local_index + stride + chunk_start;
-            genChunkFaces(chunk, block_index, local_index);
+        let len = lodBlocks(chunk).y;
+
+        for (var stride = 0u; stride < len; stride += WORKGROUP_SIZE) {
+            genChunkFaces(chunk_index, local_index + stride, local_index);
         }
     }
 }
@@ -156,13 +352,27 @@ struct DrawIndirect {
     first_instance: u32,
 }
 
-@group(0)
-@binding(8)
-var<storage, read_write> draw_indirect: DrawIndirect;
 
+// Writes the page's opaque draw followed by its translucent one, leaving out
+// the faces that were dropped.
 @compute
 @workgroup_size(1)
 fn writeVertexCount() {
-    draw_indirect.vertex_count = atomicLoad(&face_cursor) * 6;
-    draw_indirect.instance_count = 1u;
+    let len = arrayLength(&faces);
+    let opaque = atomicLoad(&face_cursors.opaque);
+    let translucent = atomicLoad(&face_cursors.translucent);
+
+    let opaque_len = min(opaque, face_page.translucent_start);
+    let translucent_len = min(translucent, len - face_page.translucent_start);
+
+    face_counts[face_page.index] = vec2(opaque, translucent);
+
+    let draw = face_page.index * 2;
+
+    draw_indirect[draw].vertex_count = opaque_len * 6;
+    draw_indirect[draw].instance_count = 1u;
+
+    draw_indirect[draw + 1].vertex_count = translucent_len * 6;
+    draw_indirect[draw + 1].instance_count = 1u;
+    draw_indirect[draw + 1].first_vertex = (len - translucent_len) * 6;
 }
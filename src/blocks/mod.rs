@@ -1,8 +1,15 @@
-use std::{borrow::Cow, mem};
+use std::{
+    borrow::Cow,
+    mem,
+    num::NonZero,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
-use wgpu::util::DeviceExt;
-
-use crate::region::Region;
+use crate::{
+    models::ModelBuffers,
+    readback::Readback,
+    world::{WorldBuffers, MAX_FACE_PAGES},
+};
 
 #[derive(Debug)]
 pub struct BlocksPipeline {
@@ -10,10 +17,30 @@ pub struct BlocksPipeline {
     gen_faces_pipeline: wgpu::ComputePipeline,
     write_vertex_count_bind_group_layout: wgpu::BindGroupLayout,
     write_vertex_count_pipeline: wgpu::ComputePipeline,
+    chunk_cursor_buffer: wgpu::Buffer,
+    face_cursor_buffer: wgpu::Buffer,
+    /// The uniforms below hold one slot of `view_stride` bytes per view, so
+    /// all views can generate faces in one submission.
+    view_stride: u64,
+    eye_buffer: wgpu::Buffer,
+    clip_from_world_with_margin_buffer: wgpu::Buffer,
+    origin_buffer: wgpu::Buffer,
+    /// `FacePage` of `blocks.wgsl` for every page of every view, each at its
+    /// own offset.
+    face_page_buffer: wgpu::Buffer,
+    face_page_stride: u64,
+    /// Faces generated for each page in a frame, `face_count_stride` bytes
+    /// per view.
+    face_count_buffer: wgpu::Buffer,
+    face_count_stride: u64,
+    face_count_readback: Readback,
+    /// Number of pages of the frame being read back.
+    face_count_readback_pages: AtomicUsize,
 }
 
 impl BlocksPipeline {
-    pub fn new(device: &wgpu::Device) -> Self {
+    /// Creates a pipeline generating faces for `views` views.
+    pub fn new(device: &wgpu::Device, views: usize) -> Self {
         let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("gen_faces_shader_module"),
             source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("blocks.wgsl"))),
@@ -43,59 +70,206 @@ impl BlocksPipeline {
         let write_vertex_count_bind_group_layout =
             write_vertex_count_pipeline.get_bind_group_layout(0);
 
+        let chunk_cursor_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("chunk_cursor_buffer"),
+            size: mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let face_cursor_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("face_cursor_buffer"),
+            size: mem::size_of::<[u32; 2]>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let limits = device.limits();
+        let uniform_alignment = limits.min_uniform_buffer_offset_alignment as u64;
+        let view_stride = (mem::size_of::<glam::Mat4>() as u64).next_multiple_of(uniform_alignment);
+
+        let eye_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("eye_buffer"),
+            size: views as u64 * view_stride,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let clip_from_world_with_margin_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("clip_from_world_with_margin_buffer"),
+            size: views as u64 * view_stride,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let origin_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("origin_buffer"),
+            size: views as u64 * view_stride,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let face_page_stride =
+            (mem::size_of::<[u32; 4]>() as u64).next_multiple_of(uniform_alignment);
+        let face_page_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("face_page_buffer"),
+            size: (views * MAX_FACE_PAGES) as u64 * face_page_stride,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let face_count_stride = ((MAX_FACE_PAGES * mem::size_of::<[u32; 2]>()) as u64)
+            .next_multiple_of(limits.min_storage_buffer_offset_alignment as u64);
+        let face_count_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("face_count_buffer"),
+            size: views as u64 * face_count_stride,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let face_count_readback = Readback::new(
+            device,
+            "face_count_readback_buffer",
+            face_count_buffer.size(),
+        );
+
         Self {
             gen_faces_bind_group_layout,
             gen_faces_pipeline,
             write_vertex_count_pipeline,
             write_vertex_count_bind_group_layout,
+            chunk_cursor_buffer,
+            face_cursor_buffer,
+            view_stride,
+            eye_buffer,
+            clip_from_world_with_margin_buffer,
+            origin_buffer,
+            face_page_buffer,
+            face_page_stride,
+            face_count_buffer,
+            face_count_stride,
+            face_count_readback,
+            face_count_readback_pages: AtomicUsize::new(0),
+        }
+    }
+
+    /// `[opaque, translucent]` faces generated for each face page by a past
+    /// frame, the most of any view, including the ones that didn't fit, once
+    /// they have been read back. Never waits for the GPU, and has to be called
+    /// between frames, after the last one was submitted.
+    pub fn read_face_counts(&self, device: &wgpu::Device) -> Option<Vec<[u32; 2]>> {
+        self.face_count_readback.poll_mapped(device, |data| {
+            let pages = self.face_count_readback_pages.load(Ordering::Relaxed);
+            let mut counts = vec![[0; 2]; pages];
+            for view in data.chunks(self.face_count_stride as usize) {
+                let view: &[[u32; 2]] = bytemuck::cast_slice(view);
+                for (count, view_count) in counts.iter_mut().zip(view) {
+                    count[0] = count[0].max(view_count[0]);
+                    count[1] = count[1].max(view_count[1]);
+                }
+            }
+
+            counts
+        })
+    }
+
+    /// Like [`BlocksPipeline::read_face_counts`], but waits for the faces of
+    /// the last frame read back, if any, for frames that aren't drawn
+    /// continuously.
+    pub fn wait_face_counts(&self, device: &wgpu::Device) -> Option<Vec<[u32; 2]>> {
+        while self.face_count_readback.is_pending() {
+            if let Some(counts) = self.read_face_counts(device) {
+                return Some(counts);
+            }
+            device.poll(wgpu::Maintain::Wait);
         }
+
+        None
     }
 
+    /// Generates the faces seen from `view` into the face pages.
+    #[allow(clippy::too_many_arguments)]
     pub fn encode(
         &self,
         device: &wgpu::Device,
+        queue: &wgpu::Queue,
         encoder: &mut wgpu::CommandEncoder,
-        region: &Region,
-        chunk_buffer: wgpu::Buffer,
-        chunks_len_buffer: wgpu::Buffer,
+        world: &WorldBuffers,
+        models: &ModelBuffers,
+        view: usize,
+        origin: glam::IVec3,
         eye: glam::Vec3,
         clip_from_world_with_margin: glam::Mat4,
         draw_indirect_buffer: &wgpu::Buffer,
-    ) -> wgpu::Buffer {
-        let block_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("block_buffer"),
-            contents: bytemuck::cast_slice(region.blocks()),
-            usage: wgpu::BufferUsages::STORAGE,
-        });
-        let chunk_cursor_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("chunk_cursor_buffer"),
-            size: mem::size_of::<u32>() as u64,
-            usage: wgpu::BufferUsages::STORAGE,
-            mapped_at_creation: false,
-        });
-        let face_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("face_buffer"),
-            size: (region.blocks().len() * 3 * mem::size_of::<[u32; 2]>()) as u64,
-            usage: wgpu::BufferUsages::STORAGE,
-            mapped_at_creation: false,
-        });
-        let face_cursor_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("face_cursor_buffer"),
-            size: mem::size_of::<u32>() as u64,
-            usage: wgpu::BufferUsages::STORAGE,
-            mapped_at_creation: false,
-        });
-        let eye_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("eye_buffer"),
-            contents: bytemuck::cast_slice(eye.extend(0.0).as_ref()),
-            usage: wgpu::BufferUsages::STORAGE,
+    ) {
+        let offset = view as u64 * self.view_stride;
+        queue.write_buffer(
+            &self.eye_buffer,
+            offset,
+            bytemuck::cast_slice(eye.extend(0.0).as_ref()),
+        );
+        queue.write_buffer(
+            &self.clip_from_world_with_margin_buffer,
+            offset,
+            bytemuck::bytes_of(clip_from_world_with_margin.as_ref()),
+        );
+        queue.write_buffer(
+            &self.origin_buffer,
+            offset,
+            bytemuck::cast_slice(origin.extend(0).as_ref()),
+        );
+
+        for page in 0..world.face_pages.len() {
+            self.encode_page(
+                device,
+                queue,
+                encoder,
+                world,
+                models,
+                view,
+                page,
+                draw_indirect_buffer,
+            );
+        }
+    }
+
+    /// Reads back the faces generated for every view, once they have all been
+    /// encoded.
+    pub fn encode_readback(&self, encoder: &mut wgpu::CommandEncoder, world: &WorldBuffers) {
+        // Only one frame is read back at a time.
+        if self
+            .face_count_readback
+            .copy(encoder, &self.face_count_buffer)
+        {
+            self.face_count_readback_pages
+                .store(world.face_pages.len(), Ordering::Relaxed);
+        }
+    }
+
+    /// Generates the faces of the chunks of a face page into it.
+    #[allow(clippy::too_many_arguments)]
+    fn encode_page(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        world: &WorldBuffers,
+        models: &ModelBuffers,
+        view: usize,
+        page: usize,
+        draw_indirect_buffer: &wgpu::Buffer,
+    ) {
+        let chunks = world.face_page_chunks(page);
+        let face_page = &world.face_pages[page];
+        let face_page_offset = (view * MAX_FACE_PAGES + page) as u64 * self.face_page_stride;
+        queue.write_buffer(
+            &self.face_page_buffer,
+            face_page_offset,
+            bytemuck::cast_slice(&[page as u32, chunks.start, chunks.end, face_page.opaque_len]),
+        );
+        let face_page_binding = wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+            buffer: &self.face_page_buffer,
+            offset: face_page_offset,
+            size: NonZero::new(mem::size_of::<[u32; 4]>() as u64),
         });
-        let clip_from_world_with_margin_buffer =
-            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("clip_from_world_with_margin_buffer"),
-                contents: bytemuck::bytes_of(clip_from_world_with_margin.as_ref()),
-                usage: wgpu::BufferUsages::STORAGE,
-            });
+
+        encoder.clear_buffer(&self.chunk_cursor_buffer, 0, None);
+        encoder.clear_buffer(&self.face_cursor_buffer, 0, None);
 
         let gen_faces_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("gen_faces_bind_group"),
@@ -103,35 +277,59 @@ impl BlocksPipeline {
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: block_buffer.as_entire_binding(),
+                    resource: world.block_buffer.as_entire_binding(),
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: chunk_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: chunks_len_buffer.as_entire_binding(),
+                    resource: world.chunk_buffer.as_entire_binding(),
                 },
                 wgpu::BindGroupEntry {
                     binding: 3,
-                    resource: chunk_cursor_buffer.as_entire_binding(),
+                    resource: self.chunk_cursor_buffer.as_entire_binding(),
                 },
                 wgpu::BindGroupEntry {
                     binding: 4,
-                    resource: face_buffer.as_entire_binding(),
+                    resource: face_page.buffer.as_entire_binding(),
                 },
                 wgpu::BindGroupEntry {
                     binding: 5,
-                    resource: face_cursor_buffer.as_entire_binding(),
+                    resource: self.face_cursor_buffer.as_entire_binding(),
                 },
                 wgpu::BindGroupEntry {
                     binding: 6,
-                    resource: eye_buffer.as_entire_binding(),
+                    resource: self.view_binding(
+                        &self.eye_buffer,
+                        view,
+                        mem::size_of::<glam::Vec4>(),
+                    ),
                 },
                 wgpu::BindGroupEntry {
                     binding: 7,
-                    resource: clip_from_world_with_margin_buffer.as_entire_binding(),
+                    resource: self.view_binding(
+                        &self.clip_from_world_with_margin_buffer,
+                        view,
+                        mem::size_of::<glam::Mat4>(),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 9,
+                    resource: self.view_binding(
+                        &self.origin_buffer,
+                        view,
+                        mem::size_of::<glam::IVec4>(),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 10,
+                    resource: models.range_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 11,
+                    resource: models.quad_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 13,
+                    resource: face_page_binding.clone(),
                 },
             ],
         });
@@ -139,14 +337,30 @@ impl BlocksPipeline {
             label: Some("write_vertex_count_bind_group"),
             layout: &self.write_vertex_count_bind_group_layout,
             entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: face_page.buffer.as_entire_binding(),
+                },
                 wgpu::BindGroupEntry {
                     binding: 5,
-                    resource: face_cursor_buffer.as_entire_binding(),
+                    resource: self.face_cursor_buffer.as_entire_binding(),
                 },
                 wgpu::BindGroupEntry {
                     binding: 8,
                     resource: draw_indirect_buffer.as_entire_binding(),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 12,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &self.face_count_buffer,
+                        offset: view as u64 * self.face_count_stride,
+                        size: NonZero::new(self.face_count_stride),
+                    }),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 13,
+                    resource: face_page_binding,
+                },
             ],
         });
 
@@ -173,7 +387,17 @@ impl BlocksPipeline {
 
             pass.dispatch_workgroups(1, 1, 1);
         }
-
-        face_buffer
+    }
+    fn view_binding<'a>(
+        &self,
+        buffer: &'a wgpu::Buffer,
+        view: usize,
+        size: usize,
+    ) -> wgpu::BindingResource<'a> {
+        wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+            buffer,
+            offset: view as u64 * self.view_stride,
+            size: NonZero::new(size as u64),
+        })
     }
 }
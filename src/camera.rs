@@ -7,12 +7,14 @@ use winit::{
 
 const FOV_Y: f32 = f32::consts::FRAC_PI_4;
 const NEAR: f32 = 1.0;
-const FAR: f32 = 1000.0;
+const FAR: f32 = 16384.0;
 const RADIANS_PER_DOT: f32 = 1.0 / 180.0;
 
 #[derive(Debug)]
 pub struct Camera {
-    pub eye: glam::Vec3,
+    /// Kept in double precision so that it stays exact far away from the world's
+    /// origin. Everything sent to the GPU is relative to [`Camera::origin`].
+    pub eye: glam::DVec3,
     dir: glam::Vec3,
     vel: glam::Vec3,
     cursor_pos_delta: glam::Vec2,
@@ -20,7 +22,7 @@ pub struct Camera {
 
 impl Default for Camera {
     fn default() -> Self {
-        let eye = glam::Vec3::new(0.0, 150.0, 0.0);
+        let eye = glam::DVec3::new(0.0, 150.0, 0.0);
 
         Self {
             eye,
@@ -35,6 +37,24 @@ impl Default for Camera {
 }
 
 impl Camera {
+    /// A camera at `eye` looking along `yaw` and `pitch` in degrees, following
+    /// Minecraft's convention: a yaw of 0 faces south (+z) and 90 west, and a
+    /// positive pitch looks down.
+    pub fn looking(eye: glam::DVec3, yaw: f32, pitch: f32) -> Self {
+        let mut camera = Self {
+            eye,
+            cursor_pos_delta: glam::Vec2::new(
+                yaw.to_radians() - f32::consts::PI,
+                pitch.to_radians(),
+            ) / RADIANS_PER_DOT,
+            ..Self::default()
+        };
+
+        camera.update(Duration::ZERO);
+
+        camera
+    }
+
     pub fn handle_key_event(&mut self, event: KeyEvent) {
         let dir_from_state =
             |state: ElementState| (state == ElementState::Pressed) as u8 as f32 * 2.0 - 1.0;
@@ -90,7 +110,7 @@ impl Camera {
 
         let vel = forward * self.vel.x + glam::Vec3::Y * self.vel.y + left * self.vel.z;
 
-        self.eye += vel * 10.0 * dt.as_secs_f32();
+        self.eye += (vel * 10.0 * dt.as_secs_f32()).as_dvec3();
 
         let pitch = (-self.cursor_pos_delta.y * RADIANS_PER_DOT)
             .clamp(-f32::consts::PI / 2.0, f32::consts::PI / 2.0);
@@ -100,8 +120,20 @@ impl Camera {
             .mul_vec3(glam::Vec3::NEG_Z);
     }
 
+    /// Coordinates of the chunk containing the eye. World-space positions on the
+    /// GPU are expressed relative to this chunk's minimum corner so that they
+    /// stay small enough for `f32` wherever the camera is.
+    pub fn origin(&self) -> glam::IVec3 {
+        (self.eye / 16.0).floor().as_ivec3()
+    }
+
+    /// Eye position relative to [`Camera::origin`].
+    pub fn local_eye(&self) -> glam::Vec3 {
+        (self.eye - (self.origin() * 16).as_dvec3()).as_vec3()
+    }
+
     pub fn clip_from_world(&self, aspect_ratio: f32) -> glam::Mat4 {
-        let view = glam::Mat4::look_to_rh(self.eye, self.dir, glam::Vec3::Y);
+        let view = glam::Mat4::look_to_rh(self.local_eye(), self.dir, glam::Vec3::Y);
         let proj = glam::Mat4::perspective_rh(FOV_Y, aspect_ratio, NEAR, FAR);
         let flip_z = glam::Mat4::from_translation(glam::Vec3::new(0.0, 0.0, 1.0))
             * glam::Mat4::from_scale(glam::Vec3::new(1.0, 1.0, -1.0));
@@ -109,10 +141,24 @@ impl Camera {
         flip_z * proj * view
     }
 
+    /// Smallest sphere around the part of the view between `near` and `far`
+    /// along the view direction, as its center relative to [`Camera::origin`]
+    /// and its radius. It doesn't change as the camera turns.
+    pub fn bounding_sphere(&self, aspect_ratio: f32, near: f32, far: f32) -> (glam::Vec3, f32) {
+        // Distance from the view axis of the slice's corners, per unit along it.
+        let slope = (FOV_Y / 2.0).tan() * (1.0 + aspect_ratio * aspect_ratio).sqrt();
+        let slope_sq = slope * slope;
+
+        let dist = ((near + far) / 2.0 * (1.0 + slope_sq)).min(far);
+        let radius = (far - dist).hypot(far * slope);
+
+        (self.local_eye() + self.dir * dist, radius)
+    }
+
     pub fn clip_from_world_with_margin(&self, aspect_ratio: f32, margin: f32) -> glam::Mat4 {
         let dist = margin / (FOV_Y / 2.0).sin();
 
-        let eye = self.eye - self.dir * dist;
+        let eye = self.local_eye() - self.dir * dist;
 
         let view = glam::Mat4::look_to_rh(eye, self.dir, glam::Vec3::Y);
         let proj = glam::Mat4::perspective_rh_gl(
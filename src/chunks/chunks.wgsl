@@ -1,98 +1,180 @@
 @group(0)
 @binding(0)
-var<storage, read_write> chunks: array<vec2<u32>>;
+var<storage, read_write> chunks: array<Chunk>;
 @group(0)
 @binding(1)
 var<storage> chunks_len: u32;
 @group(0)
 @binding(2)
 var<storage> clip_from_world_with_margin: mat4x4<f32>;
+@group(0)
+@binding(3)
+var<uniform> origin: vec3<i32>;
+@group(0)
+@binding(4)
+var<uniform> eye: vec3<f32>;
+@group(0)
+@binding(5)
+var<storage> pyramid: array<f32>;
+@group(0)
+@binding(6)
+var<uniform> pyramid_view: PyramidView;
+@group(0)
+@binding(7)
+var<storage, read_write> cull_counts: CullCounts;
 
 const WORKGROUP_SIZE = 256u;
+const CULLED_BIT = 0u;
+const LOD_BIT = 1u;
+const MIN_LOD_BIT = 3u;
+const MAX_LOD_BIT = 5u;
+// Matches `LOD_DISTANCE` in `region.rs`.
+const LOD_DISTANCE = 256.0;
+
+struct Chunk {
+    pos: vec3<i32>,
+    flags: u32,
+    block_start: u32,
+    block_len: u32,
+    lod_lens: u32,
+}
 
-@compute
-@workgroup_size(WORKGROUP_SIZE)
-fn cullChunks(@builtin(global_invocation_id) global_id: vec3<u32>) {
-    if global_id.x >= chunks_len {
-        return;
-    }
-
-    let chunk = chunks[global_id.x];
-    var unpacked = unpack4xU8(chunk.y);
-    let chunk_mid = fma(vec3<f32>(unpacked.xyz), vec3(16.0), vec3(8.0));
-
-    let clip_mid_h = clip_from_world_with_margin * vec4(chunk_mid, 1.0);
-    let clip_mid = clip_mid_h.xyz / clip_mid_h.w;
+struct PyramidView {
+    clip_from_world: mat4x4<f32>,
+    origin: vec3<i32>,
+    levels: u32,
+    size: vec2<u32>,
+}
 
-    let max_dist = max(abs(clip_mid.x), max(abs(clip_mid.y), abs(clip_mid.z)));
+struct CullCounts {
+    frustum: atomic<u32>,
+    occlusion: atomic<u32>,
+}
 
-    if max_dist > 1.0 {
-        unpacked.w = 1u;
-        chunks[global_id.x].y = pack4xU8(unpacked);
+// Level of detail of a section, doubling in coarseness every time the distance
+// doubles past `LOD_DISTANCE`, within the levels its column was loaded with.
+fn chunkLod(chunk: Chunk, dist: f32) -> u32 {
+    var lod = 0u;
+    if dist >= LOD_DISTANCE {
+        lod = u32(log2(dist / LOD_DISTANCE)) + 1;
     }
+
+    return clamp(
+        lod,
+        extractBits(chunk.flags, MIN_LOD_BIT, 2u),
+        extractBits(chunk.flags, MAX_LOD_BIT, 2u),
+    );
 }
 
+// Levels of the depth pyramid are halved and rounded down like mipmaps, and
+// stored one after the other starting from the full-size one.
+fn levelSize(level: u32) -> vec2<u32> {
+    return max(pyramid_view.size >> vec2(level), vec2(1u));
+}
 
-var<workgroup> prefix: array<u32, WORKGROUP_SIZE>;
+fn loadPyramidTexel(texel: vec2<u32>, level: u32) -> f32 {
+    var offset = 0u;
+    for (var i = 0u; i < level; i++) {
+        let size = levelSize(i);
+        offset += size.x * size.y;
+    }
 
-fn workgroupPrefixSum(val: u32, local_index: u32) -> u32 {
-    var sum = 0u;
-    var shift = 1u;
+    return pyramid[offset + texel.y * levelSize(level).x + texel.x];
+}
 
-    let shifted = (local_index + shift) & (WORKGROUP_SIZE - 1);
-    prefix[shifted] = select(val, 0u, shifted < shift);
+// Whether a section is behind what was drawn in the last frame. Its box is
+// projected with the last frame's view onto the depth pyramid level where it
+// covers at most 2×2 texels, and is hidden when its nearest point is farther
+// than all of them. Boxes crossing the near plane or the screen's edges are
+// never hidden.
+fn isOccluded(chunk: Chunk) -> bool {
+    if pyramid_view.levels == 0 {
+        return false;
+    }
 
-    loop {
-        workgroupBarrier();
+    let min_pos = vec3<f32>((chunk.pos - pyramid_view.origin) * 16);
 
-        sum += prefix[local_index];
+    var min_uv = vec2(1.0);
+    var max_uv = vec2(0.0);
+    var max_depth = 0.0;
+    for (var i = 0u; i < 8; i++) {
+        let corner = vec3(f32(i & 1), f32((i >> 1) & 1), f32((i >> 2) & 1));
+        let clip = pyramid_view.clip_from_world * vec4(fma(corner, vec3(16.0), min_pos), 1.0);
 
-        if shift == WORKGROUP_SIZE { break; }
+        if clip.w <= 0.0 {
+            return false;
+        }
 
-        workgroupBarrier();
+        let ndc = clip.xyz / clip.w;
+        let uv = fma(ndc.xy, vec2(0.5, -0.5), vec2(0.5));
 
-        let shifted = (local_index + shift) & (WORKGROUP_SIZE - 1);
-        prefix[shifted] = select(sum, 0u, shifted < shift);
+        min_uv = min(min_uv, uv);
+        max_uv = max(max_uv, uv);
+        max_depth = max(max_depth, ndc.z);
+    }
 
-        shift <<= 1u;
+    if any(min_uv < vec2(0.0)) || any(max_uv > vec2(1.0)) {
+        return false;
     }
 
-    return sum;
+    let size = vec2<f32>(pyramid_view.size);
+    let min_texel = vec2<u32>(min_uv * size);
+    let max_texel = vec2<u32>(max_uv * size);
+    let extent = max(max_texel.x - min_texel.x, max_texel.y - min_texel.y);
+
+    let level = select(
+        0u,
+        min(firstLeadingBit(extent) + 1, pyramid_view.levels - 1),
+        extent > 0,
+    );
+
+    let last_texel = levelSize(level) - 1;
+    let min_level_texel = min(min_texel >> vec2(level), last_texel);
+    let max_level_texel = min(max_texel >> vec2(level), last_texel);
+
+    let farthest = min(
+        min(
+            loadPyramidTexel(min_level_texel, level),
+            loadPyramidTexel(vec2(max_level_texel.x, min_level_texel.y), level),
+        ),
+        min(
+            loadPyramidTexel(vec2(min_level_texel.x, max_level_texel.y), level),
+            loadPyramidTexel(max_level_texel, level),
+        ),
+    );
+
+    return max_depth < farthest;
 }
 
-var<workgroup> carry: u32;
-
 @compute
 @workgroup_size(WORKGROUP_SIZE)
-fn prefixSum(
-    @builtin(local_invocation_index) local_index: u32,
-) {
-    if local_index == 0 {
-        carry = 0u;
+fn cullChunks(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    if global_id.x >= chunks_len {
+        return;
     }
 
-    prefix[local_index] = 0u;
-
-    workgroupBarrier();
+    let chunk = chunks[global_id.x];
+    let chunk_mid = fma(vec3<f32>(chunk.pos - origin), vec3(16.0), vec3(8.0));
 
-    for (var stride = 0u; stride < chunks_len; stride += WORKGROUP_SIZE) {
-        let index = local_index + stride;
+    let clip_mid_h = clip_from_world_with_margin * vec4(chunk_mid, 1.0);
+    let clip_mid = clip_mid_h.xyz / clip_mid_h.w;
 
-        var val: u32;
-        if index < chunks_len {
-            val = chunks[index].x;
-        }
+    let max_dist = max(abs(clip_mid.x), max(abs(clip_mid.y), abs(clip_mid.z)));
 
-        let sum = workgroupPrefixSum(val, local_index);
+    let frustum_culled = max_dist > 1.0;
+    let occluded = !frustum_culled && isOccluded(chunk);
 
-        if index < chunks_len {
-            chunks[index].x += sum + carry;
+    // Unused records are left out of the counts.
+    if chunk.block_len > 0 || chunk.lod_lens > 0 {
+        if frustum_culled {
+            atomicAdd(&cull_counts.frustum, 1u);
+        } else if occluded {
+            atomicAdd(&cull_counts.occlusion, 1u);
         }
+    }
 
-        workgroupBarrier();
+    var flags = insertBits(chunk.flags, u32(frustum_culled || occluded), CULLED_BIT, 1u);
+    flags = insertBits(flags, chunkLod(chunk, distance(chunk_mid, eye)), LOD_BIT, 2u);
 
-        if local_index == WORKGROUP_SIZE - 1 {
-            carry += val + sum;
-        }
-    }
+    chunks[global_id.x].flags = flags;
 }
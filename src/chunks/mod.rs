@@ -1,19 +1,35 @@
-use std::borrow::Cow;
-
-use wgpu::util::DeviceExt;
-
-use crate::region::Region;
+use std::{borrow::Cow, mem, num::NonZero};
+
+use crate::{hiz::DepthPyramid, readback::Readback, world::WorldBuffers};
+
+/// Number of sections culled in a frame, by what culled them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CullCounts {
+    /// Sections outside the view frustum.
+    pub frustum: u32,
+    /// Sections in the view frustum but hidden behind what was drawn in the
+    /// previous frame.
+    pub occlusion: u32,
+}
 
 #[derive(Debug)]
 pub struct ChunksPipeline {
     cull_chunks_bind_group_layout: wgpu::BindGroupLayout,
     cull_chunks_pipeline: wgpu::ComputePipeline,
-    prefix_sum_bind_group_layout: wgpu::BindGroupLayout,
-    prefix_sum_pipeline: wgpu::ComputePipeline,
+    /// The uniforms below hold one slot of `view_stride` bytes per view, so
+    /// all views can be culled in one submission.
+    view_stride: u64,
+    clip_from_world_with_margin_buffer: wgpu::Buffer,
+    origin_buffer: wgpu::Buffer,
+    eye_buffer: wgpu::Buffer,
+    cull_count_buffer: wgpu::Buffer,
+    cull_count_readback: Readback,
 }
 
 impl ChunksPipeline {
-    pub fn new(device: &wgpu::Device) -> Self {
+    /// Creates a pipeline culling chunks for `views` views, the camera's being
+    /// view 0.
+    pub fn new(device: &wgpu::Device, views: usize) -> Self {
         let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("cull_chunks_shader_module"),
             source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("chunks.wgsl"))),
@@ -31,49 +47,89 @@ impl ChunksPipeline {
 
         let cull_chunks_bind_group_layout = cull_chunks_pipeline.get_bind_group_layout(0);
 
-        let prefix_sum_pipeline =
-            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-                label: Some("prefix_sum_pipeline"),
-                layout: None,
-                module: &shader_module,
-                entry_point: "prefixSum",
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                cache: None,
-            });
+        let limits = device.limits();
+        let view_stride = (mem::size_of::<glam::Mat4>() as u64).next_multiple_of(
+            limits
+                .min_uniform_buffer_offset_alignment
+                .max(limits.min_storage_buffer_offset_alignment) as u64,
+        );
+
+        let clip_from_world_with_margin_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("clip_from_world_with_margin_buffer"),
+            size: views as u64 * view_stride,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let origin_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("origin_buffer"),
+            size: views as u64 * view_stride,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
 
-        let prefix_sum_bind_group_layout = prefix_sum_pipeline.get_bind_group_layout(0);
+        let eye_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("eye_buffer"),
+            size: views as u64 * view_stride,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let cull_count_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("cull_count_buffer"),
+            size: 2 * mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let cull_count_readback = Readback::new(
+            device,
+            "cull_count_readback_buffer",
+            cull_count_buffer.size(),
+        );
 
         Self {
             cull_chunks_bind_group_layout,
             cull_chunks_pipeline,
-            prefix_sum_pipeline,
-            prefix_sum_bind_group_layout,
+            view_stride,
+            clip_from_world_with_margin_buffer,
+            origin_buffer,
+            eye_buffer,
+            cull_count_buffer,
+            cull_count_readback,
         }
     }
 
+    /// Culls the chunks for `view`. Only the camera's counts are read back.
+    #[allow(clippy::too_many_arguments)]
     pub fn encode(
         &self,
         device: &wgpu::Device,
+        queue: &wgpu::Queue,
         encoder: &mut wgpu::CommandEncoder,
-        region: &Region,
+        world: &WorldBuffers,
+        view: usize,
+        origin: glam::IVec3,
+        eye: glam::Vec3,
         clip_from_world_with_margin: glam::Mat4,
-    ) -> (wgpu::Buffer, wgpu::Buffer) {
-        let chunk_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("chunk_buffer"),
-            contents: bytemuck::cast_slice(region.chunks()),
-            usage: wgpu::BufferUsages::STORAGE,
-        });
-        let chunks_len_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("chunks_buffer"),
-            contents: bytemuck::bytes_of(&(region.chunks().len() as u32)),
-            usage: wgpu::BufferUsages::STORAGE,
-        });
-        let clip_from_world_with_margin_buffer =
-            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("clip_from_world_with_margin_buffer"),
-                contents: bytemuck::bytes_of(clip_from_world_with_margin.as_ref()),
-                usage: wgpu::BufferUsages::STORAGE,
-            });
+        depth_pyramid: &DepthPyramid,
+    ) {
+        let offset = view as u64 * self.view_stride;
+        queue.write_buffer(
+            &self.clip_from_world_with_margin_buffer,
+            offset,
+            bytemuck::bytes_of(clip_from_world_with_margin.as_ref()),
+        );
+        queue.write_buffer(
+            &self.origin_buffer,
+            offset,
+            bytemuck::cast_slice(origin.extend(0).as_ref()),
+        );
+        queue.write_buffer(
+            &self.eye_buffer,
+            offset,
+            bytemuck::cast_slice(eye.extend(0.0).as_ref()),
+        );
 
         let cull_chunks_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("cull_chunks_bind_group"),
@@ -81,33 +137,53 @@ impl ChunksPipeline {
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: chunk_buffer.as_entire_binding(),
+                    resource: world.chunk_buffer.as_entire_binding(),
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: chunks_len_buffer.as_entire_binding(),
+                    resource: world.chunks_len_buffer.as_entire_binding(),
                 },
                 wgpu::BindGroupEntry {
                     binding: 2,
-                    resource: clip_from_world_with_margin_buffer.as_entire_binding(),
+                    resource: self.view_binding(
+                        &self.clip_from_world_with_margin_buffer,
+                        view,
+                        mem::size_of::<glam::Mat4>(),
+                    ),
                 },
-            ],
-        });
-        let prefix_sum_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("prefix_sum_bind_group"),
-            layout: &self.prefix_sum_bind_group_layout,
-            entries: &[
                 wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: chunk_buffer.as_entire_binding(),
+                    binding: 3,
+                    resource: self.view_binding(
+                        &self.origin_buffer,
+                        view,
+                        mem::size_of::<glam::IVec4>(),
+                    ),
                 },
                 wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: chunks_len_buffer.as_entire_binding(),
+                    binding: 4,
+                    resource: self.view_binding(
+                        &self.eye_buffer,
+                        view,
+                        mem::size_of::<glam::Vec4>(),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: depth_pyramid.pyramid_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: depth_pyramid.view_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: self.cull_count_buffer.as_entire_binding(),
                 },
             ],
         });
 
+        encoder.clear_buffer(&self.cull_count_buffer, 0, None);
+
         {
             let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
                 label: Some("cull_chunks_pass"),
@@ -117,21 +193,37 @@ impl ChunksPipeline {
             pass.set_pipeline(&self.cull_chunks_pipeline);
             pass.set_bind_group(0, &cull_chunks_bind_group, &[]);
 
-            pass.dispatch_workgroups(region.chunks().len().div_ceil(256) as u32, 1, 1);
+            pass.dispatch_workgroups(world.chunks_len().div_ceil(256), 1, 1);
         }
 
-        {
-            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("prefix_sum_pass"),
-                timestamp_writes: None,
-            });
+        // Only the camera's counts are read back, one frame at a time.
+        if view == 0 {
+            self.cull_count_readback
+                .copy(encoder, &self.cull_count_buffer);
+        }
+    }
 
-            pass.set_pipeline(&self.prefix_sum_pipeline);
-            pass.set_bind_group(0, &prefix_sum_bind_group, &[]);
+    /// Counts of a past frame, once they have been read back. Never waits for
+    /// the GPU, and has to be called between frames, after the last one was
+    /// submitted.
+    pub fn read_cull_counts(&self, device: &wgpu::Device) -> Option<CullCounts> {
+        self.cull_count_readback.poll_mapped(device, |data| {
+            let [frustum, occlusion]: [u32; 2] = bytemuck::pod_read_unaligned(data);
 
-            pass.dispatch_workgroups(1, 1, 1);
-        }
+            CullCounts { frustum, occlusion }
+        })
+    }
 
-        (chunk_buffer, chunks_len_buffer)
+    fn view_binding<'a>(
+        &self,
+        buffer: &'a wgpu::Buffer,
+        view: usize,
+        size: usize,
+    ) -> wgpu::BindingResource<'a> {
+        wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+            buffer,
+            offset: view as u64 * self.view_stride,
+            size: NonZero::new(size as u64),
+        })
     }
 }
@@ -0,0 +1,646 @@
+use std::{collections::HashMap, fs, io, path::Path};
+
+use serde::Deserialize;
+
+use crate::{opacity, palette::Palette};
+
+/// Base colors of the vanilla map palette.
+mod map {
+    pub const GRASS: [u8; 3] = [127, 178, 56];
+    pub const SAND: [u8; 3] = [247, 233, 163];
+    pub const WOOL: [u8; 3] = [199, 199, 199];
+    pub const FIRE: [u8; 3] = [255, 0, 0];
+    pub const ICE: [u8; 3] = [160, 160, 255];
+    pub const METAL: [u8; 3] = [167, 167, 167];
+    pub const PLANT: [u8; 3] = [0, 124, 0];
+    pub const SNOW: [u8; 3] = [255, 255, 255];
+    pub const CLAY: [u8; 3] = [164, 168, 184];
+    pub const DIRT: [u8; 3] = [151, 109, 77];
+    pub const STONE: [u8; 3] = [112, 112, 112];
+    pub const WATER: [u8; 3] = [64, 64, 255];
+    pub const WOOD: [u8; 3] = [143, 119, 72];
+    pub const QUARTZ: [u8; 3] = [255, 252, 245];
+    pub const COLOR_ORANGE: [u8; 3] = [216, 127, 51];
+    pub const COLOR_MAGENTA: [u8; 3] = [178, 76, 216];
+    pub const COLOR_LIGHT_BLUE: [u8; 3] = [102, 153, 216];
+    pub const COLOR_YELLOW: [u8; 3] = [229, 229, 51];
+    pub const COLOR_LIGHT_GREEN: [u8; 3] = [127, 204, 25];
+    pub const COLOR_PINK: [u8; 3] = [242, 127, 165];
+    pub const COLOR_GRAY: [u8; 3] = [76, 76, 76];
+    pub const COLOR_LIGHT_GRAY: [u8; 3] = [153, 153, 153];
+    pub const COLOR_CYAN: [u8; 3] = [76, 127, 153];
+    pub const COLOR_PURPLE: [u8; 3] = [127, 63, 178];
+    pub const COLOR_BLUE: [u8; 3] = [51, 76, 178];
+    pub const COLOR_BROWN: [u8; 3] = [102, 76, 51];
+    pub const COLOR_GREEN: [u8; 3] = [102, 127, 51];
+    pub const COLOR_RED: [u8; 3] = [153, 51, 51];
+    pub const COLOR_BLACK: [u8; 3] = [25, 25, 25];
+    pub const GOLD: [u8; 3] = [250, 238, 77];
+    pub const DIAMOND: [u8; 3] = [92, 219, 213];
+    pub const LAPIS: [u8; 3] = [74, 128, 255];
+    pub const EMERALD: [u8; 3] = [0, 217, 58];
+    pub const PODZOL: [u8; 3] = [129, 86, 49];
+    pub const NETHER: [u8; 3] = [112, 2, 0];
+    pub const TERRACOTTA_WHITE: [u8; 3] = [209, 177, 161];
+    pub const TERRACOTTA_ORANGE: [u8; 3] = [159, 82, 36];
+    pub const TERRACOTTA_MAGENTA: [u8; 3] = [149, 87, 108];
+    pub const TERRACOTTA_LIGHT_BLUE: [u8; 3] = [112, 108, 138];
+    pub const TERRACOTTA_YELLOW: [u8; 3] = [186, 133, 36];
+    pub const TERRACOTTA_LIGHT_GREEN: [u8; 3] = [103, 117, 53];
+    pub const TERRACOTTA_PINK: [u8; 3] = [160, 77, 78];
+    pub const TERRACOTTA_GRAY: [u8; 3] = [57, 41, 35];
+    pub const TERRACOTTA_LIGHT_GRAY: [u8; 3] = [135, 107, 98];
+    pub const TERRACOTTA_CYAN: [u8; 3] = [87, 92, 92];
+    pub const TERRACOTTA_PURPLE: [u8; 3] = [122, 73, 88];
+    pub const TERRACOTTA_BLUE: [u8; 3] = [76, 62, 92];
+    pub const TERRACOTTA_BROWN: [u8; 3] = [76, 50, 35];
+    pub const TERRACOTTA_GREEN: [u8; 3] = [76, 82, 42];
+    pub const TERRACOTTA_RED: [u8; 3] = [142, 60, 46];
+    pub const TERRACOTTA_BLACK: [u8; 3] = [37, 22, 16];
+    pub const CRIMSON_NYLIUM: [u8; 3] = [189, 48, 49];
+    pub const CRIMSON_STEM: [u8; 3] = [148, 63, 97];
+    pub const CRIMSON_HYPHAE: [u8; 3] = [92, 25, 29];
+    pub const WARPED_NYLIUM: [u8; 3] = [22, 126, 134];
+    pub const WARPED_STEM: [u8; 3] = [58, 142, 140];
+    pub const WARPED_HYPHAE: [u8; 3] = [86, 44, 62];
+    pub const WARPED_WART_BLOCK: [u8; 3] = [20, 180, 133];
+    pub const DEEPSLATE: [u8; 3] = [100, 100, 100];
+    pub const RAW_IRON: [u8; 3] = [216, 175, 147];
+    pub const GLOW_LICHEN: [u8; 3] = [127, 167, 150];
+}
+
+/// Dye prefixes with their regular and terracotta map colors.
+const DYES: [(&str, [u8; 3], [u8; 3]); 16] = [
+    ("white", map::SNOW, map::TERRACOTTA_WHITE),
+    ("orange", map::COLOR_ORANGE, map::TERRACOTTA_ORANGE),
+    ("magenta", map::COLOR_MAGENTA, map::TERRACOTTA_MAGENTA),
+    (
+        "light_blue",
+        map::COLOR_LIGHT_BLUE,
+        map::TERRACOTTA_LIGHT_BLUE,
+    ),
+    ("yellow", map::COLOR_YELLOW, map::TERRACOTTA_YELLOW),
+    ("lime", map::COLOR_LIGHT_GREEN, map::TERRACOTTA_LIGHT_GREEN),
+    ("pink", map::COLOR_PINK, map::TERRACOTTA_PINK),
+    ("gray", map::COLOR_GRAY, map::TERRACOTTA_GRAY),
+    (
+        "light_gray",
+        map::COLOR_LIGHT_GRAY,
+        map::TERRACOTTA_LIGHT_GRAY,
+    ),
+    ("cyan", map::COLOR_CYAN, map::TERRACOTTA_CYAN),
+    ("purple", map::COLOR_PURPLE, map::TERRACOTTA_PURPLE),
+    ("blue", map::COLOR_BLUE, map::TERRACOTTA_BLUE),
+    ("brown", map::COLOR_BROWN, map::TERRACOTTA_BROWN),
+    ("green", map::COLOR_GREEN, map::TERRACOTTA_GREEN),
+    ("red", map::COLOR_RED, map::TERRACOTTA_RED),
+    ("black", map::COLOR_BLACK, map::TERRACOTTA_BLACK),
+];
+
+/// Wood type prefixes with the map color of their planks.
+const WOODS: [(&str, [u8; 3]); 11] = [
+    ("oak", map::WOOD),
+    ("spruce", map::PODZOL),
+    ("birch", map::SAND),
+    ("jungle", map::DIRT),
+    ("acacia", map::COLOR_ORANGE),
+    ("dark_oak", map::COLOR_BROWN),
+    ("mangrove", map::COLOR_RED),
+    ("cherry", map::TERRACOTTA_WHITE),
+    ("bamboo", map::COLOR_YELLOW),
+    ("crimson", map::CRIMSON_STEM),
+    ("warped", map::WARPED_STEM),
+];
+
+/// Suffixes of blocks that share the color of the block they are made of.
+const SHAPE_SUFFIXES: [&str; 5] = ["_stairs", "_slab", "_wall", "_pressure_plate", "_button"];
+
+/// Map colors of blocks that don't follow any of the naming patterns above. Names
+/// are given without the `minecraft:` namespace.
+const BLOCKS: &[(&str, [u8; 3])] = &[
+    ("grass_block", map::GRASS),
+    ("dirt", map::DIRT),
+    ("coarse_dirt", map::DIRT),
+    ("rooted_dirt", map::DIRT),
+    ("farmland", map::DIRT),
+    ("dirt_path", map::DIRT),
+    ("granite", map::DIRT),
+    ("polished_granite", map::DIRT),
+    ("podzol", map::PODZOL),
+    ("mycelium", map::COLOR_PURPLE),
+    ("mud", map::TERRACOTTA_CYAN),
+    ("mud_bricks", map::TERRACOTTA_LIGHT_GRAY),
+    ("clay", map::CLAY),
+    ("stone", map::STONE),
+    ("cobblestone", map::STONE),
+    ("mossy_cobblestone", map::STONE),
+    ("stone_bricks", map::STONE),
+    ("mossy_stone_bricks", map::STONE),
+    ("cracked_stone_bricks", map::STONE),
+    ("chiseled_stone_bricks", map::STONE),
+    ("smooth_stone", map::STONE),
+    ("andesite", map::STONE),
+    ("polished_andesite", map::STONE),
+    ("gravel", map::STONE),
+    ("bedrock", map::STONE),
+    ("furnace", map::STONE),
+    ("dispenser", map::STONE),
+    ("dropper", map::STONE),
+    ("observer", map::STONE),
+    ("diorite", map::QUARTZ),
+    ("polished_diorite", map::QUARTZ),
+    ("quartz_block", map::QUARTZ),
+    ("quartz_bricks", map::QUARTZ),
+    ("quartz_pillar", map::QUARTZ),
+    ("chiseled_quartz_block", map::QUARTZ),
+    ("smooth_quartz", map::QUARTZ),
+    ("sea_lantern", map::QUARTZ),
+    ("tuff", map::TERRACOTTA_GRAY),
+    ("calcite", map::TERRACOTTA_WHITE),
+    ("dripstone_block", map::TERRACOTTA_BROWN),
+    ("pointed_dripstone", map::TERRACOTTA_BROWN),
+    ("sand", map::SAND),
+    ("sandstone", map::SAND),
+    ("cut_sandstone", map::SAND),
+    ("chiseled_sandstone", map::SAND),
+    ("smooth_sandstone", map::SAND),
+    ("glowstone", map::SAND),
+    ("end_stone", map::SAND),
+    ("end_stone_bricks", map::SAND),
+    ("red_sand", map::COLOR_ORANGE),
+    ("red_sandstone", map::COLOR_ORANGE),
+    ("cut_red_sandstone", map::COLOR_ORANGE),
+    ("smooth_red_sandstone", map::COLOR_ORANGE),
+    ("terracotta", map::COLOR_ORANGE),
+    ("pumpkin", map::COLOR_ORANGE),
+    ("carved_pumpkin", map::COLOR_ORANGE),
+    ("jack_o_lantern", map::COLOR_ORANGE),
+    ("water", map::WATER),
+    ("bubble_column", map::WATER),
+    ("kelp", map::WATER),
+    ("kelp_plant", map::WATER),
+    ("seagrass", map::WATER),
+    ("tall_seagrass", map::WATER),
+    ("lava", map::FIRE),
+    ("fire", map::FIRE),
+    ("tnt", map::FIRE),
+    ("redstone_block", map::FIRE),
+    ("ice", map::ICE),
+    ("packed_ice", map::ICE),
+    ("blue_ice", map::ICE),
+    ("frosted_ice", map::ICE),
+    ("glass", map::ICE),
+    ("glass_pane", map::ICE),
+    ("snow", map::SNOW),
+    ("snow_block", map::SNOW),
+    ("powder_snow", map::SNOW),
+    ("iron_block", map::METAL),
+    ("iron_bars", map::METAL),
+    ("iron_door", map::METAL),
+    ("iron_trapdoor", map::METAL),
+    ("anvil", map::METAL),
+    ("chipped_anvil", map::METAL),
+    ("damaged_anvil", map::METAL),
+    ("cauldron", map::STONE),
+    ("hopper", map::STONE),
+    ("lantern", map::METAL),
+    ("chain", map::METAL),
+    ("rail", map::METAL),
+    ("raw_iron_block", map::RAW_IRON),
+    ("gold_block", map::GOLD),
+    ("raw_gold_block", map::GOLD),
+    ("bell", map::GOLD),
+    ("torch", map::GOLD),
+    ("wall_torch", map::GOLD),
+    ("diamond_block", map::DIAMOND),
+    ("beacon", map::DIAMOND),
+    ("prismarine_bricks", map::DIAMOND),
+    ("dark_prismarine", map::DIAMOND),
+    ("prismarine", map::COLOR_CYAN),
+    ("lapis_block", map::LAPIS),
+    ("emerald_block", map::EMERALD),
+    ("netherrack", map::NETHER),
+    ("nether_bricks", map::NETHER),
+    ("red_nether_bricks", map::NETHER),
+    ("nether_quartz_ore", map::NETHER),
+    ("nether_gold_ore", map::NETHER),
+    ("magma_block", map::NETHER),
+    ("nether_wart_block", map::COLOR_RED),
+    ("warped_wart_block", map::WARPED_WART_BLOCK),
+    ("crimson_nylium", map::CRIMSON_NYLIUM),
+    ("warped_nylium", map::WARPED_NYLIUM),
+    ("crimson_hyphae", map::CRIMSON_HYPHAE),
+    ("warped_hyphae", map::WARPED_HYPHAE),
+    ("soul_sand", map::COLOR_BROWN),
+    ("soul_soil", map::COLOR_BROWN),
+    ("basalt", map::COLOR_BLACK),
+    ("polished_basalt", map::COLOR_BLACK),
+    ("blackstone", map::COLOR_BLACK),
+    ("obsidian", map::COLOR_BLACK),
+    ("crying_obsidian", map::COLOR_BLACK),
+    ("coal_block", map::COLOR_BLACK),
+    ("purpur_block", map::COLOR_MAGENTA),
+    ("purpur_pillar", map::COLOR_MAGENTA),
+    ("bricks", map::COLOR_RED),
+    ("bookshelf", map::WOOD),
+    ("crafting_table", map::WOOD),
+    ("chest", map::WOOD),
+    ("barrel", map::WOOD),
+    ("note_block", map::WOOD),
+    ("jukebox", map::DIRT),
+    ("melon", map::COLOR_LIGHT_GREEN),
+    ("hay_block", map::COLOR_YELLOW),
+    ("sponge", map::COLOR_YELLOW),
+    ("wet_sponge", map::COLOR_YELLOW),
+    ("cactus", map::PLANT),
+    ("sugar_cane", map::PLANT),
+    ("vine", map::PLANT),
+    ("lily_pad", map::PLANT),
+    ("grass", map::PLANT),
+    ("short_grass", map::PLANT),
+    ("tall_grass", map::PLANT),
+    ("fern", map::PLANT),
+    ("large_fern", map::PLANT),
+    ("dandelion", map::PLANT),
+    ("poppy", map::PLANT),
+    ("blue_orchid", map::PLANT),
+    ("allium", map::PLANT),
+    ("azure_bluet", map::PLANT),
+    ("oxeye_daisy", map::PLANT),
+    ("cornflower", map::PLANT),
+    ("lily_of_the_valley", map::PLANT),
+    ("sunflower", map::PLANT),
+    ("lilac", map::PLANT),
+    ("rose_bush", map::PLANT),
+    ("peony", map::PLANT),
+    ("moss_block", map::COLOR_GREEN),
+    ("moss_carpet", map::COLOR_GREEN),
+    ("glow_lichen", map::GLOW_LICHEN),
+    ("amethyst_block", map::COLOR_PURPLE),
+    ("budding_amethyst", map::COLOR_PURPLE),
+    ("copper_block", map::COLOR_ORANGE),
+    ("cobweb", map::WOOL),
+];
+
+/// Per-block color overrides read from a user-supplied TOML or JSON file mapping
+/// block names or full block states to colors, e.g. `"minecraft:stone" = "#7f7f7f"`
+/// or `{ "mymod:ore": [200, 40, 40] }`.
+#[derive(Debug, Default)]
+pub struct ColorOverrides(HashMap<String, [u8; 3]>);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColorValue {
+    Hex(String),
+    Rgb([u8; 3]),
+}
+
+impl ColorOverrides {
+    /// Reads overrides from `path`, parsed as TOML if it has a `.toml` extension
+    /// and as JSON otherwise.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)?;
+
+        let values: HashMap<String, ColorValue> = if path
+            .extension()
+            .is_some_and(|extension| extension == "toml")
+        {
+            toml::from_str(&contents).map_err(|err| invalid_data(err.to_string()))?
+        } else {
+            serde_json::from_str(&contents).map_err(|err| invalid_data(err.to_string()))?
+        };
+
+        values
+            .into_iter()
+            .map(|(name, value)| {
+                let color = match value {
+                    ColorValue::Hex(hex) => parse_hex(&hex)
+                        .ok_or_else(|| invalid_data(format!("invalid color for {name}: {hex}")))?,
+                    ColorValue::Rgb(rgb) => rgb,
+                };
+
+                Ok((name, color))
+            })
+            .collect::<io::Result<_>>()
+            .map(Self)
+    }
+
+    /// Whether the color of the block `id` of `palette` is overridden, by its
+    /// state or its name.
+    pub fn contains(&self, palette: &Palette, id: u16) -> bool {
+        self.0.contains_key(palette.state(id)) || self.0.contains_key(palette.name(id))
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn parse_hex(hex: &str) -> Option<[u8; 3]> {
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+
+    if hex.len() != 6 {
+        return None;
+    }
+
+    let rgb = u32::from_str_radix(hex, 16).ok()?;
+    let [b, g, r, _] = rgb.to_le_bytes();
+
+    Some([r, g, b])
+}
+
+/// Alpha of translucent blocks drawn without a texture.
+const TRANSLUCENT_ALPHA: u8 = 160;
+
+/// Extends the color table indexed by palette ids with the blocks of `palette`
+/// it is missing, as packed RGBA8 values ready to be unpacked with
+/// `unpack4x8unorm`. Overrides take precedence over the map colors, and blocks
+/// unknown to both get a color derived from their name. Translucent blocks get
+/// a lower alpha.
+pub fn extend_block_colors(colors: &mut Vec<u32>, palette: &Palette, overrides: &ColorOverrides) {
+    let new_colors = (colors.len() as u16..palette.len() as u16).map(|id| {
+        let name = palette.name(id);
+        let [r, g, b] = overrides
+            .0
+            .get(palette.state(id))
+            .or_else(|| overrides.0.get(name))
+            .copied()
+            .or_else(|| map_color(name))
+            .unwrap_or_else(|| hash_color(name));
+
+        let a = if opacity::is_translucent(palette.state(id)) {
+            TRANSLUCENT_ALPHA
+        } else {
+            255
+        };
+
+        u32::from_le_bytes([r, g, b, a])
+    });
+    colors.extend(new_colors);
+}
+
+/// Looks up the vanilla map color of a block name.
+fn map_color(name: &str) -> Option<[u8; 3]> {
+    let name = name.strip_prefix("minecraft:")?;
+
+    if let Some(&(_, color)) = BLOCKS.iter().find(|&&(block, _)| block == name) {
+        return Some(color);
+    }
+
+    if name.ends_with("_leaves") || name.ends_with("_sapling") || name.ends_with("_roots") {
+        return Some(map::PLANT);
+    }
+
+    for &(dye, color, terracotta_color) in &DYES {
+        if let Some(rest) = name
+            .strip_prefix(dye)
+            .and_then(|rest| rest.strip_prefix('_'))
+        {
+            return Some(if rest == "terracotta" {
+                terracotta_color
+            } else {
+                color
+            });
+        }
+    }
+
+    let unstripped = name.strip_prefix("stripped_").unwrap_or(name);
+    for &(wood, color) in &WOODS {
+        if unstripped
+            .strip_prefix(wood)
+            .is_some_and(|rest| rest.starts_with('_'))
+        {
+            return Some(color);
+        }
+    }
+
+    for suffix in SHAPE_SUFFIXES {
+        if let Some(base) = name.strip_suffix(suffix) {
+            let color = [base.to_owned(), format!("{base}s"), format!("{base}_block")]
+                .iter()
+                .find_map(|base| map_color(&format!("minecraft:{base}")));
+
+            if color.is_some() {
+                return color;
+            }
+        }
+    }
+
+    if name.contains("deepslate") {
+        return Some(map::DEEPSLATE);
+    }
+
+    if name.contains("blackstone") {
+        return Some(map::COLOR_BLACK);
+    }
+
+    if name.ends_with("_ore") {
+        return Some(map::STONE);
+    }
+
+    if name.contains("copper") {
+        return Some(map::COLOR_ORANGE);
+    }
+
+    if name.ends_with("_flower") || name.ends_with("_tulip") || name.ends_with("_mushroom") {
+        return Some(map::PLANT);
+    }
+
+    None
+}
+
+/// Derives a stable, reasonably bright color from a block name.
+fn hash_color(name: &str) -> [u8; 3] {
+    // FNV-1a
+    let hash = name.bytes().fold(0x811c_9dc5u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    });
+
+    let [r, g, b, _] = hash.to_le_bytes();
+
+    [r / 2 + 96, g / 2 + 96, b / 2 + 96]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `contents` to a file named `name` in a fresh temporary directory
+    /// and loads it as overrides.
+    fn load_overrides(name: &str, contents: &str) -> io::Result<ColorOverrides> {
+        let dir =
+            std::env::temp_dir().join(format!("principium-colors-{}-{name}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+
+        let path = dir.join(name);
+        fs::write(&path, contents)?;
+        let overrides = ColorOverrides::load(&path);
+        fs::remove_dir_all(&dir)?;
+
+        overrides
+    }
+
+    #[test]
+    fn parses_hex_colors() {
+        assert_eq!(parse_hex("#7f7f7f"), Some([127, 127, 127]));
+        assert_eq!(parse_hex("C82828"), Some([200, 40, 40]));
+        assert_eq!(parse_hex("#00ff80"), Some([0, 255, 128]));
+    }
+
+    #[test]
+    fn rejects_malformed_hex_colors() {
+        assert_eq!(parse_hex("#fff"), None);
+        assert_eq!(parse_hex("#7f7f7f7f"), None);
+        assert_eq!(parse_hex(""), None);
+        assert_eq!(parse_hex("#7g7f7f"), None);
+        assert_eq!(parse_hex("+7f7f7f"), None);
+    }
+
+    #[test]
+    fn loads_overrides_from_toml() {
+        let overrides = load_overrides(
+            "colors.toml",
+            r##"
+                "minecraft:stone" = "#7f7f7f"
+                "mymod:ore" = [200, 40, 40]
+            "##,
+        )
+        .unwrap();
+
+        assert_eq!(overrides.0["minecraft:stone"], [127, 127, 127]);
+        assert_eq!(overrides.0["mymod:ore"], [200, 40, 40]);
+    }
+
+    #[test]
+    fn loads_overrides_from_json() {
+        let overrides = load_overrides(
+            "colors.json",
+            r##"{ "minecraft:stone": "7f7f7f", "mymod:ore": [200, 40, 40] }"##,
+        )
+        .unwrap();
+
+        assert_eq!(overrides.0["minecraft:stone"], [127, 127, 127]);
+        assert_eq!(overrides.0["mymod:ore"], [200, 40, 40]);
+    }
+
+    #[test]
+    fn rejects_invalid_overrides() {
+        for (name, contents) in [
+            ("short.json", r##"{ "minecraft:stone": "#fff" }"##),
+            ("letters.json", r##"{ "minecraft:stone": "#zzzzzz" }"##),
+            ("range.json", r#"{ "minecraft:stone": [300, 0, 0] }"#),
+            ("length.json", r#"{ "minecraft:stone": [1, 2] }"#),
+            ("syntax.toml", r##""minecraft:stone" = "#7f7f7f"##),
+        ] {
+            let error = load_overrides(name, contents).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{name}");
+        }
+    }
+
+    #[test]
+    fn overrides_take_precedence_by_state_then_name() {
+        let mut palette = Palette::default();
+        palette.id("minecraft:stone");
+        palette.id("minecraft:oak_log|axis=y");
+        palette.id("minecraft:oak_log|axis=x");
+        palette.id("minecraft:glass");
+
+        let overrides = ColorOverrides(HashMap::from([
+            ("minecraft:oak_log".to_owned(), [1, 2, 3]),
+            ("minecraft:oak_log|axis=x".to_owned(), [4, 5, 6]),
+        ]));
+        assert!(overrides.contains(&palette, 1));
+        assert!(!overrides.contains(&palette, 0));
+
+        let mut colors = Vec::new();
+        extend_block_colors(&mut colors, &palette, &overrides);
+
+        let [r, g, b] = map::STONE;
+        let [gr, gg, gb] = map::ICE;
+        assert_eq!(
+            colors,
+            [
+                u32::from_le_bytes([r, g, b, 255]),
+                u32::from_le_bytes([1, 2, 3, 255]),
+                u32::from_le_bytes([4, 5, 6, 255]),
+                u32::from_le_bytes([gr, gg, gb, TRANSLUCENT_ALPHA]),
+            ]
+        );
+    }
+
+    #[test]
+    fn extends_only_new_palette_entries() {
+        let mut palette = Palette::default();
+        palette.id("minecraft:stone");
+
+        let mut colors = vec![0];
+        extend_block_colors(&mut colors, &palette, &ColorOverrides::default());
+        assert_eq!(colors, [0]);
+
+        palette.id("minecraft:dirt");
+        extend_block_colors(&mut colors, &palette, &ColorOverrides::default());
+        let [r, g, b] = map::DIRT;
+        assert_eq!(colors, [0, u32::from_le_bytes([r, g, b, 255])]);
+    }
+
+    #[test]
+    fn looks_up_map_colors() {
+        assert_eq!(map_color("minecraft:stone"), Some(map::STONE));
+        assert_eq!(map_color("minecraft:red_sand"), Some(map::COLOR_ORANGE));
+        assert_eq!(map_color("minecraft:birch_leaves"), Some(map::PLANT));
+        assert_eq!(map_color("minecraft:red_wool"), Some(map::COLOR_RED));
+        assert_eq!(
+            map_color("minecraft:light_gray_terracotta"),
+            Some(map::TERRACOTTA_LIGHT_GRAY)
+        );
+        assert_eq!(
+            map_color("minecraft:stripped_spruce_log"),
+            Some(map::PODZOL)
+        );
+        assert_eq!(
+            map_color("minecraft:dark_oak_planks"),
+            Some(map::COLOR_BROWN)
+        );
+    }
+
+    #[test]
+    fn falls_back_to_related_blocks() {
+        // Shapes take the color of their base block, with or without a plural.
+        assert_eq!(map_color("minecraft:stone_brick_stairs"), Some(map::STONE));
+        assert_eq!(map_color("minecraft:quartz_slab"), Some(map::QUARTZ));
+        assert_eq!(map_color("minecraft:sandstone_wall"), Some(map::SAND));
+
+        assert_eq!(
+            map_color("minecraft:deepslate_diamond_ore"),
+            Some(map::DEEPSLATE)
+        );
+        assert_eq!(map_color("minecraft:diamond_ore"), Some(map::STONE));
+        assert_eq!(
+            map_color("minecraft:oxidized_cut_copper"),
+            Some(map::COLOR_ORANGE)
+        );
+        assert_eq!(map_color("minecraft:chorus_flower"), Some(map::PLANT));
+    }
+
+    #[test]
+    fn has_no_map_color_for_unknown_blocks() {
+        assert_eq!(map_color("minecraft:mystery_block"), None);
+        assert_eq!(map_color("mymod:stone"), None);
+        assert_eq!(map_color("stone"), None);
+    }
+
+    #[test]
+    fn hashes_names_to_stable_bright_colors() {
+        let color = hash_color("mymod:ore");
+
+        assert_eq!(color, hash_color("mymod:ore"));
+        assert_ne!(color, hash_color("mymod:gem"));
+
+        for name in ["", "mymod:ore", "mymod:gem", "minecraft:mystery_block"] {
+            assert!(hash_color(name)
+                .iter()
+                .all(|&channel| (96..=223).contains(&channel)));
+        }
+    }
+}
@@ -0,0 +1,28 @@
+@group(0)
+@binding(0)
+var accum: texture_2d<f32>;
+@group(0)
+@binding(1)
+var revealage: texture_2d<f32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2(f32((vertex_index << 1) & 2), f32(vertex_index & 2));
+
+    return vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    let texel = vec2<i32>(pos.xy);
+    let revealage = textureLoad(revealage, texel, 0).r;
+
+    if revealage >= 1.0 {
+        discard;
+    }
+
+    let accum = textureLoad(accum, texel, 0);
+    let color = accum.rgb / clamp(accum.a, 1e-4, 5e4);
+
+    return vec4(color, 1.0 - revealage);
+}
@@ -1,26 +1,172 @@
 @group(0)
 @binding(0)
-var<storage> faces: array<vec2<u32>>;
+var<storage> faces: array<Face>;
 @group(0)
 @binding(1)
 var<uniform> clip_from_world: mat4x4<f32>;
+@group(0)
+@binding(2)
+var<storage> chunks: array<Chunk>;
+@group(0)
+@binding(3)
+var<uniform> origin: vec3<i32>;
+@group(0)
+@binding(4)
+var<storage> colors: array<u32>;
+@group(0)
+@binding(5)
+var block_textures: texture_2d_array<f32>;
+@group(0)
+@binding(6)
+var block_sampler: sampler;
+@group(0)
+@binding(7)
+var<storage> face_textures: array<array<u32, 3>>;
+@group(0)
+@binding(8)
+var<storage> quads: array<Quad>;
+@group(0)
+@binding(9)
+var<uniform> daylight: f32;
+// Biome ids of each chunk's 4×4×4 cells, 4 per word.
+@group(0)
+@binding(10)
+var<storage> biomes: array<array<u32, 16>>;
+@group(0)
+@binding(11)
+var<storage> block_tints: array<u32>;
+// `[grass, foliage, water]` colors of each biome.
+@group(0)
+@binding(12)
+var<storage> biome_colors: array<array<u32, 3>>;
+@group(1)
+@binding(0)
+var shadow_maps: texture_depth_2d_array;
+@group(1)
+@binding(1)
+var shadow_sampler: sampler_comparison;
+@group(1)
+@binding(2)
+var<uniform> shadow_view: ShadowView;
+@group(1)
+@binding(3)
+var<uniform> sky: SkyView;
+
+const NO_TEXTURE = 0xffffu;
+const TINTED_BIT = 15u;
+const MODEL_FACE = 7u;
+const LOD_BIT = 1u;
+// Matches `CASCADES` in `shadows/mod.rs`.
+const CASCADES = 3u;
+
+// Laid out like `Face` in `blocks.wgsl`.
+struct Face {
+    x: u32,
+    y: u32,
+    z: u32,
+}
+
+struct Quad {
+    corners: array<vec4<f32>, 4>,
+    uvs: array<vec2<f32>, 4>,
+    normal: vec3<f32>,
+    block_id: u32,
+    texture: u32,
+    cull_face: u32,
+}
+
+struct Chunk {
+    pos: vec3<i32>,
+    flags: u32,
+    block_start: u32,
+    block_len: u32,
+    lod_lens: u32,
+}
+
+struct ShadowView {
+    clip_from_world: array<mat4x4<f32>, CASCADES>,
+    // Distance along the view direction up to which each cascade reaches.
+    distances: vec3<f32>,
+    // Towards the sun.
+    sun: vec3<f32>,
+    texel_sizes: vec3<f32>,
+}
+
+// Laid out like `SkyView` in `sky/mod.rs`.
+struct SkyView {
+    world_from_clip: mat4x4<f32>,
+    horizon: vec3<f32>,
+    // Fog per block along the ground from the eye.
+    fog_density: f32,
+    zenith: vec3<f32>,
+    sun_disc: u32,
+    sun: vec3<f32>,
+    eye: vec3<f32>,
+}
 
-fn vertexPos(face: vec2<u32>, local_vertex_index: u32) -> array<vec3<f32>, 2> {
-    let block_pos = vec3(
-        f32(extractBits(face.x, 0u, 9u)),
-        f32(extractBits(face.x, 9u, 9u)),
-        f32(extractBits(face.x, 18u, 9u)),
+struct FaceVertex {
+    pos: vec3<f32>,
+    norm: vec3<f32>,
+    uv: vec2<f32>,
+    // Position across the face, from corner 0 at `(0, 0)` to corner 3 at
+    // `(1, 1)`.
+    corner: vec2<f32>,
+}
+
+fn blockPos(face: Face) -> vec3<f32> {
+    let local_block_pos = vec3(
+        extractBits(face.y, 0u, 4u),
+        extractBits(face.y, 4u, 4u),
+        extractBits(face.y, 8u, 4u),
+    );
+
+    let chunk_index = extractBits(face.x, 0u, 24u);
+
+    return vec3<f32>(
+        (chunks[chunk_index].pos - origin) * 16 + vec3<i32>(local_block_pos),
+    );
+}
+
+const QUAD_CORNERS = array(0u, 1u, 2u, 0u, 2u, 3u);
+
+// Quads are drawn as the triangles 0 1 2 and 0 2 3 of their corners.
+fn quadVertex(face: Face, quad_index: u32, local_vertex_index: u32) -> FaceVertex {
+    var quad_corners = QUAD_CORNERS;
+    let corner = quad_corners[local_vertex_index];
+
+    return FaceVertex(
+        blockPos(face) + quads[quad_index].corners[corner].xyz,
+        quads[quad_index].normal,
+        quads[quad_index].uvs[corner],
+        vec2(0.0),
     );
+}
+
+fn faceVertex(face: Face, local_vertex_index: u32) -> FaceVertex {
+    let block_pos = blockPos(face);
 
     var local_pos = array(0.0, 0.0, 0.0);
 
-    let axis = extractBits(face.y, 1u, 2u);
-    let sign = extractBits(face.y, 0u, 1u);
+    let axis = extractBits(face.y, 13u, 2u);
+    let sign = extractBits(face.y, 12u, 1u);
 
-    local_pos[axis] = f32(sign);
-    local_pos[(axis + 1) % 3] = f32(extractBits(local_vertex_index, 0u, 1u));
-    local_pos[(axis + 2) % 3] = f32(extractBits(local_vertex_index, 1u, 1u)
-        | u32(local_vertex_index == 4));
+    // Faces merged by greedy meshing span several blocks, and so do the cells
+    // of coarser levels of detail.
+    let chunk_index = extractBits(face.x, 0u, 24u);
+    let scale = f32(1u << extractBits(chunks[chunk_index].flags, LOD_BIT, 2u));
+
+    var size = array(scale, scale, scale);
+    size[(axis + 1) % 3] = f32(extractBits(face.x, 24u, 4u) + 1) * scale;
+    size[(axis + 2) % 3] = f32(extractBits(face.x, 28u, 4u) + 1) * scale;
+
+    let corner = vec2<f32>(vec2(
+        extractBits(local_vertex_index, 0u, 1u),
+        extractBits(local_vertex_index, 1u, 1u) | u32(local_vertex_index == 4),
+    ));
+
+    local_pos[axis] = f32(sign) * scale;
+    local_pos[(axis + 1) % 3] = corner.x * size[(axis + 1) % 3];
+    local_pos[(axis + 2) % 3] = corner.y * size[(axis + 2) % 3];
 
     let pos = block_pos +  vec3(local_pos[0], local_pos[1], local_pos[2]);
 
@@ -28,29 +174,249 @@ fn vertexPos(face: vec2<u32>, local_vertex_index: u32) -> array<vec3<f32>, 2> {
 
     norm[axis] = select(-1.0, 1.0, bool(sign));
 
-    return array(pos, vec3(norm[0], norm[1], norm[2]));
+    // Orients textures the same way Minecraft does: side faces upright, top
+    // and bottom faces aligned with x and z.
+    var uv: vec2<f32>;
+    switch axis {
+        case 0u: {
+            uv = vec2(select(local_pos[2], size[2] - local_pos[2], bool(sign)), size[1] - local_pos[1]);
+        }
+        case 1u: {
+            uv = vec2(local_pos[0], select(size[2] - local_pos[2], local_pos[2], bool(sign)));
+        }
+        default: {
+            uv = vec2(select(size[0] - local_pos[0], local_pos[0], bool(sign)), size[1] - local_pos[1]);
+        }
+    }
+
+    return FaceVertex(pos, vec3(norm[0], norm[1], norm[2]), uv, corner);
+}
+
+fn faceTexture(block_id: u32, face_index: u32) -> u32 {
+    let packed = face_textures[block_id][face_index / 2];
+    return extractBits(packed, (face_index % 2) * 16, 16u);
+}
+
+// Color of a face's block, with the color of the biome around it instead for
+// blocks tinted by their biome, like grass, leaves and water. Merged faces
+// never span several biome cells.
+fn faceColor(face: Face, block_id: u32) -> u32 {
+    let color = colors[block_id];
+    let tint = block_tints[block_id];
+
+    if tint == 0u {
+        return color;
+    }
+
+    let chunk_index = extractBits(face.x, 0u, 24u);
+    let cell = extractBits(face.y, 2u, 2u)
+        | (extractBits(face.y, 10u, 2u) << 2u)
+        | (extractBits(face.y, 6u, 2u) << 4u);
+    let biome = extractBits(biomes[chunk_index][cell / 4], (cell % 4) * 8, 8u);
+
+    // Keeps the alpha of the block color.
+    return insertBits(color, biome_colors[biome][tint - 1], 0u, 24u);
 }
 
 struct VertexOutput {
     @builtin(position) pos: vec4<f32>,
     @location(0) norm: vec3<f32>,
+    // Packed RGBA8, from `faceColor`.
+    @location(1) @interpolate(flat) color: u32,
+    @location(2) @interpolate(flat) texture: u32,
+    @location(3) uv: vec2<f32>,
+    @location(4) view_depth: f32,
+    @location(5) @interpolate(flat) shading: u32,
+    @location(6) corner: vec2<f32>,
+    @location(7) world_pos: vec3<f32>,
 };
 
 @vertex
 fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
-    let pos_norm = vertexPos(faces[vertex_index / 6], vertex_index % 6);
-    return VertexOutput(clip_from_world * vec4(pos_norm[0], 1.0), pos_norm[1]);
+    let face = faces[vertex_index / 6];
+    let face_index = extractBits(face.y, 12u, 3u);
+
+    var face_vertex: FaceVertex;
+    var block_id: u32;
+    var texture: u32;
+
+    if face_index == MODEL_FACE {
+        let quad_index = extractBits(face.y, 15u, 17u);
+
+        face_vertex = quadVertex(face, quad_index, vertex_index % 6);
+        block_id = quads[quad_index].block_id;
+        texture = quads[quad_index].texture;
+    } else {
+        face_vertex = faceVertex(face, vertex_index % 6);
+        block_id = extractBits(face.y, 16u, 16u);
+        texture = faceTexture(block_id, face_index);
+    }
+
+    let pos = clip_from_world * vec4(face_vertex.pos, 1.0);
+
+    return VertexOutput(
+        pos,
+        face_vertex.norm,
+        faceColor(face, block_id),
+        texture,
+        face_vertex.uv,
+        pos.w,
+        face.z,
+        face_vertex.corner,
+        face_vertex.pos,
+    );
 }
 
-const SUN = vec3(0.2, 0.8, 0.5);
 const AMBIENT = 0.3;
 const DIFFUSE = 0.7;
-const BLOCK_COLOR = vec3(1.0, 1.0, 1.0);
+// Light taken away by each level of ambient occlusion.
+const OCCLUSION = 0.2;
+// Color of the light given off by blocks such as torches and lava.
+const BLOCK_LIGHT = vec3(1.0, 0.9, 0.75);
+// Light left in the darkest places, so that they aren't pitch black.
+const MIN_LIGHT = 0.03;
+const MAX_LIGHT_LEVEL = 15.0;
+
+// Ambient occlusion interpolated between the levels of a face's four corners,
+// packed 2 bits each by `face_ao` in `region.rs`.
+fn ambientOcclusion(vertex: VertexOutput) -> f32 {
+    let levels = vec4<f32>(vec4(
+        extractBits(vertex.shading, 0u, 2u),
+        extractBits(vertex.shading, 2u, 2u),
+        extractBits(vertex.shading, 4u, 2u),
+        extractBits(vertex.shading, 6u, 2u),
+    ));
+
+    let level = mix(
+        mix(levels.x, levels.y, vertex.corner.x),
+        mix(levels.z, levels.w, vertex.corner.x),
+        vertex.corner.y,
+    );
+
+    return 1.0 - OCCLUSION * level;
+}
+
+// Brightness of a light level, falling off like Minecraft's from 1 at the
+// highest level.
+fn lightLevelBrightness(level: u32) -> f32 {
+    let fraction = f32(level) / MAX_LIGHT_LEVEL;
+    return fraction / (4.0 - 3.0 * fraction);
+}
+
+// How much of a fragment the sun reaches, from 0 when it is in the shadow of
+// the faces drawn into the cascade covering it to 1. Fragments beyond the last
+// cascade are always reached.
+fn sunVisibility(vertex: VertexOutput) -> f32 {
+    var cascade = 0u;
+    while cascade < CASCADES && vertex.view_depth > shadow_view.distances[cascade] {
+        cascade++;
+    }
+
+    if cascade == CASCADES {
+        return 1.0;
+    }
+
+    // Moved a texel off the face, so that the texels blended by the filtered
+    // comparison don't reach into the block behind it.
+    let pos = fma(vertex.norm, vec3(shadow_view.texel_sizes[cascade]), vertex.world_pos);
+    let clip = shadow_view.clip_from_world[cascade] * vec4(pos, 1.0);
+    let uv = fma(clip.xy, vec2(0.5, -0.5), vec2(0.5));
+
+    return textureSampleCompareLevel(shadow_maps, shadow_sampler, uv, cascade, clip.z);
+}
+
+// Light reaching a face from the sky, scaled by `daylight` and lit by the sun
+// where nothing shadows it, or from the blocks around it, whichever is
+// brighter.
+fn faceLight(vertex: VertexOutput) -> vec3<f32> {
+    var sun = max(0.0, dot(vertex.norm, shadow_view.sun));
+    if sun > 0.0 {
+        sun *= sunVisibility(vertex);
+    }
+
+    let block = lightLevelBrightness(extractBits(vertex.shading, 8u, 4u));
+    let sky = lightLevelBrightness(extractBits(vertex.shading, 12u, 4u)) * daylight;
+
+    let light = max(vec3(sky * (AMBIENT + DIFFUSE * sun)), block * BLOCK_LIGHT);
+    return max(light, vec3(MIN_LIGHT)) * ambientOcclusion(vertex);
+}
+
+// Unlit color of a fragment, with the alpha of its texture or, for untextured
+// faces, of its block color.
+fn blockColor(vertex: VertexOutput) -> vec4<f32> {
+    var block_color = unpack4x8unorm(vertex.color);
+
+    if vertex.texture != NO_TEXTURE {
+        let layer = extractBits(vertex.texture, 0u, TINTED_BIT);
+        let texel = textureSampleLevel(block_textures, block_sampler, vertex.uv, layer, 0.0);
+
+        let tint = select(vec3(1.0), block_color.rgb, bool(extractBits(vertex.texture, TINTED_BIT, 1u)));
+        block_color = vec4(texel.rgb * tint, texel.a);
+    }
+
+    return block_color;
+}
+
+// Matches `skyColor` in `sky.wgsl`.
+fn skyColor(dir: vec3<f32>) -> vec3<f32> {
+    return mix(sky.horizon, sky.zenith, smoothstep(-0.1, 0.7, dir.y));
+}
+
+// Fades a color seen at `pos` into the sky behind it the farther away it is
+// along the ground, like the draw distance, so that what is drawn farthest
+// away blends into the background. The fog is squared exponential, leaving
+// what is near clear.
+fn fog(color: vec3<f32>, pos: vec3<f32>) -> vec3<f32> {
+    let to_pos = pos - sky.eye;
+    let depth = sky.fog_density * length(to_pos.xz);
+
+    return mix(skyColor(normalize(to_pos)), color, exp(-depth * depth));
+}
+
+fn shade(vertex: VertexOutput) -> vec4<f32> {
+    let block_color = blockColor(vertex);
+    let color = fog(faceLight(vertex) * block_color.rgb, vertex.world_pos);
+
+    return vec4(color, block_color.a);
+}
 
 @fragment
 fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
-    let sun = max(0.0, dot(vertex.norm, normalize(SUN)));
-    let color = (AMBIENT + DIFFUSE * sun) * BLOCK_COLOR;
+    let color = shade(vertex);
+
+    // Cutout textures such as leaves and flowers.
+    if color.a < 0.5 {
+        discard;
+    }
+
+    return vec4(color.rgb, 1.0);
+}
+
+// Leaves the holes of cutout textures out of the shadow maps.
+@fragment
+fn fs_shadow(vertex: VertexOutput) {
+    if blockColor(vertex).a < 0.5 {
+        discard;
+    }
+}
+
+struct TranslucentOutput {
+    @location(0) accum: vec4<f32>,
+    @location(1) revealage: f32,
+}
+
+// Weighted blended order-independent transparency: translucent fragments are
+// summed up weighted by their distance, then resolved by `composite.wgsl`.
+@fragment
+fn fs_translucent(vertex: VertexOutput) -> TranslucentOutput {
+    let color = shade(vertex);
+
+    let depth = vertex.view_depth;
+    let weight = color.a * clamp(
+        10.0 / (1e-5 + pow(depth / 5.0, 2.0) + pow(depth / 200.0, 6.0)),
+        1e-2,
+        3e3,
+    );
 
-    return vec4(color, 1.0);
+    return TranslucentOutput(vec4(color.rgb * color.a, color.a) * weight, color.a);
 }
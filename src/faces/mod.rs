@@ -2,10 +2,299 @@ use std::{borrow::Cow, mem, num::NonZero};
 
 use wgpu::util::DeviceExt;
 
+use crate::{
+    models::ModelBuffers,
+    renderer::DEPTH_FORMAT,
+    resource_pack::{BlockTextures, NO_TEXTURE, TINTED},
+    shadows::{ShadowMaps, SHADOW_MAP_FORMAT},
+    world::{self, WorldBuffers},
+};
+
+/// Block textures uploaded as a texture array, along with the layer used by each
+/// face of every block. Extended as textures are added.
+#[derive(Debug)]
+pub struct FaceTextures {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    face_texture_buffer: wgpu::Buffer,
+    /// Number of layers and faces uploaded so far.
+    layers_len: usize,
+    faces_len: usize,
+}
+
+impl FaceTextures {
+    /// Creates empty face textures, uploaded by [`FaceTextures::update`].
+    pub fn new(device: &wgpu::Device) -> Self {
+        let texture = create_texture_array(device, 1, 2);
+        Self {
+            view: create_texture_array_view(&texture),
+            texture,
+            sampler: device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("block_sampler"),
+                // Faces merged by greedy meshing repeat their texture.
+                address_mode_u: wgpu::AddressMode::Repeat,
+                address_mode_v: wgpu::AddressMode::Repeat,
+                ..Default::default()
+            }),
+            // Storage buffers can't be empty.
+            face_texture_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("face_texture_buffer"),
+                size: 256 * mem::size_of::<[u16; 6]>() as u64,
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_SRC
+                    | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            layers_len: 0,
+            faces_len: 0,
+        }
+    }
+
+    /// Uploads the layers and faces added to `textures` since the last call,
+    /// only reallocating the texture array when it runs out of layers or its
+    /// layers change size. Returns the device's layer limit when `textures`
+    /// first go past it, the faces using the layers past it being untextured.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        textures: &BlockTextures,
+    ) -> Option<u32> {
+        let max_layers = device.limits().max_texture_array_layers as usize;
+        let over_limit = (textures.layers.len() > max_layers && self.layers_len < max_layers)
+            .then_some(max_layers as u32);
+
+        let layers_len = textures.layers.len().min(max_layers);
+        let size = self.texture.width();
+        if textures.size != size || layers_len > self.texture.depth_or_array_layers() as usize {
+            // The GL backend guesses a texture's view dimension from its layer
+            // count, taking 1 layer for a plain 2D texture and multiples of 6
+            // for cube maps, which powers of two from 2 on never are.
+            let capacity = layers_len.next_power_of_two().clamp(2, max_layers);
+            self.texture = create_texture_array(device, textures.size, capacity as u32);
+            self.view = create_texture_array_view(&self.texture);
+            self.layers_len = 0;
+        }
+
+        let new_layers = &textures.layers[self.layers_len..layers_len];
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    z: self.layers_len as u32,
+                    ..Default::default()
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &new_layers.concat(),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(textures.size * 4),
+                rows_per_image: Some(textures.size),
+            },
+            wgpu::Extent3d {
+                width: textures.size,
+                height: textures.size,
+                depth_or_array_layers: new_layers.len() as u32,
+            },
+        );
+        self.layers_len = layers_len;
+
+        let faces: Vec<_> = textures.faces[self.faces_len..]
+            .iter()
+            .map(|faces| {
+                faces.map(|layer| {
+                    if layer != NO_TEXTURE && (layer & !TINTED) as usize >= max_layers {
+                        NO_TEXTURE
+                    } else {
+                        layer
+                    }
+                })
+            })
+            .collect();
+
+        let face_size = mem::size_of::<[u16; 6]>() as u64;
+        world::grow(
+            device,
+            queue,
+            &mut self.face_texture_buffer,
+            "face_texture_buffer",
+            textures.faces.len() as u64 * face_size,
+        );
+        queue.write_buffer(
+            &self.face_texture_buffer,
+            self.faces_len as u64 * face_size,
+            bytemuck::cast_slice(&faces),
+        );
+        self.faces_len = textures.faces.len();
+
+        over_limit
+    }
+}
+
+fn create_texture_array(device: &wgpu::Device, size: u32, layers: u32) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("block_textures"),
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: layers,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    })
+}
+
+fn create_texture_array_view(texture: &wgpu::Texture) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("block_textures_view"),
+        dimension: Some(wgpu::TextureViewDimension::D2Array),
+        ..Default::default()
+    })
+}
+
+/// Block colors uploaded as storage buffers, along with the [`Tint`](crate::biomes::Tint) of every
+/// block and the colors each biome tints them with. Extended as blocks are
+/// added.
+#[derive(Debug)]
+pub struct FaceColors {
+    pub block_color_buffer: wgpu::Buffer,
+    pub block_tint_buffer: wgpu::Buffer,
+    pub biome_color_buffer: wgpu::Buffer,
+    /// Number of blocks uploaded so far.
+    blocks_len: usize,
+}
+
+impl FaceColors {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        colors: &[u32],
+        tints: &[u32],
+        biomes: &[[u32; 3]],
+    ) -> Self {
+        let buffer = |label| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                // Storage buffers can't be empty.
+                size: 256 * mem::size_of::<u32>() as u64,
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_SRC
+                    | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+
+        let mut face_colors = Self {
+            block_color_buffer: buffer("block_color_buffer"),
+            block_tint_buffer: buffer("block_tint_buffer"),
+            biome_color_buffer: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("biome_color_buffer"),
+                contents: bytemuck::cast_slice(biomes),
+                usage: wgpu::BufferUsages::STORAGE,
+            }),
+            blocks_len: 0,
+        };
+        face_colors.update(device, queue, colors, tints);
+
+        face_colors
+    }
+
+    /// Uploads the colors and tints of the blocks added since the last call,
+    /// growing the buffers when they run out of room.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        colors: &[u32],
+        tints: &[u32],
+    ) {
+        let size = colors.len() as u64 * mem::size_of::<u32>() as u64;
+        let offset = self.blocks_len as u64 * mem::size_of::<u32>() as u64;
+
+        for (buffer, label, values) in [
+            (&mut self.block_color_buffer, "block_color_buffer", colors),
+            (&mut self.block_tint_buffer, "block_tint_buffer", tints),
+        ] {
+            world::grow(device, queue, buffer, label, size);
+            queue.write_buffer(
+                buffer,
+                offset,
+                bytemuck::cast_slice(&values[self.blocks_len..]),
+            );
+        }
+
+        self.blocks_len = colors.len();
+    }
+}
+
+const ACCUM_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const REVEALAGE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+
+/// Screen-sized targets translucent faces are accumulated into before being
+/// composited over the opaque ones.
+#[derive(Debug)]
+pub struct TranslucentTargets {
+    accum_view: wgpu::TextureView,
+    revealage_view: wgpu::TextureView,
+}
+
+impl TranslucentTargets {
+    pub fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let create_view = |label, format| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width,
+                        height,
+                        ..Default::default()
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+
+        Self {
+            accum_view: create_view("accum_texture", ACCUM_FORMAT),
+            revealage_view: create_view("revealage_texture", REVEALAGE_FORMAT),
+        }
+    }
+}
+
+/// The bind groups a frame's opaque faces were drawn with, returned by
+/// [`FacesPipeline::encode_opaque`] to draw the translucent ones with.
+#[derive(Debug)]
+pub struct FacesBindGroups {
+    /// One for each of [`WorldBuffers::face_pages`].
+    faces: Vec<wgpu::BindGroup>,
+    shadows: wgpu::BindGroup,
+}
+
 #[derive(Debug)]
 pub struct FacesPipeline {
     bind_group_layout: wgpu::BindGroupLayout,
+    shadow_bind_group_layout: wgpu::BindGroupLayout,
     pipeline: wgpu::RenderPipeline,
+    translucent_pipeline: wgpu::RenderPipeline,
+    shadow_pipeline: wgpu::RenderPipeline,
+    composite_bind_group_layout: wgpu::BindGroupLayout,
+    composite_pipeline: wgpu::RenderPipeline,
+    clip_from_world_buffer: wgpu::Buffer,
+    origin_buffer: wgpu::Buffer,
+    daylight_buffer: wgpu::Buffer,
 }
 
 impl FacesPipeline {
@@ -40,12 +329,165 @@ impl FacesPipeline {
                     },
                     count: None,
                 },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: Some(
+                            NonZero::new(mem::size_of::<glam::IVec3>() as u64).unwrap(),
+                        ),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 6,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 7,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 8,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 9,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: Some(NonZero::new(mem::size_of::<f32>() as u64).unwrap()),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 10,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 11,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 12,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
             ],
         });
 
+        // Kept apart from the rest so that shadow maps can be drawn with the
+        // same shader while they and the sky aren't bound.
+        let shadow_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("faces_shadow_bind_group_layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Depth,
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("faces_pipeline_layout"),
-            bind_group_layouts: &[&bind_group_layout],
+            bind_group_layouts: &[&bind_group_layout, &shadow_bind_group_layout],
             push_constant_ranges: &[],
         });
 
@@ -66,7 +508,99 @@ impl FacesPipeline {
             }),
             primitive: wgpu::PrimitiveState::default(),
             depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth24Plus,
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::GreaterEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let translucent_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("translucent_faces_render_pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "vs_main",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: "fs_translucent",
+                compilation_options: Default::default(),
+                targets: &[
+                    Some(wgpu::ColorTargetState {
+                        format: ACCUM_FORMAT,
+                        blend: Some(wgpu::BlendState {
+                            color: wgpu::BlendComponent {
+                                src_factor: wgpu::BlendFactor::One,
+                                dst_factor: wgpu::BlendFactor::One,
+                                operation: wgpu::BlendOperation::Add,
+                            },
+                            alpha: wgpu::BlendComponent {
+                                src_factor: wgpu::BlendFactor::One,
+                                dst_factor: wgpu::BlendFactor::One,
+                                operation: wgpu::BlendOperation::Add,
+                            },
+                        }),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }),
+                    Some(wgpu::ColorTargetState {
+                        format: REVEALAGE_FORMAT,
+                        blend: Some(wgpu::BlendState {
+                            color: wgpu::BlendComponent {
+                                src_factor: wgpu::BlendFactor::Zero,
+                                dst_factor: wgpu::BlendFactor::OneMinusSrc,
+                                operation: wgpu::BlendOperation::Add,
+                            },
+                            alpha: wgpu::BlendComponent::REPLACE,
+                        }),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }),
+                ],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::GreaterEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let shadow_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("faces_shadow_pipeline_layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let shadow_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("shadow_faces_render_pipeline"),
+            layout: Some(&shadow_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "vs_main",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: "fs_shadow",
+                compilation_options: Default::default(),
+                targets: &[],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: SHADOW_MAP_FORMAT,
                 depth_write_enabled: true,
                 depth_compare: wgpu::CompareFunction::GreaterEqual,
                 stencil: wgpu::StencilState::default(),
@@ -77,39 +611,135 @@ impl FacesPipeline {
             cache: None,
         });
 
+        let composite_shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("composite_shader_module"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("composite.wgsl"))),
+        });
+
+        let composite_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("composite_render_pipeline"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &composite_shader_module,
+                entry_point: "vs_main",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &composite_shader_module,
+                entry_point: "fs_main",
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: swapchain_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let composite_bind_group_layout = composite_pipeline.get_bind_group_layout(0);
+
+        let clip_from_world_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("clip_from_world_buffer"),
+            size: mem::size_of::<glam::Mat4>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let origin_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("origin_buffer"),
+            size: mem::size_of::<glam::IVec4>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let daylight_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("daylight_buffer"),
+            size: mem::size_of::<glam::Vec4>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
         Self {
             bind_group_layout,
+            shadow_bind_group_layout,
             pipeline,
+            translucent_pipeline,
+            shadow_pipeline,
+            composite_bind_group_layout,
+            composite_pipeline,
+            clip_from_world_buffer,
+            origin_buffer,
+            daylight_buffer,
         }
     }
 
-    pub fn encode(
+    /// Draws the opaque faces, clearing the color and depth targets, and returns
+    /// the bind groups to draw the translucent ones with. Sky light is scaled by
+    /// `daylight`, from 0 at night to 1 at noon, sunlight is shadowed by what
+    /// was drawn into `shadow_maps`, and faces fade into the sky of
+    /// `sky_view_buffer` with distance.
+    #[allow(clippy::too_many_arguments)]
+    pub fn encode_opaque(
         &self,
         device: &wgpu::Device,
+        queue: &wgpu::Queue,
         encoder: &mut wgpu::CommandEncoder,
-        face_buffer: &wgpu::Buffer,
+        world: &WorldBuffers,
+        origin: glam::IVec3,
+        colors: &FaceColors,
+        textures: &FaceTextures,
+        models: &ModelBuffers,
         clip_from_world: glam::Mat4,
+        daylight: f32,
+        shadow_maps: &ShadowMaps,
+        sky_view_buffer: &wgpu::Buffer,
         draw_indirect_buffer: &wgpu::Buffer,
         color_view: &wgpu::TextureView,
         depth_view: &wgpu::TextureView,
-    ) {
-        let clip_from_world_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("clip_from_world_buffer"),
-            contents: bytemuck::cast_slice(clip_from_world.as_ref()),
-            usage: wgpu::BufferUsages::UNIFORM,
-        });
+    ) -> FacesBindGroups {
+        queue.write_buffer(
+            &self.clip_from_world_buffer,
+            0,
+            bytemuck::cast_slice(clip_from_world.as_ref()),
+        );
+        queue.write_buffer(
+            &self.origin_buffer,
+            0,
+            bytemuck::cast_slice(origin.extend(0).as_ref()),
+        );
+        queue.write_buffer(&self.daylight_buffer, 0, bytemuck::bytes_of(&daylight));
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("faces_bind_group"),
-            layout: &self.bind_group_layout,
+        let bind_groups = self.create_bind_groups(
+            device,
+            world,
+            colors,
+            textures,
+            models,
+            &self.clip_from_world_buffer,
+        );
+        let shadow_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("faces_shadow_bind_group"),
+            layout: &self.shadow_bind_group_layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: face_buffer.as_entire_binding(),
+                    resource: wgpu::BindingResource::TextureView(&shadow_maps.view),
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: clip_from_world_buffer.as_entire_binding(),
+                    resource: wgpu::BindingResource::Sampler(&shadow_maps.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: shadow_maps.view_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: sky_view_buffer.as_entire_binding(),
                 },
             ],
         });
@@ -138,9 +768,271 @@ impl FacesPipeline {
             });
 
             pass.set_pipeline(&self.pipeline);
-            pass.set_bind_group(0, &bind_group, &[]);
+            pass.set_bind_group(1, &shadow_bind_group, &[]);
+
+            for (page, bind_group) in bind_groups.iter().enumerate() {
+                pass.set_bind_group(0, bind_group, &[]);
+                pass.draw_indirect(draw_indirect_buffer, draw_offset(page, false));
+            }
+        }
+
+        FacesBindGroups {
+            faces: bind_groups,
+            shadows: shadow_bind_group,
+        }
+    }
 
-            pass.draw_indirect(draw_indirect_buffer, 0);
+    /// Draws the opaque faces generated for a cascade into its shadow map,
+    /// seen from the sun.
+    #[allow(clippy::too_many_arguments)]
+    pub fn encode_shadow(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        world: &WorldBuffers,
+        origin: glam::IVec3,
+        colors: &FaceColors,
+        textures: &FaceTextures,
+        models: &ModelBuffers,
+        shadow_maps: &ShadowMaps,
+        cascade: usize,
+        draw_indirect_buffer: &wgpu::Buffer,
+    ) {
+        queue.write_buffer(
+            &self.origin_buffer,
+            0,
+            bytemuck::cast_slice(origin.extend(0).as_ref()),
+        );
+
+        let bind_groups = self.create_bind_groups(
+            device,
+            world,
+            colors,
+            textures,
+            models,
+            &shadow_maps.clip_from_world_buffers[cascade],
+        );
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("shadow_faces_pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &shadow_maps.layer_views[cascade],
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(0.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        pass.set_pipeline(&self.shadow_pipeline);
+
+        for (page, bind_group) in bind_groups.iter().enumerate() {
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.draw_indirect(draw_indirect_buffer, draw_offset(page, false));
         }
     }
+
+    /// Draws the translucent faces over what has been drawn into `color_view`
+    /// so far, with the bind groups returned by [`FacesPipeline::encode_opaque`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn encode_translucent(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        bind_groups: &FacesBindGroups,
+        draw_indirect_buffer: &wgpu::Buffer,
+        color_view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+        translucent_targets: &TranslucentTargets,
+    ) {
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("translucent_faces_pass"),
+                color_attachments: &[
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: &translucent_targets.accum_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    }),
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: &translucent_targets.revealage_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    }),
+                ],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            pass.set_pipeline(&self.translucent_pipeline);
+            pass.set_bind_group(1, &bind_groups.shadows, &[]);
+
+            for (page, bind_group) in bind_groups.faces.iter().enumerate() {
+                pass.set_bind_group(0, bind_group, &[]);
+                pass.draw_indirect(draw_indirect_buffer, draw_offset(page, true));
+            }
+        }
+
+        let composite_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("composite_bind_group"),
+            layout: &self.composite_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&translucent_targets.accum_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(
+                        &translucent_targets.revealage_view,
+                    ),
+                },
+            ],
+        });
+
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("composite_pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: color_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            pass.set_pipeline(&self.composite_pipeline);
+            pass.set_bind_group(0, &composite_bind_group, &[]);
+
+            pass.draw(0..3, 0..1);
+        }
+    }
+
+    /// Creates a bind group for each face page.
+    fn create_bind_groups(
+        &self,
+        device: &wgpu::Device,
+        world: &WorldBuffers,
+        colors: &FaceColors,
+        textures: &FaceTextures,
+        models: &ModelBuffers,
+        clip_from_world_buffer: &wgpu::Buffer,
+    ) -> Vec<wgpu::BindGroup> {
+        world
+            .face_pages
+            .iter()
+            .map(|page| {
+                self.create_bind_group(
+                    device,
+                    world,
+                    &page.buffer,
+                    colors,
+                    textures,
+                    models,
+                    clip_from_world_buffer,
+                )
+            })
+            .collect()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create_bind_group(
+        &self,
+        device: &wgpu::Device,
+        world: &WorldBuffers,
+        face_buffer: &wgpu::Buffer,
+        colors: &FaceColors,
+        textures: &FaceTextures,
+        models: &ModelBuffers,
+        clip_from_world_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("faces_bind_group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: face_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: clip_from_world_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: world.chunk_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: self.origin_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: colors.block_color_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(&textures.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: wgpu::BindingResource::Sampler(&textures.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: textures.face_texture_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 8,
+                    resource: models.quad_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 9,
+                    resource: self.daylight_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 10,
+                    resource: world.biome_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 11,
+                    resource: colors.block_tint_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 12,
+                    resource: colors.biome_color_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+}
+
+/// Offset in the draw indirect buffer of the opaque or translucent draw of a
+/// face page, written by `writeVertexCount` in `blocks.wgsl`.
+fn draw_offset(page: usize, translucent: bool) -> u64 {
+    (page * 2 + translucent as usize) as u64 * mem::size_of::<wgpu::util::DrawIndirectArgs>() as u64
 }
@@ -0,0 +1,83 @@
+@group(0)
+@binding(0)
+var depth_texture: texture_2d<f32>;
+@group(0)
+@binding(1)
+var nearest_sampler: sampler;
+@group(0)
+@binding(2)
+var<storage, read_write> pyramid: array<f32>;
+@group(0)
+@binding(3)
+var<uniform> pyramid_view: PyramidView;
+@group(0)
+@binding(4)
+var<uniform> level: u32;
+
+const WORKGROUP_SIZE = 8u;
+
+struct PyramidView {
+    clip_from_world: mat4x4<f32>,
+    origin: vec3<i32>,
+    levels: u32,
+    size: vec2<u32>,
+}
+
+// Levels are halved and rounded down like mipmaps, and stored one after the
+// other starting from the full-size one.
+fn levelSize(level: u32) -> vec2<u32> {
+    return max(pyramid_view.size >> vec2(level), vec2(1u));
+}
+
+fn levelOffset(level: u32) -> u32 {
+    var offset = 0u;
+    for (var i = 0u; i < level; i++) {
+        let size = levelSize(i);
+        offset += size.x * size.y;
+    }
+
+    return offset;
+}
+
+// The depth texture is bound as a plain float texture and read through a
+// nearest sampler, which is the only way the GL backend reads depth values.
+@compute
+@workgroup_size(WORKGROUP_SIZE, WORKGROUP_SIZE)
+fn copyDepth(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let size = pyramid_view.size;
+    if any(global_id.xy >= size) {
+        return;
+    }
+
+    let coords = (vec2<f32>(global_id.xy) + vec2(0.5)) / vec2<f32>(size);
+    let depth = textureSampleLevel(depth_texture, nearest_sampler, coords, 0.0).r;
+
+    pyramid[global_id.y * size.x + global_id.x] = depth;
+}
+
+// Keeps the farthest depth of the texels below, which with reverse Z is the
+// smallest. Each texel covers 2×2 texels, and 3 along the last row or column
+// when the level below is odd-sized.
+@compute
+@workgroup_size(WORKGROUP_SIZE, WORKGROUP_SIZE)
+fn reduceDepth(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let size = levelSize(level);
+    if any(global_id.xy >= size) {
+        return;
+    }
+
+    let src_size = levelSize(level - 1);
+    let src_offset = levelOffset(level - 1);
+
+    let start = global_id.xy * 2;
+    let end = select(start + 1, src_size - 1, global_id.xy == size - 1);
+
+    var depth = 1.0;
+    for (var y = start.y; y <= end.y; y++) {
+        for (var x = start.x; x <= end.x; x++) {
+            depth = min(depth, pyramid[src_offset + y * src_size.x + x]);
+        }
+    }
+
+    pyramid[levelOffset(level) + global_id.y * size.x + global_id.x] = depth;
+}
@@ -0,0 +1,262 @@
+use std::{borrow::Cow, mem};
+
+use wgpu::util::DeviceExt;
+
+const WORKGROUP_SIZE: u32 = 8;
+
+/// The view a [`DepthPyramid`] was built from, laid out like `PyramidView` in
+/// `hiz.wgsl` and `chunks.wgsl`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct PyramidView {
+    clip_from_world: glam::Mat4,
+    origin: glam::IVec3,
+    /// 0 until the pyramid has been built once.
+    levels: u32,
+    size: glam::UVec2,
+    _padding: [u32; 2],
+}
+
+/// Mip chain of the last frame's depth, each texel holding the farthest depth
+/// of the texels it covers, along with the view it was seen from. Chunks are
+/// tested against it in the next frame.
+///
+/// The levels are stored one after the other in a buffer rather than as the
+/// mipmaps of a texture, which the GL backend can't write one level of while
+/// reading another.
+#[derive(Debug)]
+pub struct DepthPyramid {
+    size: glam::UVec2,
+    pub pyramid_buffer: wgpu::Buffer,
+    level_buffers: Vec<wgpu::Buffer>,
+    /// Copied to `view_buffer` once the pyramid is built, as chunks are culled
+    /// against the previous frame's until then.
+    frame_buffer: wgpu::Buffer,
+    pub view_buffer: wgpu::Buffer,
+}
+
+impl DepthPyramid {
+    pub fn new(device: &wgpu::Device, size: glam::UVec2) -> Self {
+        let levels = size.max_element().max(1).ilog2() + 1;
+        let len: u32 = (0..levels)
+            .map(|level| level_size(size, level).element_product())
+            .sum();
+
+        let pyramid_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("pyramid_buffer"),
+            size: len as u64 * mem::size_of::<f32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let level_buffers = (0..levels)
+            .map(|level| {
+                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("pyramid_level_buffer"),
+                    contents: bytemuck::bytes_of(&level),
+                    usage: wgpu::BufferUsages::UNIFORM,
+                })
+            })
+            .collect();
+
+        let buffer = |label, usage| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: mem::size_of::<PyramidView>() as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST | usage,
+                mapped_at_creation: false,
+            })
+        };
+
+        Self {
+            size,
+            pyramid_buffer,
+            level_buffers,
+            frame_buffer: buffer("pyramid_frame_buffer", wgpu::BufferUsages::COPY_SRC),
+            view_buffer: buffer("pyramid_view_buffer", wgpu::BufferUsages::empty()),
+        }
+    }
+
+    fn levels(&self) -> u32 {
+        self.level_buffers.len() as u32
+    }
+}
+
+fn level_size(size: glam::UVec2, level: u32) -> glam::UVec2 {
+    (size >> level).max(glam::UVec2::ONE)
+}
+
+#[derive(Debug)]
+pub struct HizPipeline {
+    bind_group_layout: wgpu::BindGroupLayout,
+    copy_depth_pipeline: wgpu::ComputePipeline,
+    reduce_depth_pipeline: wgpu::ComputePipeline,
+    nearest_sampler: wgpu::Sampler,
+}
+
+impl HizPipeline {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("hiz_shader_module"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("hiz.wgsl"))),
+        });
+
+        let buffer_entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        // The depth texture can't be filtered, which a layout derived from the
+        // shader would assume.
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("hiz_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
+                    count: None,
+                },
+                buffer_entry(2, wgpu::BufferBindingType::Storage { read_only: false }),
+                buffer_entry(3, wgpu::BufferBindingType::Uniform),
+                buffer_entry(4, wgpu::BufferBindingType::Uniform),
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("hiz_pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = |label, entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &shader_module,
+                entry_point,
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                cache: None,
+            })
+        };
+
+        let nearest_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("nearest_sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            ..Default::default()
+        });
+
+        Self {
+            copy_depth_pipeline: pipeline("copy_depth_pipeline", "copyDepth"),
+            reduce_depth_pipeline: pipeline("reduce_depth_pipeline", "reduceDepth"),
+            bind_group_layout,
+            nearest_sampler,
+        }
+    }
+
+    /// Builds `pyramid` from the depth of the frame seen through
+    /// `clip_from_world`, once everything opaque has been drawn.
+    #[allow(clippy::too_many_arguments)]
+    pub fn encode(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        depth_view: &wgpu::TextureView,
+        pyramid: &DepthPyramid,
+        origin: glam::IVec3,
+        clip_from_world: glam::Mat4,
+    ) {
+        let frame = PyramidView {
+            clip_from_world,
+            origin,
+            levels: pyramid.levels(),
+            size: pyramid.size,
+            ..Default::default()
+        };
+        queue.write_buffer(&pyramid.frame_buffer, 0, bytemuck::bytes_of(&frame));
+
+        let bind_groups: Vec<_> = pyramid
+            .level_buffers
+            .iter()
+            .map(|level_buffer| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("hiz_bind_group"),
+                    layout: &self.bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(depth_view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&self.nearest_sampler),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: pyramid.pyramid_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 3,
+                            resource: pyramid.frame_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 4,
+                            resource: level_buffer.as_entire_binding(),
+                        },
+                    ],
+                })
+            })
+            .collect();
+
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("hiz_pass"),
+                timestamp_writes: None,
+            });
+
+            for (level, bind_group) in (0..).zip(&bind_groups) {
+                let size = level_size(pyramid.size, level);
+
+                // The first level is copied from the depth texture, the others
+                // reduced from the level before them.
+                let pipeline = match level {
+                    0 => &self.copy_depth_pipeline,
+                    _ => &self.reduce_depth_pipeline,
+                };
+
+                pass.set_pipeline(pipeline);
+                pass.set_bind_group(0, bind_group, &[]);
+                pass.dispatch_workgroups(
+                    size.x.div_ceil(WORKGROUP_SIZE),
+                    size.y.div_ceil(WORKGROUP_SIZE),
+                    1,
+                );
+            }
+        }
+
+        encoder.copy_buffer_to_buffer(
+            &pyramid.frame_buffer,
+            0,
+            &pyramid.view_buffer,
+            0,
+            mem::size_of::<PyramidView>() as u64,
+        );
+    }
+}
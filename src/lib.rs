@@ -0,0 +1,35 @@
+//! Renders Minecraft worlds on the GPU.
+//!
+//! A [`World`] is loaded from its region files, then drawn by a [`Renderer`]
+//! into any texture view of a caller-supplied wgpu device.
+
+mod biomes;
+mod blocks;
+mod camera;
+mod chunks;
+mod colors;
+mod faces;
+mod hiz;
+mod light;
+mod models;
+mod opacity;
+mod palette;
+mod readback;
+mod region;
+mod renderer;
+mod resource_pack;
+mod shadows;
+mod sky;
+mod tiles;
+mod world;
+
+pub use crate::{
+    camera::Camera,
+    chunks::CullCounts,
+    colors::ColorOverrides,
+    region::{LoadOptions, RegionError},
+    renderer::{render_to_png, BlockAssets, Renderer, Skipped, DEFAULT_SUN_ANGLE},
+    resource_pack::ResourcePack,
+    sky::Sky,
+    world::{OutOfMemory, World, MAX_FAR_DISTANCE},
+};
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+
+use fastnbt::ByteArray;
+
+/// Highest light level, of the sky in the open and of the brightest light
+/// sources.
+pub const MAX_LIGHT: u8 = 15;
+
+/// The light levels stored with a chunk, as last computed by the game.
+#[derive(Debug, Default)]
+pub struct ChunkLight {
+    sections: HashMap<i32, LightSection>,
+    /// Highest section with sky light.
+    top: i32,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct LightSection {
+    #[serde(rename = "Y")]
+    y: i8,
+    #[serde(rename = "BlockLight")]
+    block_light: Option<ByteArray>,
+    #[serde(rename = "SkyLight")]
+    sky_light: Option<ByteArray>,
+}
+
+/// The part of a chunk holding its light, either at the top level since 1.18
+/// or inside `Level` before.
+#[derive(serde::Deserialize)]
+struct ChunkLightNbt {
+    sections: Option<Vec<LightSection>>,
+    #[serde(rename = "Level")]
+    level: Option<LevelLightNbt>,
+}
+
+#[derive(serde::Deserialize)]
+struct LevelLightNbt {
+    #[serde(rename = "Sections")]
+    sections: Option<Vec<LightSection>>,
+}
+
+impl ChunkLight {
+    /// Reads the light of a chunk from its decoded NBT. Chunks without light,
+    /// e.g. because the game never lit them, are taken to be lit by the sky
+    /// everywhere.
+    pub fn from_nbt(nbt: &fastnbt::Value) -> Result<Self, fastnbt::error::Error> {
+        let nbt: ChunkLightNbt = fastnbt::from_value(nbt)?;
+
+        let sections: HashMap<_, _> = nbt
+            .sections
+            .or(nbt.level.and_then(|level| level.sections))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|section| (section.y as i32, section))
+            .collect();
+        let top = sections
+            .values()
+            .filter(|section| section.sky_light.is_some())
+            .map(|section| section.y as i32)
+            .max()
+            .unwrap_or(i32::MIN);
+
+        Ok(Self { sections, top })
+    }
+
+    /// `(sky, block)` light levels at block `key` of the chunk, with `x` and `z`
+    /// in `0..16`. Sections the game stored no sky light for take the light
+    /// coming down from the section above them, like the game does.
+    pub fn get(&self, key: glam::IVec3) -> (u8, u8) {
+        let section_y = key.y.div_euclid(16);
+        let index = ((key.y.rem_euclid(16) * 16 + key.z) * 16 + key.x) as usize;
+        let section = self.sections.get(&section_y);
+
+        let block = section
+            .and_then(|section| nibble(section.block_light.as_ref(), index))
+            .unwrap_or(0);
+
+        let sky = match section.and_then(|section| nibble(section.sky_light.as_ref(), index)) {
+            Some(sky) => sky,
+            None if section_y < self.top => {
+                // The bottom layer of the next section with sky light.
+                let bottom = (key.z * 16 + key.x) as usize;
+
+                (section_y + 1..=self.top)
+                    .find_map(|y| {
+                        let section = self.sections.get(&y)?;
+                        nibble(section.sky_light.as_ref(), bottom)
+                    })
+                    .unwrap_or(MAX_LIGHT)
+            }
+            None => MAX_LIGHT,
+        };
+
+        (sky, block)
+    }
+}
+
+/// Light level at `index` of a nibble array of a section's 4096 blocks, `None`
+/// if there is no array or it is malformed.
+fn nibble(array: Option<&ByteArray>, index: usize) -> Option<u8> {
+    let array = array.filter(|array| array.len() == 2048)?;
+
+    Some((array[index / 2] as u8 >> (index % 2 * 4)) & 0b1111)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A nibble array with `level` at `index` and 0 everywhere else.
+    fn nibbles(index: usize, level: u8) -> ByteArray {
+        let mut array = vec![0; 2048];
+        array[index / 2] = (level << (index % 2 * 4)) as i8;
+
+        ByteArray::new(array)
+    }
+
+    fn section(
+        y: i8,
+        block_light: Option<ByteArray>,
+        sky_light: Option<ByteArray>,
+    ) -> LightSection {
+        LightSection {
+            y,
+            block_light,
+            sky_light,
+        }
+    }
+
+    fn chunk_light(sections: Vec<LightSection>, top: i32) -> ChunkLight {
+        ChunkLight {
+            sections: sections
+                .into_iter()
+                .map(|section| (section.y as i32, section))
+                .collect(),
+            top,
+        }
+    }
+
+    #[test]
+    fn reads_both_halves_of_a_byte() {
+        let array = ByteArray::new(vec![0x5a_u8 as i8; 2048]);
+
+        assert_eq!(nibble(Some(&array), 0), Some(0xa));
+        assert_eq!(nibble(Some(&array), 1), Some(0x5));
+    }
+
+    #[test]
+    fn ignores_missing_and_malformed_arrays() {
+        assert_eq!(nibble(None, 0), None);
+        assert_eq!(nibble(Some(&ByteArray::new(vec![0; 16])), 0), None);
+    }
+
+    #[test]
+    fn unlit_chunks_are_lit_by_the_sky() {
+        assert_eq!(
+            ChunkLight::default().get(glam::IVec3::new(3, 64, 5)),
+            (MAX_LIGHT, 0)
+        );
+    }
+
+    #[test]
+    fn reads_the_light_of_a_block() {
+        // Block (3, 17, 5), in section 1 at index (1 * 16 + 5) * 16 + 3.
+        let index = (16 + 5) * 16 + 3;
+        let light = chunk_light(
+            vec![section(
+                1,
+                Some(nibbles(index, 14)),
+                Some(nibbles(index, 7)),
+            )],
+            1,
+        );
+
+        assert_eq!(light.get(glam::IVec3::new(3, 17, 5)), (7, 14));
+        assert_eq!(light.get(glam::IVec3::new(4, 17, 5)), (0, 0));
+    }
+
+    #[test]
+    fn sections_without_sky_light_take_it_from_above() {
+        // The bottom layer of section 2 is lit at (3, 5).
+        let light = chunk_light(
+            vec![
+                section(0, None, None),
+                section(2, None, Some(nibbles(5 * 16 + 3, 9))),
+            ],
+            2,
+        );
+
+        assert_eq!(light.get(glam::IVec3::new(3, 4, 5)), (9, 0));
+        assert_eq!(light.get(glam::IVec3::new(3, 20, 5)), (9, 0));
+        // Above the highest section with sky light is open sky.
+        assert_eq!(light.get(glam::IVec3::new(3, 60, 5)), (MAX_LIGHT, 0));
+    }
+}
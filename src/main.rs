@@ -1,10 +1,9 @@
 use std::{
     env,
-    fs::File,
-    mem,
     ops::{Deref, DerefMut},
+    str::FromStr,
     sync::Arc,
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use winit::{
@@ -15,18 +14,20 @@ use winit::{
     window::{CursorGrabMode, Window, WindowId},
 };
 
-mod blocks;
-mod camera;
-mod chunks;
-mod faces;
-mod region;
-mod tiles;
-
-use crate::{
-    blocks::BlocksPipeline, camera::Camera, chunks::ChunksPipeline, faces::FacesPipeline,
-    region::Region, tiles::TilesPipeline,
+use principium::{
+    render_to_png, BlockAssets, Camera, ColorOverrides, CullCounts, LoadOptions, Renderer,
+    ResourcePack, Skipped, Sky, World, DEFAULT_SUN_ANGLE,
 };
 
+/// How often the cull counts are shown in the title bar.
+const COUNTS_INTERVAL: Duration = Duration::from_secs(1);
+/// How much the daylight changes with every press of `[` or `]`.
+const DAYLIGHT_STEP: f32 = 0.125;
+/// How many degrees the sun moves with every press of `,` or `.`.
+const SUN_ANGLE_STEP: f32 = 7.5;
+/// How much the fog density changes with every press of `-` or `=`.
+const FOG_DENSITY_STEP: f32 = 0.5;
+
 #[derive(Debug)]
 struct Inner {
     window: Arc<Window>,
@@ -34,18 +35,23 @@ struct Inner {
     queue: wgpu::Queue,
     surface: wgpu::Surface<'static>,
     config: wgpu::SurfaceConfiguration,
-    depth_texture: wgpu::Texture,
-    chunks_pipeline: ChunksPipeline,
-    draw_indirect_buffer: wgpu::Buffer,
-    blocks_pipeline: BlocksPipeline,
-    faces_pipeline: FacesPipeline,
-    tiles_pipeline: TilesPipeline,
+    renderer: Renderer,
     camera: Camera,
     last_inst: Option<Instant>,
+    /// When the cull counts were last shown in the title bar.
+    last_counts_inst: Instant,
+    /// Latest cull counts read back, shown until newer ones are.
+    cull_counts: CullCounts,
 }
 
 impl Inner {
-    pub async fn new(window: Window) -> Self {
+    pub async fn new(
+        window: Window,
+        block_assets: BlockAssets,
+        daylight: f32,
+        sun_angle: f32,
+        sky: Sky,
+    ) -> Self {
         let window = Arc::new(window);
 
         let mut size = window.inner_size();
@@ -69,7 +75,10 @@ impl Inner {
                 &wgpu::DeviceDescriptor {
                     label: None,
                     required_features: wgpu::Features::empty(),
-                    required_limits: wgpu::Limits::default().using_resolution(adapter.limits()),
+                    required_limits: wgpu::Limits {
+                        max_texture_array_layers: adapter.limits().max_texture_array_layers,
+                        ..wgpu::Limits::default().using_resolution(adapter.limits())
+                    },
                     memory_hints: wgpu::MemoryHints::MemoryUsage,
                 },
                 None,
@@ -80,37 +89,21 @@ impl Inner {
         let swapchain_capabilities = surface.get_capabilities(&adapter);
         let swapchain_format = swapchain_capabilities.formats[0];
 
-        let chunks_pipeline = ChunksPipeline::new(&device);
-        let blocks_pipeline = BlocksPipeline::new(&device);
-        let faces_pipeline = FacesPipeline::new(&device, swapchain_format);
-        let tiles_pipeline = TilesPipeline::new(&device);
-
-        let draw_indirect_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("draw_indirect_buffer"),
-            size: mem::size_of::<wgpu::util::DrawIndirectArgs>() as u64,
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDIRECT,
-            mapped_at_creation: false,
-        });
-
         let config = surface
             .get_default_config(&adapter, size.width, size.height)
             .unwrap();
         surface.configure(&device, &config);
 
-        let depth_texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("depth_texture"),
-            size: wgpu::Extent3d {
-                width: size.width,
-                height: size.height,
-                ..Default::default()
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth24Plus,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
-            view_formats: &[],
-        });
+        let mut renderer = Renderer::new(
+            &device,
+            &queue,
+            swapchain_format,
+            glam::UVec2::new(size.width, size.height),
+            block_assets,
+        );
+        renderer.set_daylight(daylight);
+        renderer.set_sun_angle(sun_angle);
+        renderer.set_sky(sky);
 
         let camera = Camera::default();
 
@@ -125,28 +118,42 @@ impl Inner {
             queue,
             surface,
             config,
-            depth_texture,
-            chunks_pipeline,
-            draw_indirect_buffer,
-            blocks_pipeline,
-            faces_pipeline,
-            tiles_pipeline,
+            renderer,
             camera,
             last_inst: None,
+            last_counts_inst: Instant::now(),
+            cull_counts: CullCounts::default(),
         }
     }
 }
 
 #[derive(Debug)]
 struct App {
-    region: Region,
+    world: World,
+    /// Owned by the renderer while the app is resumed.
+    block_assets: Option<BlockAssets>,
+    /// Kept by the renderer while the app is resumed, like `sun_angle` and
+    /// `sky`.
+    daylight: f32,
+    sun_angle: f32,
+    sky: Sky,
     inner: Option<Inner>,
 }
 
 impl App {
-    pub fn new(region: Region) -> Self {
+    pub fn new(
+        world: World,
+        block_assets: BlockAssets,
+        daylight: f32,
+        sun_angle: f32,
+        sky: Sky,
+    ) -> Self {
         Self {
-            region,
+            world,
+            block_assets: Some(block_assets),
+            daylight,
+            sun_angle,
+            sky,
             inner: None,
         }
     }
@@ -172,11 +179,29 @@ impl ApplicationHandler for App {
             .create_window(Window::default_attributes().with_maximized(true))
             .unwrap();
 
-        self.inner = Some(pollster::block_on(Inner::new(window)));
+        let block_assets = self
+            .block_assets
+            .take()
+            .expect("App has already been resumed");
+
+        self.inner = Some(pollster::block_on(Inner::new(
+            window,
+            block_assets,
+            self.daylight,
+            self.sun_angle,
+            self.sky,
+        )));
     }
 
     fn suspended(&mut self, _event_loop: &ActiveEventLoop) {
-        self.inner = None;
+        if let Some(inner) = self.inner.take() {
+            // The next renderer starts with empty buffers.
+            self.daylight = inner.renderer.daylight();
+            self.sun_angle = inner.renderer.sun_angle();
+            self.sky = inner.renderer.sky();
+            self.block_assets = Some(inner.renderer.into_block_assets());
+            self.world.reload();
+        }
     }
 
     fn device_event(
@@ -185,9 +210,8 @@ impl ApplicationHandler for App {
         _device_id: winit::event::DeviceId,
         event: winit::event::DeviceEvent,
     ) {
-        match event {
-            DeviceEvent::MouseMotion { delta } => self.camera.handle_mouse_motion(delta),
-            _ => (),
+        if let DeviceEvent::MouseMotion { delta } = event {
+            self.camera.handle_mouse_motion(delta);
         }
     }
 
@@ -208,28 +232,67 @@ impl ApplicationHandler for App {
                     },
                 ..
             } => event_loop.exit(),
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key:
+                            PhysicalKey::Code(code @ (KeyCode::BracketLeft | KeyCode::BracketRight)),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                let step = match code {
+                    KeyCode::BracketLeft => -DAYLIGHT_STEP,
+                    _ => DAYLIGHT_STEP,
+                };
+                let daylight = self.renderer.daylight() + step;
+                self.renderer.set_daylight(daylight);
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(code @ (KeyCode::Comma | KeyCode::Period)),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                let step = match code {
+                    KeyCode::Comma => -SUN_ANGLE_STEP,
+                    _ => SUN_ANGLE_STEP,
+                };
+                let sun_angle = self.renderer.sun_angle() + step;
+                self.renderer.set_sun_angle(sun_angle);
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(code @ (KeyCode::Minus | KeyCode::Equal)),
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                let step = match code {
+                    KeyCode::Minus => -FOG_DENSITY_STEP,
+                    _ => FOG_DENSITY_STEP,
+                };
+                let mut sky = self.renderer.sky();
+                sky.fog_density = (sky.fog_density + step).max(0.0);
+                self.renderer.set_sky(sky);
+            }
             WindowEvent::KeyboardInput { event, .. } => self.camera.handle_key_event(event),
             WindowEvent::Resized(new_size) => {
                 self.config.width = new_size.width.max(1);
                 self.config.height = new_size.height.max(1);
                 self.surface.configure(&self.device, &self.config);
 
-                self.depth_texture.destroy();
-                self.depth_texture = self.device.create_texture(&wgpu::TextureDescriptor {
-                    label: Some("depth_texture"),
-                    size: wgpu::Extent3d {
-                        width: new_size.width,
-                        height: new_size.height,
-                        ..Default::default()
-                    },
-                    mip_level_count: 1,
-                    sample_count: 1,
-                    dimension: wgpu::TextureDimension::D2,
-                    format: wgpu::TextureFormat::Depth24Plus,
-                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
-                        | wgpu::TextureUsages::TEXTURE_BINDING,
-                    view_formats: &[],
-                });
+                let inner = &mut **self;
+                inner.renderer.resize(
+                    &inner.device,
+                    glam::UVec2::new(inner.config.width, inner.config.height),
+                );
 
                 self.window.request_redraw();
             }
@@ -244,6 +307,22 @@ impl ApplicationHandler for App {
 
                 self.camera.update(dt);
 
+                let inner = self.inner.as_mut().expect("App has not been resumed yet");
+                let update = inner.renderer.update(
+                    &inner.device,
+                    &inner.queue,
+                    &mut self.world,
+                    inner.camera.eye,
+                );
+                match update {
+                    Ok(skipped) => report_skipped(&skipped),
+                    Err(error) => {
+                        eprintln!("failed to load world: {error} (pass --lenient to skip it)");
+                        event_loop.exit();
+                        return;
+                    }
+                }
+
                 let frame = self
                     .surface
                     .get_current_texture()
@@ -251,50 +330,34 @@ impl ApplicationHandler for App {
                 let color_view = frame
                     .texture
                     .create_view(&wgpu::TextureViewDescriptor::default());
-                let depth_view = self
-                    .depth_texture
-                    .create_view(&wgpu::TextureViewDescriptor::default());
 
                 let mut encoder = self
                     .device
                     .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
 
-                let aspect_ratio = self.config.width as f32 / self.config.height as f32;
-
-                let (chunk_buffer, chunks_len_buffer) = self.chunks_pipeline.encode(
+                self.renderer.encode(
                     &self.device,
+                    &self.queue,
                     &mut encoder,
-                    &self.region,
-                    self.camera
-                        .clip_from_world_with_margin(aspect_ratio, 8.0 * 3.0f32.sqrt()),
-                );
-                let face_buffer = self.blocks_pipeline.encode(
-                    &self.device,
-                    &mut encoder,
-                    &self.region,
-                    chunk_buffer,
-                    chunks_len_buffer,
-                    self.camera.eye,
-                    self.camera
-                        .clip_from_world_with_margin(aspect_ratio, 0.5 * 3.0f32.sqrt()),
-                    &self.draw_indirect_buffer,
-                );
-                self.faces_pipeline.encode(
-                    &self.device,
-                    &mut encoder,
-                    &face_buffer,
-                    self.camera.clip_from_world(aspect_ratio),
-                    &self.draw_indirect_buffer,
+                    &self.camera,
                     &color_view,
-                    &depth_view,
                 );
-                self.tiles_pipeline
-                    .encode(&self.device, &mut encoder, &depth_view, &self.config);
 
                 self.queue.submit(Some(encoder.finish()));
 
                 frame.present();
 
+                if let Some(counts) = self.renderer.cull_counts(&self.device) {
+                    self.cull_counts = counts;
+                }
+                if now - self.last_counts_inst >= COUNTS_INTERVAL {
+                    self.window.set_title(&format!(
+                        "principium: {} sections culled by frustum, {} by occlusion",
+                        self.cull_counts.frustum, self.cull_counts.occlusion,
+                    ));
+                    self.last_counts_inst = now;
+                }
+
                 self.window.request_redraw();
             }
             WindowEvent::CloseRequested => event_loop.exit(),
@@ -303,16 +366,173 @@ impl ApplicationHandler for App {
     }
 }
 
+/// Parses `N` values separated by `separator`, like `1,2,3` or `1920x1080`.
+fn parse_values<T: FromStr, const N: usize>(arg: &str, separator: char) -> Option<[T; N]> {
+    let values: Vec<_> = arg
+        .split(separator)
+        .map(|value| value.trim().parse().ok())
+        .collect::<Option<_>>()?;
+
+    values.try_into().ok()
+}
+
+/// Where and how `principium render` takes its picture.
+#[derive(Debug)]
+struct RenderOptions {
+    pos: glam::DVec3,
+    look: [f32; 2],
+    size: glam::UVec2,
+    output: Option<String>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            pos: Camera::default().eye,
+            look: [-45.0, 0.0],
+            size: glam::UVec2::new(1920, 1080),
+            output: None,
+        }
+    }
+}
+
+/// Prints what the renderer left out of the world.
+fn report_skipped(skipped: &Skipped) {
+    for error in &skipped.regions {
+        eprintln!("skipping {error}");
+    }
+    for error in &skipped.columns {
+        eprintln!("skipping {error}");
+    }
+    if let Some(limit) = skipped.texture_limit {
+        eprintln!("more than {limit} block textures, some faces will be untextured");
+    }
+}
+
 fn main() {
-    let path = env::args().nth(1).expect("provide .mca path as arg");
+    let mut path = None;
+    let mut options = LoadOptions::default();
+    let mut color_overrides = ColorOverrides::default();
+    let mut resource_pack = None;
+    let mut render_options = None;
+    let mut daylight = 1.0;
+    let mut sun_angle = DEFAULT_SUN_ANGLE;
+    let mut sky = Sky::default();
+
+    let mut args = env::args().skip(1).peekable();
+    if args.next_if_eq("render").is_some() {
+        render_options = Some(RenderOptions::default());
+    }
+
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), render_options.as_mut()) {
+            ("--all-sections", _) => options.all_sections = true,
+            ("--greedy", _) => options.greedy_meshing = true,
+            ("--no-lod", _) => options.lod = false,
+            ("--lenient", _) => options.lenient = true,
+            ("--view-distance", _) => {
+                let view_distance = args.next().expect("provide view distance in chunks");
+                options.view_distance = view_distance
+                    .parse()
+                    .expect("view distance must be a number of chunks");
+            }
+            ("--daylight", _) => {
+                let value = args.next().expect("provide daylight from 0 to 1");
+                daylight = value
+                    .parse()
+                    .expect("daylight must be a number from 0 to 1");
+            }
+            ("--sun-angle", _) => {
+                let value = args
+                    .next()
+                    .expect("provide sun angle from 0 to 180 degrees");
+                sun_angle = value
+                    .parse()
+                    .expect("sun angle must be a number of degrees from 0 to 180");
+            }
+            ("--fog", _) => {
+                let value = args.next().expect("provide fog density");
+                sky.fog_density = value
+                    .parse()
+                    .expect("fog density must be a number, 0 for no fog");
+            }
+            ("--no-sun-disc", _) => sky.sun_disc = false,
+            ("--far-distance", _) => {
+                let far_distance = args.next().expect("provide far distance in chunks");
+                options.far_distance = far_distance
+                    .parse()
+                    .expect("far distance must be a number of chunks");
+            }
+            ("--colors", _) => {
+                let colors_path = args.next().expect("provide color overrides path");
+                color_overrides =
+                    ColorOverrides::load(colors_path).expect("failed to read color overrides");
+            }
+            ("--resource-pack", _) => {
+                let pack_path = args.next().expect("provide resource pack path");
+                resource_pack =
+                    Some(ResourcePack::open(pack_path).expect("failed to open resource pack"));
+            }
+            ("--pos", Some(render_options)) => {
+                let pos = args.next().expect("provide position as x,y,z");
+                render_options.pos = glam::DVec3::from_array(
+                    parse_values(&pos, ',').expect("position must be x,y,z"),
+                );
+            }
+            ("--look", Some(render_options)) => {
+                let look = args.next().expect("provide look direction as yaw,pitch");
+                render_options.look =
+                    parse_values(&look, ',').expect("look direction must be yaw,pitch");
+            }
+            ("--size", Some(render_options)) => {
+                let size = args.next().expect("provide image size as WIDTHxHEIGHT");
+                render_options.size = glam::UVec2::from_array(
+                    parse_values(&size, 'x')
+                        .filter(|size: &[u32; 2]| size.iter().all(|&len| len > 0))
+                        .expect("image size must be WIDTHxHEIGHT"),
+                );
+            }
+            ("-o" | "--output", Some(render_options)) => {
+                render_options.output = Some(args.next().expect("provide output path"));
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => panic!("unexpected argument: {arg}"),
+        }
+    }
 
-    let file = File::open(path).unwrap();
+    let path = path.expect("provide world path as arg");
+
+    let mut world = World::open(path, options).expect("failed to open world");
+    let block_assets = BlockAssets::new(color_overrides, resource_pack);
+
+    if let Some(render_options) = render_options {
+        let output = render_options.output.expect("provide output path with -o");
+        let [yaw, pitch] = render_options.look;
+
+        let result = render_to_png(
+            &mut world,
+            block_assets,
+            &Camera::looking(render_options.pos, yaw, pitch),
+            render_options.size,
+            daylight,
+            sun_angle,
+            sky,
+            output,
+        );
+        match result {
+            Ok(skipped) => report_skipped(&skipped),
+            Err(error) => {
+                eprintln!("failed to render image: {error}");
+                std::process::exit(1);
+            }
+        }
 
-    let region = Region::new(file).unwrap();
+        return;
+    }
 
     EventLoop::with_user_event()
         .build()
         .unwrap()
-        .run_app(&mut App::new(region))
+        .run_app(&mut App::new(world, block_assets, daylight, sun_angle, sky))
         .unwrap();
 }
@@ -0,0 +1,778 @@
+use std::mem;
+
+use glam::Vec3;
+
+use crate::{
+    opacity::Opacity, palette::Palette, region::TRANSLUCENT_BIT, resource_pack::NO_TEXTURE, world,
+};
+
+/// Cull face of quads that are drawn whatever their neighbours are.
+pub const NO_CULL_FACE: u32 = 6;
+/// Quads are referenced by faces with 17 bits.
+const MAX_QUADS: usize = 1 << 17;
+
+/// Unit directions of the face indices used on the GPU: `-x`, `+x`, `-y`, `+y`,
+/// `-z`, `+z`.
+pub const FACE_DIRS: [glam::IVec3; 6] = [
+    glam::IVec3::NEG_X,
+    glam::IVec3::X,
+    glam::IVec3::NEG_Y,
+    glam::IVec3::Y,
+    glam::IVec3::NEG_Z,
+    glam::IVec3::Z,
+];
+
+/// A textured quad of a block model, laid out like `Quad` in the shaders.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Quad {
+    /// Corners in block space, where the block spans `[0, 1]` on every axis.
+    corners: [[f32; 4]; 4],
+    uvs: [[f32; 2]; 4],
+    normal: [f32; 3],
+    block_id: u32,
+    /// Texture layer like the ones of [`BlockTextures`](crate::resource_pack::BlockTextures).
+    texture: u32,
+    /// Face index of the neighbour hiding this quad, or [`NO_CULL_FACE`].
+    cull_face: u32,
+    _padding: [u32; 2],
+}
+
+/// A face of an [`Element`].
+#[derive(Clone, Copy, Debug)]
+pub struct ElementFace {
+    /// `[u0, v0, u1, v1]` in `[0, 1]`, defaulting to the part of the texture the
+    /// face would cover on a full cube.
+    pub uv: Option<[f32; 4]>,
+    /// Clockwise texture rotation in degrees.
+    pub rotation: i64,
+    pub texture: u16,
+    pub cull_face: Option<usize>,
+}
+
+/// Rotation of an [`Element`] around a single axis.
+#[derive(Clone, Copy, Debug)]
+pub struct ElementRotation {
+    pub origin: Vec3,
+    pub axis: usize,
+    /// Angle in degrees.
+    pub angle: f32,
+    /// Stretches the element so that it keeps spanning the whole block once
+    /// rotated, like plants do.
+    pub rescale: bool,
+}
+
+/// An axis-aligned box of a block model, as found in the `elements` of model files
+/// but in block space.
+#[derive(Clone, Debug)]
+pub struct Element {
+    pub from: Vec3,
+    pub to: Vec3,
+    /// Faces in the order of face indices, missing ones not being drawn.
+    pub faces: [Option<ElementFace>; 6],
+    pub rotation: Option<ElementRotation>,
+}
+
+impl Element {
+    /// A box with every face untextured and culled by the neighbour it touches.
+    fn untextured(from: [f32; 3], to: [f32; 3]) -> Self {
+        let from = Vec3::from(from);
+        let to = Vec3::from(to);
+
+        Self {
+            from,
+            to,
+            faces: std::array::from_fn(|i| {
+                let axis = i / 2;
+                let touches_neighbor = if i % 2 == 1 {
+                    to[axis] == 1.0
+                } else {
+                    from[axis] == 0.0
+                };
+
+                Some(ElementFace {
+                    uv: None,
+                    rotation: 0,
+                    texture: NO_TEXTURE,
+                    cull_face: touches_neighbor.then_some(i),
+                })
+            }),
+            rotation: None,
+        }
+    }
+
+    /// The two crossed planes plants are made of.
+    pub fn cross(texture: u16) -> [Self; 2] {
+        let face = Some(ElementFace {
+            uv: Some([0.0, 0.0, 1.0, 1.0]),
+            rotation: 0,
+            texture,
+            cull_face: None,
+        });
+        let plane = |axis: usize, angle| {
+            let mut from = Vec3::splat(0.8 / 16.0);
+            let mut to = Vec3::splat(15.2 / 16.0);
+            from.y = 0.0;
+            to.y = 1.0;
+            from[axis] = 0.5;
+            to[axis] = 0.5;
+
+            let mut faces = [None; 6];
+            faces[axis * 2] = face;
+            faces[axis * 2 + 1] = face;
+
+            Self {
+                from,
+                to,
+                faces,
+                rotation: Some(ElementRotation {
+                    origin: Vec3::splat(0.5),
+                    axis: 1,
+                    angle,
+                    rescale: true,
+                }),
+            }
+        };
+
+        [plane(0, 45.0), plane(2, 45.0)]
+    }
+
+    /// Appends the quads of this element, rotated like a blockstate variant.
+    pub fn quads(&self, block_id: u16, rotation_x: i64, rotation_y: i64, quads: &mut Vec<Quad>) {
+        for (i, face) in self.faces.iter().enumerate() {
+            let Some(face) = face else {
+                continue;
+            };
+
+            let axis = i / 2;
+            let sign = i % 2 == 1;
+
+            // Corners go around the face so that they form the triangles 0 1 2 and
+            // 0 2 3.
+            let corners = [[0, 0], [1, 0], [1, 1], [0, 1]].map(|[a, b]| {
+                let mut corner = self.from;
+                corner[axis] = if sign { self.to[axis] } else { self.from[axis] };
+                corner[(axis + 1) % 3] = [self.from, self.to][a][(axis + 1) % 3];
+                corner[(axis + 2) % 3] = [self.from, self.to][b][(axis + 2) % 3];
+                corner
+            });
+
+            let default_uvs = corners.map(|corner| cube_uv(corner, axis, sign));
+            let min = default_uvs.into_iter().reduce(glam::Vec2::min).unwrap();
+            let max = default_uvs.into_iter().reduce(glam::Vec2::max).unwrap();
+
+            let uvs = default_uvs.map(|uv| {
+                let Some([u0, v0, u1, v1]) = face.uv else {
+                    return uv;
+                };
+
+                let extent = (max - min).max(glam::Vec2::splat(f32::EPSILON));
+                let mut st = (uv - min) / extent;
+                for _ in 0..face.rotation.rem_euclid(360) / 90 {
+                    st = glam::Vec2::new(st.y, 1.0 - st.x);
+                }
+
+                glam::Vec2::new(u0 + (u1 - u0) * st.x, v0 + (v1 - v0) * st.y)
+            });
+
+            let mut normal = FACE_DIRS[i].as_vec3();
+            let mut corners = corners;
+
+            if let Some(rotation) = self.rotation {
+                let transform = rotation.transform();
+
+                corners = corners.map(|corner| transform.transform_point3(corner));
+                normal = transform.transform_vector3(normal).normalize();
+            }
+
+            let center = Vec3::splat(0.5);
+            let corners = corners.map(|corner| {
+                let corner = center + rotate(corner - center, rotation_x, rotation_y);
+                [corner.x, corner.y, corner.z, 1.0]
+            });
+            let normal = rotate(normal, rotation_x, rotation_y);
+
+            let cull_face = face.cull_face.map_or(NO_CULL_FACE, |cull_face| {
+                let dir = rotate(FACE_DIRS[cull_face].as_vec3(), rotation_x, rotation_y);
+
+                FACE_DIRS
+                    .iter()
+                    .position(|&face_dir| face_dir == dir.round().as_ivec3())
+                    .unwrap_or(cull_face) as u32
+            });
+
+            quads.push(Quad {
+                corners,
+                uvs: uvs.map(|uv| uv.to_array()),
+                normal: normal.to_array(),
+                block_id: block_id as u32,
+                texture: face.texture as u32,
+                cull_face,
+                _padding: [0; 2],
+            });
+        }
+    }
+}
+
+impl ElementRotation {
+    fn transform(&self) -> glam::Affine3A {
+        let angle = self.angle.to_radians();
+        let (rotation, mut scale) = match self.axis {
+            0 => (glam::Quat::from_rotation_x(angle), Vec3::new(0.0, 1.0, 1.0)),
+            1 => (glam::Quat::from_rotation_y(angle), Vec3::new(1.0, 0.0, 1.0)),
+            _ => (glam::Quat::from_rotation_z(angle), Vec3::new(1.0, 1.0, 0.0)),
+        };
+
+        scale = if self.rescale {
+            scale / angle.cos() + (Vec3::ONE - scale)
+        } else {
+            Vec3::ONE
+        };
+
+        glam::Affine3A::from_translation(self.origin)
+            * glam::Affine3A::from_quat(rotation)
+            * glam::Affine3A::from_scale(scale)
+            * glam::Affine3A::from_translation(-self.origin)
+    }
+}
+
+/// Texture coordinates a point of a full cube face gets, matching the orientation
+/// of cube faces in `faces.wgsl`.
+fn cube_uv(pos: Vec3, axis: usize, sign: bool) -> glam::Vec2 {
+    match axis {
+        0 => glam::Vec2::new(if sign { 1.0 - pos.z } else { pos.z }, 1.0 - pos.y),
+        1 => glam::Vec2::new(pos.x, if sign { pos.z } else { 1.0 - pos.z }),
+        _ => glam::Vec2::new(if sign { pos.x } else { 1.0 - pos.x }, 1.0 - pos.y),
+    }
+}
+
+/// Applies a blockstate variant's rotation, in degrees around the x then y axis.
+pub fn rotate(v: Vec3, rotation_x: i64, rotation_y: i64) -> Vec3 {
+    let mut v = v;
+
+    for _ in 0..rotation_x.rem_euclid(360) / 90 {
+        v = Vec3::new(v.x, v.z, -v.y);
+    }
+
+    for _ in 0..rotation_y.rem_euclid(360) / 90 {
+        v = Vec3::new(-v.z, v.y, v.x);
+    }
+
+    v
+}
+
+/// Quads of the blocks of a [`Palette`] that aren't full cubes.
+#[derive(Debug, Default)]
+pub struct BlockModels {
+    /// `[start, len]` of the quads of every palette block, empty for full cubes.
+    ranges: Vec<[u32; 2]>,
+    quads: Vec<Quad>,
+}
+
+impl BlockModels {
+    /// Adds approximate shapes of common non-cube blocks for the blocks of
+    /// `palette` missing from these models, for when no resource pack is used.
+    pub fn extend_builtin(&mut self, palette: &Palette) {
+        for id in self.len() as u16..palette.len() as u16 {
+            let elements = builtin_elements(palette.state(id)).unwrap_or_default();
+            self.push(id, elements.iter().map(|element| (element, 0, 0)));
+        }
+    }
+
+    /// Number of palette blocks with a model, full cubes included.
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Adds the model of the next palette block from its elements along with
+    /// their variant rotation. Models that would not fit are drawn as cubes.
+    pub fn push<'a>(
+        &mut self,
+        block_id: u16,
+        elements: impl IntoIterator<Item = (&'a Element, i64, i64)>,
+    ) {
+        debug_assert_eq!(self.ranges.len(), block_id as usize);
+
+        let start = self.quads.len();
+        for (element, rotation_x, rotation_y) in elements {
+            element.quads(block_id, rotation_x, rotation_y, &mut self.quads);
+        }
+
+        if self.quads.len() > MAX_QUADS {
+            self.quads.truncate(start);
+        }
+
+        self.ranges
+            .push([start as u32, (self.quads.len() - start) as u32]);
+    }
+}
+
+/// Shapes of [`BlockModels::extend_builtin`], `None` meaning a full cube.
+pub fn builtin_elements(state: &str) -> Option<Vec<Element>> {
+    if Opacity::of(state) != Opacity::NonCube {
+        return None;
+    }
+
+    let (name, properties) = state.split_once('|').unwrap_or((state, ""));
+    let name = name.strip_prefix("minecraft:")?;
+    let property = |key: &str| {
+        properties
+            .split(',')
+            .filter_map(|property| property.split_once('='))
+            .find_map(|(k, v)| (k == key).then_some(v))
+    };
+    let top = property("half") == Some("top") || property("type") == Some("top");
+    let flip = |[x0, y0, z0]: [f32; 3], [x1, y1, z1]: [f32; 3]| {
+        if top {
+            Element::untextured([x0, 1.0 - y1, z0], [x1, 1.0 - y0, z1])
+        } else {
+            Element::untextured([x0, y0, z0], [x1, y1, z1])
+        }
+    };
+
+    // Posts with arms towards the sides they connect to.
+    let post = |radius: f32, height: f32, arm_radius: f32, arm_height: f32| {
+        let (lo, hi) = (0.5 - radius, 0.5 + radius);
+        let (arm_lo, arm_hi) = (0.5 - arm_radius, 0.5 + arm_radius);
+        let mut elements = vec![Element::untextured([lo, 0.0, lo], [hi, height, hi])];
+
+        for (side, from, to) in [
+            ("north", [arm_lo, 0.0, 0.0], [arm_hi, arm_height, lo]),
+            ("south", [arm_lo, 0.0, hi], [arm_hi, arm_height, 1.0]),
+            ("west", [0.0, 0.0, arm_lo], [lo, arm_height, arm_hi]),
+            ("east", [hi, 0.0, arm_lo], [1.0, arm_height, arm_hi]),
+        ] {
+            if property(side).is_some_and(|value| value != "false" && value != "none") {
+                elements.push(Element::untextured(from, to));
+            }
+        }
+
+        elements
+    };
+
+    if name.ends_with("_slab") {
+        return Some(vec![flip([0.0; 3], [1.0, 0.5, 1.0])]);
+    }
+
+    if name.ends_with("_stairs") {
+        let (from, to) = match property("facing")? {
+            "north" => ([0.0, 0.5, 0.0], [1.0, 1.0, 0.5]),
+            "south" => ([0.0, 0.5, 0.5], [1.0, 1.0, 1.0]),
+            "west" => ([0.0, 0.5, 0.0], [0.5, 1.0, 1.0]),
+            _ => ([0.5, 0.5, 0.0], [1.0, 1.0, 1.0]),
+        };
+
+        return Some(vec![flip([0.0; 3], [1.0, 0.5, 1.0]), flip(from, to)]);
+    }
+
+    if name.ends_with("_carpet") || name.ends_with("_pressure_plate") {
+        return Some(vec![Element::untextured([0.0; 3], [1.0, 1.0 / 16.0, 1.0])]);
+    }
+
+    if name == "snow" {
+        let layers: f32 = property("layers")?.parse().ok()?;
+        return Some(vec![Element::untextured(
+            [0.0; 3],
+            [1.0, layers / 8.0, 1.0],
+        )]);
+    }
+
+    if name.ends_with("_fence") {
+        return Some(post(2.0 / 16.0, 1.0, 1.0 / 16.0, 15.0 / 16.0));
+    }
+
+    if name.ends_with("_wall") {
+        return Some(post(4.0 / 16.0, 1.0, 3.0 / 16.0, 14.0 / 16.0));
+    }
+
+    if name.ends_with("_pane") || name == "iron_bars" {
+        return Some(post(1.0 / 16.0, 1.0, 1.0 / 16.0, 1.0));
+    }
+
+    if name.ends_with("torch") {
+        return Some(vec![Element::untextured(
+            [7.0 / 16.0, 0.0, 7.0 / 16.0],
+            [9.0 / 16.0, 10.0 / 16.0, 9.0 / 16.0],
+        )]);
+    }
+
+    if is_plant(name) {
+        return Some(Element::cross(NO_TEXTURE).into());
+    }
+
+    None
+}
+
+const PLANTS: &[&str] = &[
+    "grass",
+    "short_grass",
+    "tall_grass",
+    "fern",
+    "large_fern",
+    "dead_bush",
+    "sugar_cane",
+    "wheat",
+    "carrots",
+    "potatoes",
+    "beetroots",
+    "nether_wart",
+    "sweet_berry_bush",
+    "nether_sprouts",
+    "dandelion",
+    "poppy",
+    "blue_orchid",
+    "allium",
+    "azure_bluet",
+    "oxeye_daisy",
+    "cornflower",
+    "lily_of_the_valley",
+    "wither_rose",
+    "torchflower",
+    "sunflower",
+    "lilac",
+    "rose_bush",
+    "peony",
+    "cobweb",
+    "hanging_roots",
+    "seagrass",
+    "kelp",
+    "kelp_plant",
+];
+
+const PLANT_SUFFIXES: [&str; 6] = [
+    "_sapling",
+    "_tulip",
+    "_mushroom",
+    "_fungus",
+    "_roots",
+    "_coral",
+];
+
+fn is_plant(name: &str) -> bool {
+    PLANTS.contains(&name) || PLANT_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+}
+
+/// [`BlockModels`] uploaded for the blocks and faces pipelines, extended as
+/// models are added.
+#[derive(Debug)]
+pub struct ModelBuffers {
+    pub range_buffer: wgpu::Buffer,
+    pub quad_buffer: wgpu::Buffer,
+    ranges: Vec<[u32; 2]>,
+    /// Number of quads uploaded so far.
+    quads_len: usize,
+}
+
+impl ModelBuffers {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, models: &BlockModels) -> Self {
+        let buffer = |label, size| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size,
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::COPY_SRC
+                    | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+
+        // Storage buffers can't be empty.
+        let mut model_buffers = Self {
+            range_buffer: buffer(
+                "model_range_buffer",
+                256 * mem::size_of::<[u32; 2]>() as u64,
+            ),
+            quad_buffer: buffer("quad_buffer", 64 * mem::size_of::<Quad>() as u64),
+            ranges: Vec::new(),
+            quads_len: 0,
+        };
+        model_buffers.update(device, queue, models);
+
+        model_buffers
+    }
+
+    /// Uploads the models added to `models` since the last call, growing the
+    /// buffers when they run out of room.
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, models: &BlockModels) {
+        let ranges = &models.ranges[self.ranges.len()..];
+        let range_size = mem::size_of::<[u32; 2]>() as u64;
+        world::grow(
+            device,
+            queue,
+            &mut self.range_buffer,
+            "model_range_buffer",
+            models.ranges.len() as u64 * range_size,
+        );
+        queue.write_buffer(
+            &self.range_buffer,
+            self.ranges.len() as u64 * range_size,
+            bytemuck::cast_slice(ranges),
+        );
+        self.ranges.extend_from_slice(ranges);
+
+        let quads = &models.quads[self.quads_len..];
+        let quad_size = mem::size_of::<Quad>() as u64;
+        world::grow(
+            device,
+            queue,
+            &mut self.quad_buffer,
+            "quad_buffer",
+            models.quads.len() as u64 * quad_size,
+        );
+        queue.write_buffer(
+            &self.quad_buffer,
+            self.quads_len as u64 * quad_size,
+            bytemuck::cast_slice(quads),
+        );
+        self.quads_len = models.quads.len();
+    }
+
+    /// Most opaque and translucent faces `blocks` can produce at once: 3 for
+    /// cubes, of which only the ones facing the camera are drawn, and every quad
+    /// for models.
+    pub fn face_capacity(&self, blocks: &[[u32; 5]]) -> [usize; 2] {
+        blocks
+            .iter()
+            .fold([0; 2], |mut capacity, &[block, id, ..]| {
+                capacity[(block >> TRANSLUCENT_BIT) as usize & 1] +=
+                    match self.ranges.get(id as usize) {
+                        Some(&[_, len]) if len > 0 => len as usize,
+                        _ => cube_face_capacity(block),
+                    };
+                capacity
+            })
+    }
+}
+
+/// Most faces a block drawn as a cube can generate in a frame, from its packed
+/// record.
+pub fn cube_face_capacity(block: u32) -> usize {
+    // At most three faces of a cube face the eye.
+    ((block >> 12) & 0b11_1111).count_ones().min(3) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds(state: &str) -> Option<Vec<[[f32; 3]; 2]>> {
+        builtin_elements(state).map(|elements| {
+            elements
+                .iter()
+                .map(|element| [element.from.to_array(), element.to.to_array()])
+                .collect()
+        })
+    }
+
+    fn cull_faces(element: &Element) -> [Option<usize>; 6] {
+        element.faces.map(|face| face.unwrap().cull_face)
+    }
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!(a.abs_diff_eq(b, 1e-5), "{a} != {b}");
+    }
+
+    #[test]
+    fn leaves_full_cubes_to_the_cube_path() {
+        assert!(builtin_elements("minecraft:stone").is_none());
+        assert!(builtin_elements("minecraft:oak_slab|type=double").is_none());
+        assert!(builtin_elements("minecraft:snow|layers=8").is_none());
+        assert!(builtin_elements("mymod:odd_slab|type=bottom").is_none());
+    }
+
+    #[test]
+    fn builds_slabs_and_stairs() {
+        assert_eq!(
+            bounds("minecraft:oak_slab|type=bottom,waterlogged=false"),
+            Some(vec![[[0.0; 3], [1.0, 0.5, 1.0]]])
+        );
+        assert_eq!(
+            bounds("minecraft:oak_slab|type=top,waterlogged=false"),
+            Some(vec![[[0.0, 0.5, 0.0], [1.0; 3]]])
+        );
+        assert_eq!(
+            bounds("minecraft:oak_stairs|facing=east,half=bottom,shape=straight"),
+            Some(vec![
+                [[0.0; 3], [1.0, 0.5, 1.0]],
+                [[0.5, 0.5, 0.0], [1.0; 3]],
+            ])
+        );
+        assert_eq!(
+            bounds("minecraft:oak_stairs|facing=north,half=top,shape=straight"),
+            Some(vec![
+                [[0.0, 0.5, 0.0], [1.0; 3]],
+                [[0.0; 3], [1.0, 0.5, 0.5]],
+            ])
+        );
+        assert!(builtin_elements("minecraft:oak_stairs").is_none());
+    }
+
+    #[test]
+    fn culls_only_faces_touching_neighbours() {
+        let bottom = builtin_elements("minecraft:oak_slab|type=bottom").unwrap();
+        assert_eq!(
+            cull_faces(&bottom[0]),
+            [Some(0), Some(1), Some(2), None, Some(4), Some(5)]
+        );
+
+        let top = builtin_elements("minecraft:oak_slab|type=top").unwrap();
+        assert_eq!(
+            cull_faces(&top[0]),
+            [Some(0), Some(1), None, Some(3), Some(4), Some(5)]
+        );
+
+        let torch = builtin_elements("minecraft:torch").unwrap();
+        assert_eq!(
+            cull_faces(&torch[0]),
+            [None, None, Some(2), None, None, None]
+        );
+    }
+
+    #[test]
+    fn builds_layers_posts_and_plants() {
+        assert_eq!(
+            bounds("minecraft:snow|layers=3"),
+            Some(vec![[[0.0; 3], [1.0, 0.375, 1.0]]])
+        );
+        assert_eq!(
+            bounds("minecraft:white_carpet"),
+            Some(vec![[[0.0; 3], [1.0, 1.0 / 16.0, 1.0]]])
+        );
+
+        // Arms only reach towards connected sides.
+        let fence = bounds("minecraft:oak_fence|east=true,north=false,south=false,west=false");
+        assert_eq!(
+            fence,
+            Some(vec![
+                [[0.375, 0.0, 0.375], [0.625, 1.0, 0.625]],
+                [[0.625, 0.0, 0.4375], [1.0, 0.9375, 0.5625]],
+            ])
+        );
+        let wall = bounds("minecraft:cobblestone_wall|east=none,north=low,up=true");
+        assert_eq!(
+            wall,
+            Some(vec![
+                [[0.25, 0.0, 0.25], [0.75, 1.0, 0.75]],
+                [[0.3125, 0.0, 0.0], [0.6875, 0.875, 0.25]],
+            ])
+        );
+
+        let poppy = builtin_elements("minecraft:poppy").unwrap();
+        assert_eq!(poppy.len(), 2);
+        assert!(poppy.iter().all(|element| element
+            .rotation
+            .is_some_and(|rotation| rotation.axis == 1 && rotation.rescale)));
+    }
+
+    #[test]
+    fn rotates_like_blockstate_variants() {
+        assert_eq!(rotate(Vec3::X, 0, 90), Vec3::Z);
+        assert_eq!(rotate(Vec3::Z, 0, 90), Vec3::NEG_X);
+        assert_eq!(rotate(Vec3::Z, 0, -90), Vec3::X);
+        assert_eq!(rotate(Vec3::Y, 90, 0), Vec3::NEG_Z);
+        assert_eq!(rotate(Vec3::Y, 90, 90), Vec3::X);
+        assert_eq!(rotate(Vec3::X, 0, 360), Vec3::X);
+    }
+
+    #[test]
+    fn rotates_elements_around_their_origin() {
+        let rotation = ElementRotation {
+            origin: Vec3::splat(0.5),
+            axis: 1,
+            angle: 90.0,
+            rescale: false,
+        };
+        assert_near(
+            rotation
+                .transform()
+                .transform_point3(Vec3::new(1.0, 0.0, 0.5)),
+            Vec3::new(0.5, 0.0, 0.0),
+        );
+
+        // Rescaled crossed planes reach the corners of the block, less their
+        // margin.
+        let mut quads = Vec::new();
+        for plane in Element::cross(0) {
+            plane.quads(0, 0, 0, &mut quads);
+        }
+
+        assert_eq!(quads.len(), 4);
+        for quad in &quads {
+            for [x, y, z, _] in quad.corners {
+                for c in [x, z] {
+                    assert!((c - 0.05).abs() < 1e-5 || (c - 0.95).abs() < 1e-5, "{c}");
+                }
+                assert!(y == 0.0 || y == 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn turns_quads_and_cull_faces_with_the_variant() {
+        let cube = Element::untextured([0.0; 3], [1.0; 3]);
+
+        let mut quads = Vec::new();
+        cube.quads(7, 0, 0, &mut quads);
+        assert_eq!(quads.len(), 6);
+        for (i, quad) in quads.iter().enumerate() {
+            assert_eq!(quad.cull_face, i as u32);
+            assert_eq!(quad.block_id, 7);
+            assert_eq!(quad.texture, NO_TEXTURE as u32);
+            assert_eq!(Vec3::from(quad.normal), FACE_DIRS[i].as_vec3());
+        }
+
+        // A quarter turn around y turns +x towards +z, and -z towards +x.
+        let mut quads = Vec::new();
+        cube.quads(7, 0, 90, &mut quads);
+        assert_eq!(quads[1].cull_face, 5);
+        assert_eq!(quads[4].cull_face, 1);
+        assert_near(Vec3::from(quads[1].normal), Vec3::Z);
+
+        let torch = &builtin_elements("minecraft:torch").unwrap()[0];
+        let mut quads = Vec::new();
+        torch.quads(0, 0, 0, &mut quads);
+        assert_eq!(quads[0].cull_face, NO_CULL_FACE);
+        assert_eq!(quads[2].cull_face, 2);
+    }
+
+    #[test]
+    fn maps_face_uvs() {
+        let mut slab = Element::untextured([0.0; 3], [1.0, 0.5, 1.0]);
+
+        // Without explicit UVs, faces take the part of the texture they would
+        // cover on a full cube: the lower half of the sides.
+        let mut quads = Vec::new();
+        slab.quads(0, 0, 0, &mut quads);
+        assert_eq!(
+            quads[0].uvs,
+            [[0.0, 1.0], [0.0, 0.5], [1.0, 0.5], [1.0, 1.0]]
+        );
+        assert_eq!(
+            quads[3].uvs,
+            [[0.0, 0.0], [0.0, 1.0], [1.0, 1.0], [1.0, 0.0]]
+        );
+
+        // Explicit UVs are stretched over the face, then rotated clockwise.
+        let face = slab.faces[3].as_mut().unwrap();
+        face.uv = Some([0.0, 0.0, 0.5, 0.5]);
+        let mut quads = Vec::new();
+        slab.quads(0, 0, 0, &mut quads);
+        assert_eq!(
+            quads[3].uvs,
+            [[0.0, 0.0], [0.0, 0.5], [0.5, 0.5], [0.5, 0.0]]
+        );
+
+        let face = slab.faces[3].as_mut().unwrap();
+        face.rotation = 90;
+        let mut quads = Vec::new();
+        slab.quads(0, 0, 0, &mut quads);
+        assert_eq!(
+            quads[3].uvs,
+            [[0.0, 0.5], [0.5, 0.5], [0.5, 0.0], [0.0, 0.0]]
+        );
+    }
+}
@@ -0,0 +1,328 @@
+/// How a block hides the faces of its neighbours.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Opacity {
+    /// A full cube that cannot be seen through, hiding every face touching it.
+    Opaque,
+    /// A full cube that can be seen through, like glass, leaves or water. It only
+    /// hides faces of blocks of the same kind, so that glass walls and bodies of
+    /// water don't show their inner faces.
+    Transparent,
+    /// A block not filling its whole cube, like slabs, fences, torches or flowers,
+    /// which never hides its neighbours' faces.
+    NonCube,
+}
+
+/// Blocks that are never drawn and are skipped while loading.
+pub const AIR: [&str; 3] = ["minecraft:air", "minecraft:cave_air", "minecraft:void_air"];
+
+const TRANSPARENT: &[&str] = &[
+    "glass",
+    "tinted_glass",
+    "ice",
+    "frosted_ice",
+    "water",
+    "bubble_column",
+    "slime_block",
+    "honey_block",
+    "spawner",
+    "trial_spawner",
+    "vault",
+    "beacon",
+    "barrier",
+    "mangrove_roots",
+    "nether_portal",
+];
+
+const TRANSPARENT_SUFFIXES: [&str; 2] = ["_glass", "_leaves"];
+
+const TRANSLUCENT: &[&str] = &[
+    "glass",
+    "glass_pane",
+    "tinted_glass",
+    "ice",
+    "frosted_ice",
+    "water",
+    "bubble_column",
+    "slime_block",
+    "honey_block",
+    "nether_portal",
+];
+
+const TRANSLUCENT_SUFFIXES: [&str; 2] = ["_stained_glass", "_stained_glass_pane"];
+
+const NON_CUBE: &[&str] = &[
+    "torch",
+    "soul_torch",
+    "redstone_torch",
+    "lantern",
+    "soul_lantern",
+    "chain",
+    "ladder",
+    "vine",
+    "lever",
+    "tripwire",
+    "tripwire_hook",
+    "redstone_wire",
+    "repeater",
+    "comparator",
+    "daylight_detector",
+    "rail",
+    "iron_bars",
+    "cobweb",
+    "scaffolding",
+    "farmland",
+    "dirt_path",
+    "cactus",
+    "cake",
+    "candle",
+    "chest",
+    "trapped_chest",
+    "ender_chest",
+    "flower_pot",
+    "anvil",
+    "chipped_anvil",
+    "damaged_anvil",
+    "enchanting_table",
+    "brewing_stand",
+    "cauldron",
+    "water_cauldron",
+    "lava_cauldron",
+    "powder_snow_cauldron",
+    "hopper",
+    "lectern",
+    "bell",
+    "campfire",
+    "soul_campfire",
+    "end_rod",
+    "lightning_rod",
+    "pointed_dripstone",
+    "grindstone",
+    "stonecutter",
+    "composter",
+    "conduit",
+    "dragon_egg",
+    "end_portal",
+    "end_portal_frame",
+    "end_gateway",
+    "piston_head",
+    "moving_piston",
+    "structure_void",
+    "light",
+    "heavy_core",
+    "decorated_pot",
+    "chorus_plant",
+    "chorus_flower",
+    "sea_pickle",
+    "lily_pad",
+    "turtle_egg",
+    "sniffer_egg",
+    "frogspawn",
+    "amethyst_cluster",
+    "big_dripleaf",
+    "big_dripleaf_stem",
+    "small_dripleaf",
+    "spore_blossom",
+    "hanging_roots",
+    "glow_lichen",
+    "sculk_vein",
+    "sculk_sensor",
+    "calibrated_sculk_sensor",
+    "sculk_shrieker",
+    "grass",
+    "short_grass",
+    "tall_grass",
+    "fern",
+    "large_fern",
+    "dead_bush",
+    "seagrass",
+    "tall_seagrass",
+    "kelp",
+    "kelp_plant",
+    "sugar_cane",
+    "bamboo",
+    "bamboo_sapling",
+    "wheat",
+    "carrots",
+    "potatoes",
+    "beetroots",
+    "melon_stem",
+    "pumpkin_stem",
+    "attached_melon_stem",
+    "attached_pumpkin_stem",
+    "nether_wart",
+    "sweet_berry_bush",
+    "cocoa",
+    "torchflower",
+    "torchflower_crop",
+    "pitcher_plant",
+    "pitcher_crop",
+    "azalea",
+    "flowering_azalea",
+    "nether_sprouts",
+    "dandelion",
+    "poppy",
+    "blue_orchid",
+    "allium",
+    "azure_bluet",
+    "oxeye_daisy",
+    "cornflower",
+    "lily_of_the_valley",
+    "wither_rose",
+    "sunflower",
+    "lilac",
+    "rose_bush",
+    "peony",
+    "fire",
+    "soul_fire",
+];
+
+const NON_CUBE_SUFFIXES: &[&str] = &[
+    "_slab",
+    "_stairs",
+    "_wall",
+    "_fence",
+    "_fence_gate",
+    "_pane",
+    "_door",
+    "_trapdoor",
+    "_sign",
+    "_button",
+    "_pressure_plate",
+    "_carpet",
+    "_bed",
+    "_banner",
+    "_torch",
+    "_lantern",
+    "_rail",
+    "_candle",
+    "_cake",
+    "_head",
+    "_skull",
+    "_sapling",
+    "_tulip",
+    "_mushroom",
+    "_fungus",
+    "_roots",
+    "_vines",
+    "_vines_plant",
+    "_coral",
+    "_fan",
+    "_bud",
+    "_petals",
+    "_bush",
+];
+
+impl Opacity {
+    /// Classifies a block state given as `namespace:name|prop=value,...`. Blocks
+    /// from other namespaces are assumed to be opaque.
+    pub fn of(state: &str) -> Self {
+        let (name, properties) = state.split_once('|').unwrap_or((state, ""));
+        let Some(name) = name.strip_prefix("minecraft:") else {
+            return Self::Opaque;
+        };
+        let has_property = |property| properties.split(',').any(|p| p == property);
+
+        if TRANSPARENT.contains(&name)
+            || TRANSPARENT_SUFFIXES
+                .iter()
+                .any(|suffix| name.ends_with(suffix))
+        {
+            return Self::Transparent;
+        }
+
+        // Muddy roots, double slabs and full snow layers fill their whole cube.
+        if name == "muddy_mangrove_roots"
+            || has_property("type=double")
+            || (name == "snow" && has_property("layers=8"))
+        {
+            return Self::Opaque;
+        }
+
+        if name == "snow"
+            || NON_CUBE.contains(&name)
+            || name.starts_with("potted_")
+            || NON_CUBE_SUFFIXES
+                .iter()
+                .any(|suffix| name.ends_with(suffix))
+        {
+            return Self::NonCube;
+        }
+
+        Self::Opaque
+    }
+
+    /// Whether a block with this opacity hides the face of a neighbour touching
+    /// it, `same_block` telling whether the neighbour is the same block.
+    pub fn hides(self, same_block: bool) -> bool {
+        match self {
+            Self::Opaque => true,
+            Self::Transparent => same_block,
+            Self::NonCube => false,
+        }
+    }
+}
+
+/// Whether a block state is partially see-through and has to be blended over what
+/// is behind it instead of being drawn with the opaque geometry.
+pub fn is_translucent(state: &str) -> bool {
+    let name = state.split_once('|').map_or(state, |(name, _)| name);
+
+    name.strip_prefix("minecraft:").is_some_and(|name| {
+        TRANSLUCENT.contains(&name)
+            || TRANSLUCENT_SUFFIXES
+                .iter()
+                .any(|suffix| name.ends_with(suffix))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_blocks() {
+        assert_eq!(Opacity::of("minecraft:stone"), Opacity::Opaque);
+        assert_eq!(Opacity::of("minecraft:glass"), Opacity::Transparent);
+        assert_eq!(
+            Opacity::of("minecraft:oak_leaves|distance=1,persistent=false"),
+            Opacity::Transparent
+        );
+        assert_eq!(Opacity::of("minecraft:torch"), Opacity::NonCube);
+        assert_eq!(
+            Opacity::of("minecraft:oak_stairs|facing=east,half=bottom"),
+            Opacity::NonCube
+        );
+        assert_eq!(Opacity::of("minecraft:potted_poppy"), Opacity::NonCube);
+    }
+
+    #[test]
+    fn full_variants_are_opaque() {
+        assert_eq!(
+            Opacity::of("minecraft:oak_slab|type=bottom"),
+            Opacity::NonCube
+        );
+        assert_eq!(
+            Opacity::of("minecraft:oak_slab|type=double"),
+            Opacity::Opaque
+        );
+        assert_eq!(Opacity::of("minecraft:snow|layers=1"), Opacity::NonCube);
+        assert_eq!(Opacity::of("minecraft:snow|layers=8"), Opacity::Opaque);
+        assert_eq!(
+            Opacity::of("minecraft:muddy_mangrove_roots"),
+            Opacity::Opaque
+        );
+    }
+
+    #[test]
+    fn other_namespaces_are_opaque() {
+        assert_eq!(Opacity::of("mod:glass"), Opacity::Opaque);
+    }
+
+    #[test]
+    fn transparent_blocks_only_hide_themselves() {
+        assert!(Opacity::Opaque.hides(false));
+        assert!(Opacity::Transparent.hides(true));
+        assert!(!Opacity::Transparent.hides(false));
+        assert!(!Opacity::NonCube.hides(true));
+    }
+}
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+/// Assigns a compact id to every block state encountered while loading a world.
+#[derive(Debug, Default)]
+pub struct Palette {
+    ids: HashMap<String, u16>,
+    states: Vec<String>,
+}
+
+impl Palette {
+    /// Returns the id of `state`, a block state as produced by
+    /// [`fastanvil::Block::encoded_description`], adding it if it is new.
+    pub fn id(&mut self, state: &str) -> u16 {
+        if let Some(&id) = self.ids.get(state) {
+            return id;
+        }
+
+        let id = u16::try_from(self.states.len()).expect("too many block states in palette");
+
+        self.ids.insert(state.to_owned(), id);
+        self.states.push(state.to_owned());
+
+        id
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    /// Full block state, e.g. `minecraft:oak_stairs|facing=east,half=bottom`.
+    pub fn state(&self, id: u16) -> &str {
+        &self.states[id as usize]
+    }
+
+    /// Block name without its properties, e.g. `minecraft:oak_stairs`.
+    pub fn name(&self, id: u16) -> &str {
+        let state = self.state(id);
+
+        state.split_once('|').map_or(state, |(name, _)| name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assigns_ids_in_order_of_first_use() {
+        let mut palette = Palette::default();
+
+        assert_eq!(palette.id("minecraft:stone"), 0);
+        assert_eq!(
+            palette.id("minecraft:oak_stairs|facing=east,half=bottom"),
+            1
+        );
+        assert_eq!(palette.id("minecraft:stone"), 0);
+        assert_eq!(palette.len(), 2);
+    }
+
+    #[test]
+    fn splits_names_from_properties() {
+        let mut palette = Palette::default();
+        let stairs = palette.id("minecraft:oak_stairs|facing=east,half=bottom");
+        let stone = palette.id("minecraft:stone");
+
+        assert_eq!(
+            palette.state(stairs),
+            "minecraft:oak_stairs|facing=east,half=bottom"
+        );
+        assert_eq!(palette.name(stairs), "minecraft:oak_stairs");
+        assert_eq!(palette.name(stone), "minecraft:stone");
+    }
+}
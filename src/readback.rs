@@ -0,0 +1,91 @@
+use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    Arc,
+};
+
+/// Steps of a [`Readback`], advanced by the mapping callback.
+const IDLE: u8 = 0;
+/// Copied in a frame that may not have been submitted yet.
+const COPIED: u8 = 1;
+const MAPPING: u8 = 2;
+const MAPPED: u8 = 3;
+
+/// Reads a GPU buffer back without ever waiting for the GPU. A frame copies the
+/// buffer when no past copy is still being read back, which later calls between
+/// frames map and read.
+#[derive(Debug)]
+pub struct Readback {
+    buffer: wgpu::Buffer,
+    /// One of the steps above.
+    step: Arc<AtomicU8>,
+}
+
+impl Readback {
+    pub fn new(device: &wgpu::Device, label: &str, size: u64) -> Self {
+        Self {
+            buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+            step: Arc::new(AtomicU8::new(IDLE)),
+        }
+    }
+
+    /// Copies `source` to be read back, unless a past copy still is. Returns
+    /// whether it was copied.
+    pub fn copy(&self, encoder: &mut wgpu::CommandEncoder, source: &wgpu::Buffer) -> bool {
+        if self.step.load(Ordering::Acquire) != IDLE {
+            return false;
+        }
+
+        encoder.copy_buffer_to_buffer(source, 0, &self.buffer, 0, self.buffer.size());
+        self.step.store(COPIED, Ordering::Release);
+
+        true
+    }
+
+    /// Reads the last copy with `read` once it has been mapped. Never waits for
+    /// the GPU, and has to be called between frames, after the frame copying it
+    /// was submitted.
+    pub fn poll_mapped<T>(
+        &self,
+        device: &wgpu::Device,
+        read: impl FnOnce(&[u8]) -> T,
+    ) -> Option<T> {
+        device.poll(wgpu::Maintain::Poll);
+
+        match self.step.load(Ordering::Acquire) {
+            COPIED => {
+                self.step.store(MAPPING, Ordering::Release);
+
+                let step = self.step.clone();
+                self.buffer
+                    .slice(..)
+                    .map_async(wgpu::MapMode::Read, move |result| {
+                        let next = match result {
+                            Ok(()) => MAPPED,
+                            Err(_) => IDLE,
+                        };
+                        step.store(next, Ordering::Release);
+                    });
+
+                None
+            }
+            MAPPED => {
+                let value = read(&self.buffer.slice(..).get_mapped_range());
+                self.buffer.unmap();
+                self.step.store(IDLE, Ordering::Release);
+
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether a copy is being read back.
+    pub fn is_pending(&self) -> bool {
+        self.step.load(Ordering::Acquire) != IDLE
+    }
+}
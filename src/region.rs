@@ -1,138 +1,1207 @@
 use std::{
     collections::{HashMap, HashSet},
+    error::Error,
+    fmt, io,
     io::{Read, Seek},
+    ops::Range,
+    sync::Mutex,
 };
 
 use fastanvil::{complete::Chunk, Chunk as _, HeightMode};
 
+use crate::{
+    biomes::{self, DEFAULT_BIOME},
+    light::{ChunkLight, MAX_LIGHT},
+    opacity::{self, Opacity, AIR},
+    palette::Palette,
+};
+
+/// Set on blocks drawn in the translucent pass.
+pub const TRANSLUCENT_BIT: u32 = 18;
+/// Width and height minus one of faces merged by greedy meshing, along the
+/// first and second axes following the face's own.
+const WIDTH_BIT: u32 = 19;
+const HEIGHT_BIT: u32 = 23;
+
+/// Coarsest level of detail, at which a cell stands for `2^MAX_LOD` blocks
+/// along every axis.
+pub const MAX_LOD: u32 = 3;
+/// Distance in blocks from the eye beyond which sections are drawn at level of
+/// detail 1, each further level starting twice as far. Matches `LOD_DISTANCE` in
+/// `chunks.wgsl`.
+pub const LOD_DISTANCE: f32 = 256.0;
+/// Level of detail of columns beyond the view distance, which only load their
+/// [`Column::surface`].
+pub const SURFACE_LOD: u32 = MAX_LOD + 1;
+/// Width in blocks of the cells of [`Column::surface`].
+pub const SURFACE_CELL_SIZE: usize = 4;
+const SURFACE_CELLS: usize = 16 / SURFACE_CELL_SIZE;
+/// Number of the 4×4×4 cells a section's biomes are stored for, like in
+/// Minecraft 1.18 and later. Older chunks have the same biome all the way up.
+pub const BIOME_CELLS: usize = 64;
+
+/// Level of detail sections `distance` blocks away from the eye are drawn at.
+pub fn lod(distance: f32) -> u32 {
+    if distance < LOD_DISTANCE {
+        return 0;
+    }
+
+    ((distance / LOD_DISTANCE).log2() as u32 + 1).min(MAX_LOD)
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LoadOptions {
+    /// Loads every section that contains blocks instead of only the ones between
+    /// the lowest and highest surface of each chunk, keeping caves and anything
+    /// built underground.
+    pub all_sections: bool,
+    /// Distance in chunks around the eye within which chunks are kept loaded.
+    pub view_distance: u32,
+    /// Skips chunks and regions that fail to load, returning them in
+    /// [`Skipped::regions`](crate::Skipped::regions), instead of stopping at the
+    /// first one.
+    pub lenient: bool,
+    /// Merges coplanar faces of the same block into rectangles, which makes flat
+    /// and distant terrain much cheaper to draw.
+    pub greedy_meshing: bool,
+    /// Draws distant sections from downsampled copies, and only loads the
+    /// levels of detail a column needs at its distance.
+    pub lod: bool,
+    /// Distance in chunks up to which the surface of the columns beyond the view
+    /// distance is loaded and ray-marched in the screen tiles left empty,
+    /// clamped to [`MAX_FAR_DISTANCE`](crate::world::MAX_FAR_DISTANCE). Nothing
+    /// is loaded beyond the view distance when it is not larger.
+    pub far_distance: u32,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            all_sections: false,
+            view_distance: 32,
+            lenient: false,
+            greedy_meshing: false,
+            lod: true,
+            far_distance: 0,
+        }
+    }
+}
+
+/// Why a region or one of its chunks could not be loaded.
 #[derive(Debug)]
-pub struct Region {
-    chunks: Vec<[u32; 2]>,
-    blocks: Vec<u32>,
-}
-
-impl Region {
-    pub fn new<S>(stream: S) -> Option<Self>
-    where
-        S: Read + Seek,
-    {
-        let mut region = fastanvil::Region::from_stream(stream).ok()?;
-        let mut blocks = Vec::new();
-        let mut chunks = Vec::new();
-
-        let mut block_set = HashSet::new();
-
-        for cx in 0..32 {
-            for cz in 0..32 {
-                if let Some(chunk) = region
-                    .read_chunk(cx, cz)
-                    .ok()?
-                    .and_then(|data| Chunk::from_bytes(&data).ok())
-                {
-                    let mut min = isize::MAX;
-                    let mut max = isize::MIN;
-                    for x in 0..16 {
-                        for z in 0..16 {
-                            let h = chunk.surface_height(x, z, HeightMode::Trust);
-
-                            min = min.min(h);
-                            max = max.max(h);
+pub enum RegionError {
+    /// Reading the region file at region coordinates `region` failed.
+    Io { region: [i32; 2], error: io::Error },
+    /// The chunk location table at the start of the region file is truncated.
+    BadHeader { region: [i32; 2] },
+    /// The chunk's data is truncated, corrupt or uses an unknown compression
+    /// scheme.
+    Decompression {
+        chunk: glam::IVec2,
+        error: fastanvil::Error,
+    },
+    /// The chunk's NBT is malformed.
+    Nbt {
+        chunk: glam::IVec2,
+        error: fastnbt::error::Error,
+    },
+    /// The chunk's NBT is readable but not laid out like any version fastanvil
+    /// knows.
+    UnsupportedVersion {
+        chunk: glam::IVec2,
+        data_version: i32,
+    },
+}
+
+impl RegionError {
+    /// Chunk coordinates of the chunk that failed, or `None` if the whole region
+    /// failed.
+    pub fn chunk(&self) -> Option<glam::IVec2> {
+        match self {
+            Self::Io { .. } | Self::BadHeader { .. } => None,
+            Self::Decompression { chunk, .. }
+            | Self::Nbt { chunk, .. }
+            | Self::UnsupportedVersion { chunk, .. } => Some(*chunk),
+        }
+    }
+}
+
+impl fmt::Display for RegionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io {
+                region: [x, z],
+                error,
+            } => write!(f, "region {x}, {z}: {error}"),
+            Self::BadHeader { region: [x, z] } => write!(f, "region {x}, {z}: truncated header"),
+            Self::Decompression { chunk, error } => {
+                write!(f, "chunk {}, {}: {error}", chunk.x, chunk.y)
+            }
+            Self::Nbt { chunk, error } => write!(f, "chunk {}, {}: {error}", chunk.x, chunk.y),
+            Self::UnsupportedVersion {
+                chunk,
+                data_version,
+            } => write!(
+                f,
+                "chunk {}, {}: unsupported data version {data_version}",
+                chunk.x, chunk.y
+            ),
+        }
+    }
+}
+
+impl Error for RegionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { error, .. } => Some(error),
+            Self::Decompression { error, .. } => Some(error),
+            Self::Nbt { error, .. } => Some(error),
+            Self::BadHeader { .. } | Self::UnsupportedVersion { .. } => None,
+        }
+    }
+}
+
+/// The part of a chunk read to tell a malformed chunk from an unsupported one.
+#[derive(serde::Deserialize)]
+struct ChunkVersion {
+    #[serde(rename = "DataVersion")]
+    data_version: i32,
+}
+
+/// The blocks of a chunk, split into the 16×16×16 sections drawn on the GPU.
+#[derive(Debug)]
+pub struct Column {
+    /// Chunk coordinates of the column.
+    pub pos: glam::IVec2,
+    /// Sections with at least one visible block.
+    pub sections: Vec<Section>,
+    /// Levels of detail the sections hold blocks for.
+    pub lods: Range<u32>,
+    /// Blocks along the column's sides, to cull faces against once the
+    /// neighbouring columns are loaded.
+    pub sides: Sides,
+    /// Highest block of each cell of [`SURFACE_CELL_SIZE`]² blocks, x first.
+    /// Holds the y above the block in the upper 16 bits and its [`Palette`] id
+    /// plus one in the lower ones, or 0 for cells without blocks, then the
+    /// block's biome in the lower 8 bits and the block light and sky light above
+    /// it in the next 4 bits each, like [`shading`].
+    pub surface: [[u32; 2]; SURFACE_CELLS * SURFACE_CELLS],
+}
+
+/// The blocks touching the four vertical sides of a column, in the sections it
+/// loaded. Anything below those sections is below every surface of the chunk
+/// and taken to be solid, anything above is taken to be air.
+#[derive(Debug, Default)]
+pub struct Sides {
+    /// Lowest loaded block y.
+    pub min_y: i32,
+    /// `[-x, +x, -z, +z]` sides, indexed by `(y - min_y) * 16 + i` where `i` is
+    /// the block's z on x sides and x on z sides. Holds the [`Palette`] id plus
+    /// one of blocks that can hide faces, and 0 for air and non-cube blocks.
+    pub cells: [Vec<u16>; 4],
+    /// Sky light in the upper 4 bits and block light in the lower ones of every
+    /// block of `cells`, to light the faces of the neighbouring columns with.
+    pub light: [Vec<u8>; 4],
+}
+
+impl Sides {
+    /// Palette id of the block at `y` and `i` on `side`, `Err(true)` below the
+    /// loaded sections and `Err(false)` above them or for blocks that never hide
+    /// faces.
+    pub fn get(&self, side: usize, y: i32, i: u32) -> Result<u16, bool> {
+        if y < self.min_y {
+            return Err(true);
+        }
+
+        let cell = (y - self.min_y) as usize * 16 + i as usize;
+        match self.cells[side].get(cell) {
+            Some(&id) if id > 0 => Ok(id - 1),
+            _ => Err(false),
+        }
+    }
+
+    /// `(sky, block)` light levels at `y` and `i` on `side`, `None` outside the
+    /// loaded sections.
+    pub fn light(&self, side: usize, y: i32, i: u32) -> Option<(u8, u8)> {
+        let cell = usize::try_from(y - self.min_y).ok()? * 16 + i as usize;
+        let light = *self.light[side].get(cell)?;
+
+        Some((light >> 4, light & 0b1111))
+    }
+}
+
+#[derive(Debug)]
+pub struct Section {
+    /// Section coordinate along y, i.e. the section's lowest block divided by 16.
+    pub y: i32,
+    /// `[packed, id, shading, shading, shading]` where `packed` holds the
+    /// position inside the section, the visible faces, whether the block is
+    /// translucent and, for faces merged by greedy meshing, their size, `id` is
+    /// the block's [`Palette`] id, and the last three hold the [`shading`] of the
+    /// visible faces, 16 bits per face in the order of the face bits. Empty below
+    /// [`Column::lods`].
+    pub blocks: Vec<[u32; 5]>,
+    /// Cells of the downsampled copies of the section at levels of detail 1 to
+    /// [`MAX_LOD`], laid out like `blocks` with their position being the cell's
+    /// lowest block.
+    pub lod_blocks: [Vec<[u32; 5]>; MAX_LOD as usize],
+    /// [`biomes::biome_id`] of each 4×4×4 cell of the section, indexed by
+    /// `(y * 4 + z) * 4 + x` in cells.
+    pub biomes: [u8; BIOME_CELLS],
+}
+
+impl Section {
+    /// Blocks or cells of the section at level of detail `lod`.
+    pub fn lod(&self, lod: u32) -> &[[u32; 5]] {
+        match lod {
+            0 => &self.blocks,
+            _ => &self.lod_blocks[lod as usize - 1],
+        }
+    }
+}
+
+/// A block state found in a column, resolved once against the shared palette.
+struct ColumnState<'a> {
+    id: u16,
+    name: &'a str,
+    opacity: Opacity,
+    translucent: bool,
+}
+
+pub struct Region<S> {
+    region: fastanvil::Region<S>,
+    pos: [i32; 2],
+}
+
+impl<S> Region<S>
+where
+    S: Read + Seek,
+{
+    /// Opens the region at region coordinates `[x, z]`, i.e. the one stored in
+    /// `r.x.z.mca`.
+    pub fn new(stream: S, pos: [i32; 2]) -> Result<Self, RegionError> {
+        let region = fastanvil::Region::from_stream(stream).map_err(|error| match error {
+            fastanvil::Error::IO(error) if error.kind() != io::ErrorKind::UnexpectedEof => {
+                RegionError::Io { region: pos, error }
+            }
+            _ => RegionError::BadHeader { region: pos },
+        })?;
+
+        Ok(Self { region, pos })
+    }
+
+    /// Reads the chunk at `[x, z]` inside the region, with `x` and `z` in
+    /// `0..32`, and culls the faces hidden inside it. Levels of detail finer than
+    /// `min_lod` are left out, and every section at [`SURFACE_LOD`]. A chunk
+    /// that was never generated gives an empty column. Block states are added to
+    /// `palette` as they are found.
+    pub fn column(
+        &mut self,
+        [x, z]: [usize; 2],
+        options: LoadOptions,
+        min_lod: u32,
+        palette: &Mutex<Palette>,
+    ) -> Result<Column, RegionError> {
+        let lods = if min_lod >= SURFACE_LOD {
+            SURFACE_LOD..SURFACE_LOD
+        } else if options.lod {
+            min_lod..MAX_LOD + 1
+        } else {
+            0..1
+        };
+        let pos = glam::IVec2::new(self.pos[0] * 32 + x as i32, self.pos[1] * 32 + z as i32);
+
+        let data = self.region.read_chunk(x, z).map_err(|error| match error {
+            // Reading past the end of the file or inflating garbage means the
+            // chunk itself is damaged.
+            fastanvil::Error::IO(error)
+                if !matches!(
+                    error.kind(),
+                    io::ErrorKind::UnexpectedEof
+                        | io::ErrorKind::InvalidData
+                        | io::ErrorKind::InvalidInput
+                ) =>
+            {
+                RegionError::Io {
+                    region: self.pos,
+                    error,
+                }
+            }
+            error => RegionError::Decompression { chunk: pos, error },
+        })?;
+        let Some(data) = data else {
+            return Ok(Column {
+                pos,
+                sections: Vec::new(),
+                lods,
+                sides: Sides::default(),
+                surface: Default::default(),
+            });
+        };
+        // Decoded once, for both the blocks and the light.
+        let nbt: fastnbt::Value =
+            fastnbt::from_bytes(&data).map_err(|error| RegionError::Nbt { chunk: pos, error })?;
+        let chunk = chunk_from_nbt(&nbt).map_err(|error| {
+            match fastnbt::from_value::<ChunkVersion>(&nbt) {
+                Ok(version) => RegionError::UnsupportedVersion {
+                    chunk: pos,
+                    data_version: version.data_version,
+                },
+                Err(_) => RegionError::Nbt { chunk: pos, error },
+            }
+        })?;
+
+        let light =
+            ChunkLight::from_nbt(&nbt).map_err(|error| RegionError::Nbt { chunk: pos, error })?;
+
+        let surface = surface(&chunk, &light, palette);
+        if lods.is_empty() {
+            return Ok(Column {
+                pos,
+                sections: Vec::new(),
+                lods,
+                sides: Sides::default(),
+                surface,
+            });
+        }
+
+        let sections = if options.all_sections {
+            let y_range = chunk.y_range();
+            y_range.start.div_euclid(16)..=(y_range.end - 1).div_euclid(16)
+        } else {
+            let mut min = isize::MAX;
+            let mut max = isize::MIN;
+            for x in 0..16 {
+                for z in 0..16 {
+                    let h = chunk.surface_height(x, z, HeightMode::Trust);
+
+                    min = min.min(h);
+                    max = max.max(h);
+                }
+            }
+
+            min.div_euclid(16)..=max.div_euclid(16)
+        };
+
+        let mut states = Vec::new();
+        let mut state_indices = HashMap::new();
+        let mut block_states = HashMap::new();
+        let mut sections_blocks = Vec::new();
+        let band = (*sections.start() as i32 * 16)..((*sections.end() as i32 + 1) * 16);
+
+        for cy in sections {
+            let mut blocks = Vec::new();
+
+            for x in 0..16 {
+                for y in 0..16 {
+                    for z in 0..16 {
+                        let Some(block) = chunk.block(x, y + cy * 16, z) else {
+                            continue;
+                        };
+                        if AIR.contains(&block.name()) {
+                            continue;
+                        }
+
+                        let state = block.encoded_description();
+                        let index = *state_indices.entry(state).or_insert_with(|| {
+                            states.push(ColumnState {
+                                id: palette.lock().unwrap().id(state),
+                                name: block.name(),
+                                opacity: Opacity::of(state),
+                                translucent: opacity::is_translucent(state),
+                            });
+
+                            states.len() - 1
+                        });
+
+                        let key = glam::IVec3::new(x as i32, y as i32 + cy as i32 * 16, z as i32);
+                        block_states.insert(key, index);
+
+                        blocks.push(((z << 8) as u32 | (y << 4) as u32 | x as u32, index));
+                    }
+                }
+            }
+
+            sections_blocks.push((cy as i32, blocks, section_biomes(&chunk, cy)));
+        }
+
+        // Blocks of the neighbouring columns aren't known here, and are taken to
+        // leave the faces next to them open.
+        let occludes = |key: glam::IVec3| {
+            if !(0..16).contains(&key.x) || !(0..16).contains(&key.z) {
+                return false;
+            }
+
+            if band.contains(&key.y) {
+                return block_states.get(&key).is_some_and(|&index| {
+                    let state = &states[index];
+
+                    occludes_light(state.opacity, state.translucent)
+                });
+            }
+
+            chunk
+                .block(key.x as usize, key.y as isize, key.z as usize)
+                .filter(|block| !AIR.contains(&block.name()))
+                .is_some_and(|block| {
+                    let state = block.encoded_description();
+
+                    occludes_light(Opacity::of(state), opacity::is_translucent(state))
+                })
+        };
+
+        let mut visible = HashSet::new();
+        let sections: Vec<_> = sections_blocks
+            .into_iter()
+            .map(|(y, blocks, biomes)| {
+                let blocks: Vec<_> = blocks
+                    .into_iter()
+                    .filter_map(|(block, index)| {
+                        let state = &states[index];
+                        let key = glam::IVec3::new(
+                            (block & 0b1111) as i32,
+                            ((block >> 4) & 0b1111) as i32 + y * 16,
+                            ((block >> 8) & 0b1111) as i32,
+                        );
+
+                        let neighbors = [
+                            glam::IVec3::NEG_X,
+                            glam::IVec3::X,
+                            glam::IVec3::NEG_Y,
+                            glam::IVec3::Y,
+                            glam::IVec3::NEG_Z,
+                            glam::IVec3::Z,
+                        ]
+                        .map(|offset| {
+                            let neighbor = key + offset;
+
+                            if band.contains(&neighbor.y) {
+                                return block_states.get(&neighbor).is_some_and(|&neighbor| {
+                                    let neighbor = &states[neighbor];
+
+                                    neighbor.opacity.hides(neighbor.name == state.name)
+                                });
+                            }
+
+                            // Above or below the loaded sections, but still in the chunk.
+                            chunk
+                                .block(
+                                    neighbor.x as usize,
+                                    neighbor.y as isize,
+                                    neighbor.z as usize,
+                                )
+                                .filter(|neighbor| !AIR.contains(&neighbor.name()))
+                                .is_some_and(|neighbor| {
+                                    Opacity::of(neighbor.encoded_description())
+                                        .hides(neighbor.name() == state.name)
+                                })
+                        });
+                        let face_bit_set = neighbors
+                            .into_iter()
+                            .enumerate()
+                            .fold(0, |set, (i, hidden)| set | (((!hidden) as u8) << i));
+
+                        let translucent_bit = (state.translucent as u32) << TRANSLUCENT_BIT;
+
+                        if face_bit_set.count_ones() > 0 {
+                            visible.insert(key);
+                        }
+
+                        // Models are lit by the light at their own position.
+                        let mut record = [
+                            translucent_bit | ((face_bit_set as u32) << 12) | block,
+                            state.id as u32,
+                            0,
+                            0,
+                            0,
+                        ];
+                        for face in 0..6 {
+                            let shading = match state.opacity {
+                                Opacity::NonCube => shading(0, light.get(key)),
+                                _ if face_bit_set & (1 << face) != 0 => shading(
+                                    face_ao(face, key, occludes),
+                                    face_light(&light, face, key),
+                                ),
+                                _ => continue,
+                            };
+
+                            record[2 + face / 2] |= shading << (face % 2 * 16);
+                        }
+
+                        // Models can have faces inside their cube that no neighbour hides.
+                        (face_bit_set.count_ones() > 0 || state.opacity == Opacity::NonCube)
+                            .then_some((record, state.opacity != Opacity::NonCube))
+                    })
+                    .collect();
+
+                let blocks = if lods.start > 0 {
+                    Vec::new()
+                } else if options.greedy_meshing {
+                    merge_faces(blocks, &biomes)
+                } else {
+                    blocks.into_iter().map(|(block, _)| block).collect()
+                };
+
+                (y, blocks, biomes)
+            })
+            .collect();
+
+        let mut lod_blocks = if lods.end > 1 {
+            downsample(&states, &block_states, &visible, band.clone())
+        } else {
+            HashMap::new()
+        };
+
+        let sections = sections
+            .into_iter()
+            .filter_map(|(y, blocks, biomes)| {
+                let mut lod_blocks = lod_blocks.remove(&y).unwrap_or_default();
+                for lod in 1..lods.start {
+                    lod_blocks[lod as usize - 1].clear();
+                }
+
+                (!blocks.is_empty() || lod_blocks.iter().any(|blocks| !blocks.is_empty()))
+                    .then_some(Section {
+                        y,
+                        blocks,
+                        lod_blocks,
+                        biomes,
+                    })
+            })
+            .collect();
+
+        let mut sides = Sides {
+            min_y: band.start,
+            cells: Default::default(),
+            light: Default::default(),
+        };
+        for y in band {
+            for i in 0..16 {
+                let keys = [
+                    glam::IVec3::new(0, y, i),
+                    glam::IVec3::new(15, y, i),
+                    glam::IVec3::new(i, y, 0),
+                    glam::IVec3::new(i, y, 15),
+                ];
+
+                for (side, key) in keys.into_iter().enumerate() {
+                    let cell = block_states.get(&key).map_or(0, |&index| {
+                        let state = &states[index];
+
+                        match state.opacity {
+                            Opacity::NonCube => 0,
+                            _ => state.id + 1,
+                        }
+                    });
+                    let (sky, block) = light.get(key);
+
+                    sides.cells[side].push(cell);
+                    sides.light[side].push(sky << 4 | block);
+                }
+            }
+        }
+
+        Ok(Column {
+            pos,
+            sections,
+            lods,
+            sides,
+            surface,
+        })
+    }
+}
+
+/// Reads a chunk from its decoded NBT, trying every layout fastanvil knows like
+/// [`fastanvil::JavaChunk::from_bytes`] does.
+fn chunk_from_nbt(nbt: &fastnbt::Value) -> Result<Chunk, fastnbt::error::Error> {
+    fastnbt::from_value::<fastanvil::CurrentJavaChunk>(nbt)
+        .map(Chunk::from)
+        .or_else(|_| fastnbt::from_value::<fastanvil::pre18::JavaChunk>(nbt).map(Chunk::from))
+        .or_else(|_| fastnbt::from_value::<fastanvil::pre13::JavaChunk>(nbt).map(Chunk::from))
+}
+
+/// Biomes of the cells of the section at `cy`, defaulting to [`DEFAULT_BIOME`]
+/// where the chunk has none.
+fn section_biomes(chunk: &Chunk, cy: isize) -> [u8; BIOME_CELLS] {
+    std::array::from_fn(|cell| {
+        let (x, z, y) = (cell % 4, cell / 4 % 4, cell / 16);
+        let biome = chunk
+            .biome(x * 4, cy * 16 + y as isize * 4, z * 4)
+            .unwrap_or(DEFAULT_BIOME);
+
+        biomes::biome_id(biome)
+    })
+}
+
+/// Finds the highest block of each cell of a column's [`Column::surface`] from
+/// its height map, with its biome and the light above it.
+fn surface(
+    chunk: &Chunk,
+    light: &ChunkLight,
+    palette: &Mutex<Palette>,
+) -> [[u32; 2]; SURFACE_CELLS * SURFACE_CELLS] {
+    let mut surface = [[0; 2]; SURFACE_CELLS * SURFACE_CELLS];
+    let y_range = chunk.y_range();
+
+    for (i, cell) in surface.iter_mut().enumerate() {
+        let cell_x = i % SURFACE_CELLS * SURFACE_CELL_SIZE;
+        let cell_z = i / SURFACE_CELLS * SURFACE_CELL_SIZE;
+
+        let mut top = None;
+        for x in cell_x..cell_x + SURFACE_CELL_SIZE {
+            for z in cell_z..cell_z + SURFACE_CELL_SIZE {
+                // Height maps can count blocks drawn as air, such as light
+                // blocks, so air is skipped downwards.
+                let mut y = chunk.surface_height(x, z, HeightMode::Trust) - 1;
+                while y >= y_range.start && top.is_none_or(|(_, top_y, _, _)| y > top_y) {
+                    match chunk.block(x, y, z) {
+                        Some(block) if !AIR.contains(&block.name()) => {
+                            top = Some((x, y, z, block));
+                            break;
                         }
+                        _ => y -= 1,
                     }
+                }
+            }
+        }
+
+        if let Some((x, y, z, block)) = top {
+            let id = palette.lock().unwrap().id(block.encoded_description());
+            let biome = biomes::biome_id(chunk.biome(x, y, z).unwrap_or(DEFAULT_BIOME));
+            let above = glam::IVec3::new(x as i32, y as i32 + 1, z as i32);
 
-                    for cy in min / 16..=max / 16 {
-                        let start = blocks.len() as u32;
-                        let pos = u32::from_le_bytes([cx as u8, (cy + 4) as u8, cz as u8, 0]);
+            *cell = [
+                ((y + 1) as i16 as u16 as u32) << 16 | (id as u32 + 1),
+                shading(biome as u32, light.get(above)),
+            ];
+        }
+    }
+
+    surface
+}
+
+/// Whether a block darkens the corners of the faces next to it: full cubes that
+/// aren't blended over what is behind them, leaves included.
+fn occludes_light(opacity: Opacity, translucent: bool) -> bool {
+    opacity != Opacity::NonCube && !translucent
+}
+
+/// Shading of a face packed in 16 bits: its ambient occlusion from [`face_ao`]
+/// in the lower 8, then the block light and sky light from `(sky, block)`, 4
+/// bits each.
+pub fn shading(ao: u32, (sky, block): (u8, u8)) -> u32 {
+    ao | (block as u32) << 8 | (sky as u32) << 12
+}
+
+/// Light levels of the block in front of face `face` of the block at `key`, or
+/// above the block for faces towards the neighbouring columns, which aren't
+/// known here and relight those faces from their [`Sides::light`] once loaded.
+fn face_light(light: &ChunkLight, face: usize, key: glam::IVec3) -> (u8, u8) {
+    let mut front = key;
+    front[face / 2] += if face % 2 == 1 { 1 } else { -1 };
+
+    if (0..16).contains(&front.x) && (0..16).contains(&front.z) {
+        light.get(front)
+    } else {
+        light.get(key + glam::IVec3::Y)
+    }
+}
+
+/// Minecraft-style ambient occlusion of the four corners of face `face` of the
+/// block at `key`, from the blocks `occludes` says are around the corner in
+/// front of the face. Each corner takes 2 bits, from 0 for open corners to 3
+/// for corners darkened by both blocks along the face's edges, and corners go
+/// `u + 2 * v` along the first and second axes following the face's own, like
+/// in `faces.wgsl`.
+fn face_ao(face: usize, key: glam::IVec3, occludes: impl Fn(glam::IVec3) -> bool) -> u32 {
+    let axis = face / 2;
+
+    let mut front = key;
+    front[axis] += if face % 2 == 1 { 1 } else { -1 };
+
+    (0..4).fold(0, |ao, corner| {
+        let mut u = glam::IVec3::ZERO;
+        u[(axis + 1) % 3] = if corner & 1 == 1 { 1 } else { -1 };
+        let mut v = glam::IVec3::ZERO;
+        v[(axis + 2) % 3] = if corner & 2 == 2 { 1 } else { -1 };
+
+        let side_u = occludes(front + u);
+        let side_v = occludes(front + v);
+        let level = if side_u && side_v {
+            3
+        } else {
+            side_u as u32 + side_v as u32 + occludes(front + u + v) as u32
+        };
+
+        ao | level << (corner * 2)
+    })
+}
+
+/// Greedy meshing of a section: merges the faces of full cubes lying in the same
+/// plane and belonging to the same block into rectangles, each stored as a block
+/// with a single face and a size. `blocks` are `(block, is_cube)` pairs.
+///
+/// Only faces with the same ambient occlusion at all four corners are merged,
+/// and only with faces lit the same and in the same biome, since a rectangle
+/// can't show the shading or tint of the faces inside it. Faces on
+/// the column's sides are left alone so that they can still be culled
+/// one by one once the neighbouring column is loaded.
+fn merge_faces(blocks: Vec<([u32; 5], bool)>, biomes: &[u8; BIOME_CELLS]) -> Vec<[u32; 5]> {
+    let mut merged = Vec::with_capacity(blocks.len());
+    // `[translucent_bit, id, shading, biome]` of the faces to merge, indexed by face,
+    // then by layer along the face's axis, then by the two other axes.
+    let mut planes = vec![None; 6 * 16 * 16 * 16];
+    let plane_index = |face: usize, [layer, u, v]: [u32; 3]| {
+        ((face * 16 + layer as usize) * 16 + v as usize) * 16 + u as usize
+    };
+
+    for (record, is_cube) in blocks {
+        let [block, id, ..] = record;
+        let pos = [block & 0b1111, (block >> 4) & 0b1111, (block >> 8) & 0b1111];
+        let mut faces = (block >> 12) & 0b11_1111;
+
+        if is_cube {
+            for face in 0..6 {
+                let axis = face / 2;
+                let on_side = match face {
+                    0 => pos[0] == 0,
+                    1 => pos[0] == 15,
+                    4 => pos[2] == 0,
+                    5 => pos[2] == 15,
+                    _ => false,
+                };
+
+                let shading = (record[2 + face / 2] >> (face % 2 * 16)) & 0xffff;
+                let ao = shading & 0xff;
+                let uniform_ao = ao == (ao & 0b11) * 0b0101_0101;
+
+                if faces & (1 << face) != 0 && !on_side && uniform_ao {
+                    faces &= !(1 << face);
+
+                    let key = [pos[axis], pos[(axis + 1) % 3], pos[(axis + 2) % 3]];
+                    let cell = ((pos[1] / 4 * 4 + pos[2] / 4) * 4 + pos[0] / 4) as usize;
+
+                    planes[plane_index(face, key)] = Some([
+                        block & (1 << TRANSLUCENT_BIT),
+                        id,
+                        shading,
+                        biomes[cell] as u32,
+                    ]);
+                }
+            }
+        }
+
+        if faces != 0 || !is_cube {
+            let mut record = record;
+            record[0] = (block & !(0b11_1111 << 12)) | (faces << 12);
+
+            merged.push(record);
+        }
+    }
+
+    for face in 0..6 {
+        let axis = face / 2;
+
+        for layer in 0..16 {
+            for v in 0..16 {
+                for u in 0..16 {
+                    let Some(key) = planes[plane_index(face, [layer, u, v])] else {
+                        continue;
+                    };
+
+                    let mut width = 1;
+                    while u + width < 16
+                        && planes[plane_index(face, [layer, u + width, v])] == Some(key)
+                    {
+                        width += 1;
+                    }
+
+                    let mut height = 1;
+                    while v + height < 16
+                        && (u..u + width)
+                            .all(|u| planes[plane_index(face, [layer, u, v + height])] == Some(key))
+                    {
+                        height += 1;
+                    }
+
+                    for v in v..v + height {
+                        for u in u..u + width {
+                            planes[plane_index(face, [layer, u, v])] = None;
+                        }
+                    }
+
+                    let mut pos = [0; 3];
+                    pos[axis] = layer;
+                    pos[(axis + 1) % 3] = u;
+                    pos[(axis + 2) % 3] = v;
+
+                    let [translucent_bit, id, shading, _] = key;
+                    let mut record = [
+                        pos[0]
+                            | (pos[1] << 4)
+                            | (pos[2] << 8)
+                            | (1 << (12 + face))
+                            | translucent_bit
+                            | ((width - 1) << WIDTH_BIT)
+                            | ((height - 1) << HEIGHT_BIT),
+                        id,
+                        0,
+                        0,
+                        0,
+                    ];
+                    record[2 + face / 2] = shading << (face % 2 * 16);
+
+                    merged.push(record);
+                }
+            }
+        }
+    }
+
+    merged
+}
 
-                        for x in 0..16 {
-                            for y in 0..16 {
-                                for z in 0..16 {
-                                    if let Some(block) = chunk.block(x, y + cy * 16, z) {
-                                        if block.name() != "minecraft:air" {
-                                            let key_x = (x + cx * 16) as i16;
-                                            let key_y = (y + (cy + 4) * 16) as i16;
-                                            let key_z = (z + cz * 16) as i16;
+/// Downsampled copies of a column's loaded sections for levels of detail 1 to
+/// [`MAX_LOD`], keyed by section coordinate. A cell is filled when at least half
+/// of it is made of full cubes, with the block seen the most among its visible
+/// blocks. Faces are culled between cells of the column, and against whatever
+/// is below the loaded sections, which is taken to be solid.
+/// Cells have no ambient occlusion and are lit by the sky.
+fn downsample(
+    states: &[ColumnState],
+    block_states: &HashMap<glam::IVec3, usize>,
+    visible: &HashSet<glam::IVec3>,
+    band: Range<i32>,
+) -> HashMap<i32, [Vec<[u32; 5]>; MAX_LOD as usize]> {
+    let mut sections: HashMap<i32, [Vec<[u32; 5]>; MAX_LOD as usize]> = HashMap::new();
 
-                                            block_set.insert([key_x, key_y, key_z]);
+    for lod in 1..=MAX_LOD {
+        let scale = 1 << lod;
+        let size = glam::IVec3::new(16, band.len() as i32, 16) / scale;
+        let cell_index =
+            |cell: glam::IVec3| ((cell.y * size.z + cell.z) * size.x + cell.x) as usize;
 
-                                            blocks
-                                                .push((z << 8) as u32 | (y << 4) as u32 | x as u32);
-                                        }
+        let mut cells = vec![None; (size.x * size.y * size.z) as usize];
+        for y in 0..size.y {
+            for z in 0..size.z {
+                for x in 0..size.x {
+                    let cell = glam::IVec3::new(x, y, z);
+                    let origin = cell * scale + glam::IVec3::Y * band.start;
+
+                    // `(state, visible count, count)` of the cubes in the cell.
+                    let mut counts: Vec<(usize, u32, u32)> = Vec::new();
+                    let mut cubes = 0;
+                    for dy in 0..scale {
+                        for dz in 0..scale {
+                            for dx in 0..scale {
+                                let key = origin + glam::IVec3::new(dx, dy, dz);
+                                let Some(&index) = block_states.get(&key) else {
+                                    continue;
+                                };
+                                if states[index].opacity == Opacity::NonCube {
+                                    continue;
+                                }
+
+                                cubes += 1;
+
+                                let is_visible = visible.contains(&key) as u32;
+                                match counts.iter_mut().find(|(state, ..)| *state == index) {
+                                    Some((_, visible, count)) => {
+                                        *visible += is_visible;
+                                        *count += 1;
                                     }
+                                    None => counts.push((index, is_visible, 1)),
                                 }
                             }
                         }
+                    }
 
-                        let end = blocks.len() as u32;
-                        chunks.push([end - start, pos]);
+                    if cubes * 2 >= scale * scale * scale {
+                        cells[cell_index(cell)] = counts
+                            .into_iter()
+                            .max_by_key(|&(_, visible, count)| (visible, count))
+                            .map(|(state, ..)| state);
                     }
                 }
             }
         }
 
-        let chunks_iter = chunks
-            .iter()
-            .map(|&[len, pos]| (0..len).into_iter().map(move |_| pos))
-            .flatten();
+        let neighbors = [
+            glam::IVec3::NEG_X,
+            glam::IVec3::X,
+            glam::IVec3::NEG_Y,
+            glam::IVec3::Y,
+            glam::IVec3::NEG_Z,
+            glam::IVec3::Z,
+        ];
 
-        let mut chunk_map: HashMap<_, _> = chunks.iter().map(|&[len, pos]| (pos, len)).collect();
+        for y in 0..size.y {
+            for z in 0..size.z {
+                for x in 0..size.x {
+                    let cell = glam::IVec3::new(x, y, z);
+                    let Some(index) = cells[cell_index(cell)] else {
+                        continue;
+                    };
+                    let state = &states[index];
 
-        let blocks: Vec<_> = blocks
-            .into_iter()
-            .zip(chunks_iter)
-            .filter_map(|(block, pos)| {
-                let [cx, cy, cz, _] = pos.to_le_bytes();
-
-                let x = block & 0b1111;
-                let y = (block >> 4) & 0b1111;
-                let z = (block >> 8) & 0b1111;
-
-                let key_x = (x + cx as u32 * 16) as i16;
-                let key_y = (y + cy as u32 * 16) as i16;
-                let key_z = (z + cz as u32 * 16) as i16;
-
-                let is_in_set =
-                    |key_x: i16, key_y: i16, key_z: i16| block_set.contains(&[key_x, key_y, key_z]);
-
-                let neighbors = [
-                    is_in_set(key_x - 1, key_y, key_z),
-                    is_in_set(key_x + 1, key_y, key_z),
-                    is_in_set(key_x, key_y - 1, key_z),
-                    is_in_set(key_x, key_y + 1, key_z),
-                    is_in_set(key_x, key_y, key_z - 1),
-                    is_in_set(key_x, key_y, key_z + 1),
-                ];
-                let face_bit_set = neighbors
-                    .into_iter()
-                    .enumerate()
-                    .fold(0, |set, (i, has_neighbor)| {
-                        set | (((!has_neighbor) as u8) << i)
+                    let face_bit_set = neighbors.iter().enumerate().fold(0, |set, (i, &offset)| {
+                        let neighbor = cell + offset;
+                        let hidden = if neighbor.cmplt(glam::IVec3::ZERO).any()
+                            || neighbor.cmpge(size).any()
+                        {
+                            neighbor.y < 0
+                        } else {
+                            cells[cell_index(neighbor)].is_some_and(|neighbor| {
+                                let neighbor = &states[neighbor];
+
+                                neighbor.opacity.hides(neighbor.name == state.name)
+                            })
+                        };
+
+                        set | ((!hidden as u32) << i)
                     });
 
-                if face_bit_set.count_ones() > 0 {
-                    Some(((face_bit_set as u32) << 12) | block)
-                } else {
-                    if let Some(len) = chunk_map.get_mut(&pos) {
-                        *len -= 1;
+                    if face_bit_set == 0 {
+                        continue;
                     }
 
-                    None
+                    let pos = cell * scale + glam::IVec3::Y * band.start;
+                    let section_y = pos.y.div_euclid(16);
+                    let block = (pos.x as u32)
+                        | ((pos.y.rem_euclid(16) as u32) << 4)
+                        | ((pos.z as u32) << 8)
+                        | (face_bit_set << 12)
+                        | ((state.translucent as u32) << TRANSLUCENT_BIT);
+
+                    let sky_lit = shading(0, (MAX_LIGHT, 0)) * 0x1_0001;
+                    sections.entry(section_y).or_default()[lod as usize - 1].push([
+                        block,
+                        state.id as u32,
+                        sky_lit,
+                        sky_lit,
+                        sky_lit,
+                    ]);
+                }
+            }
+        }
+    }
+
+    sections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A block record at `pos` in its section showing `faces`, unlit and without
+    /// ambient occlusion.
+    fn block(pos: [u32; 3], faces: u32, id: u32) -> [u32; 5] {
+        [
+            pos[0] | pos[1] << 4 | pos[2] << 8 | faces << 12,
+            id,
+            0,
+            0,
+            0,
+        ]
+    }
+
+    #[test]
+    fn merges_coplanar_faces_of_the_same_block() {
+        let blocks = [[1, 0, 1], [2, 0, 1], [1, 0, 2], [2, 0, 2]]
+            .map(|pos| (block(pos, 1 << 3, 7), true))
+            .to_vec();
+
+        let merged = merge_faces(blocks, &[0; BIOME_CELLS]);
+
+        // One 2×2 top face, along z then x.
+        assert_eq!(
+            merged,
+            [[
+                block([1, 0, 1], 1 << 3, 7)[0] | 1 << WIDTH_BIT | 1 << HEIGHT_BIT,
+                7,
+                0,
+                0,
+                0
+            ]]
+        );
+    }
+
+    #[test]
+    fn keeps_faces_of_different_blocks_apart() {
+        let blocks = vec![
+            (block([1, 0, 1], 1 << 3, 7), true),
+            (block([1, 0, 2], 1 << 3, 8), true),
+        ];
+
+        let merged = merge_faces(blocks, &[0; BIOME_CELLS]);
+
+        assert_eq!(
+            merged,
+            [block([1, 0, 1], 1 << 3, 7), block([1, 0, 2], 1 << 3, 8)]
+        );
+    }
+
+    #[test]
+    fn keeps_faces_on_the_column_sides_and_of_non_cubes() {
+        let side = block([0, 0, 1], 1 << 0 | 1 << 3, 7);
+        let non_cube = block([4, 0, 4], 1 << 3, 9);
+
+        let merged = merge_faces(vec![(side, true), (non_cube, false)], &[0; BIOME_CELLS]);
+
+        // The side face stays on the block, and its top face is merged alone.
+        assert_eq!(
+            merged,
+            [
+                block([0, 0, 1], 1 << 0, 7),
+                non_cube,
+                block([0, 0, 1], 1 << 3, 7)
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_faces_with_uneven_ambient_occlusion() {
+        let mut record = block([1, 0, 1], 1 << 3, 7);
+        // Face 3 takes the upper half of `record[3]`, and only its first corner
+        // is darkened.
+        record[3] = 1 << 16;
+
+        assert_eq!(
+            merge_faces(vec![(record, true)], &[0; BIOME_CELLS]),
+            [record]
+        );
+    }
+
+    #[test]
+    fn lod_doubles_in_distance() {
+        assert_eq!(lod(0.0), 0);
+        assert_eq!(lod(LOD_DISTANCE - 1.0), 0);
+        assert_eq!(lod(LOD_DISTANCE), 1);
+        assert_eq!(lod(LOD_DISTANCE * 2.0 - 1.0), 1);
+        assert_eq!(lod(LOD_DISTANCE * 2.0), 2);
+        assert_eq!(lod(LOD_DISTANCE * 4.0), 3);
+        assert_eq!(lod(LOD_DISTANCE * 1000.0), MAX_LOD);
+    }
+
+    const STATES: [ColumnState; 3] = [
+        ColumnState {
+            id: 1,
+            name: "minecraft:stone",
+            opacity: Opacity::Opaque,
+            translucent: false,
+        },
+        ColumnState {
+            id: 2,
+            name: "minecraft:dirt",
+            opacity: Opacity::Opaque,
+            translucent: false,
+        },
+        ColumnState {
+            id: 3,
+            name: "minecraft:torch",
+            opacity: Opacity::NonCube,
+            translucent: false,
+        },
+    ];
+
+    /// Downsamples a section of `blocks`, `(position, index in STATES, visible)`.
+    fn downsample_blocks(
+        blocks: &[([i32; 3], usize, bool)],
+    ) -> HashMap<i32, [Vec<[u32; 5]>; MAX_LOD as usize]> {
+        let block_states = blocks
+            .iter()
+            .map(|&(pos, state, _)| (glam::IVec3::from(pos), state))
+            .collect();
+        let visible = blocks
+            .iter()
+            .filter(|&&(.., visible)| visible)
+            .map(|&(pos, ..)| glam::IVec3::from(pos))
+            .collect();
+
+        downsample(&STATES, &block_states, &visible, 0..16)
+    }
+
+    /// The 8 blocks of the level 1 cell at the corner of the section.
+    fn corner_cell() -> impl Iterator<Item = [i32; 3]> {
+        (0..8).map(|i| [i & 1, i >> 1 & 1, i >> 2])
+    }
+
+    #[test]
+    fn downsamples_cells_half_made_of_cubes() {
+        let blocks: Vec<_> = corner_cell().take(4).map(|pos| (pos, 0, true)).collect();
+
+        let sections = downsample_blocks(&blocks);
+
+        let sky_lit = shading(0, (MAX_LIGHT, 0)) * 0x1_0001;
+        // Every face but the bottom one, which is against what is below the
+        // section.
+        let faces = 0b11_1011;
+        assert_eq!(
+            sections[&0][0],
+            [[faces << 12, 1, sky_lit, sky_lit, sky_lit]]
+        );
+        assert!(sections[&0][1].is_empty());
+        assert!(sections[&0][2].is_empty());
+    }
+
+    #[test]
+    fn skips_cells_mostly_empty_or_non_cube() {
+        let cubes: Vec<_> = corner_cell().take(3).map(|pos| (pos, 0, true)).collect();
+        let non_cubes: Vec<_> = corner_cell().map(|pos| (pos, 2, true)).collect();
+
+        assert!(downsample_blocks(&cubes).is_empty());
+        assert!(downsample_blocks(&non_cubes).is_empty());
+    }
+
+    #[test]
+    fn downsamples_cells_to_their_most_visible_block() {
+        let blocks: Vec<_> = corner_cell()
+            .enumerate()
+            .map(|(i, pos)| {
+                if i < 5 {
+                    (pos, 0, false)
+                } else {
+                    (pos, 1, true)
                 }
             })
             .collect();
 
-        chunks
-            .iter_mut()
-            .for_each(|[len, pos]| *len = chunk_map[pos]);
+        let sections = downsample_blocks(&blocks);
+
+        assert_eq!(sections[&0][0][0][1], 2);
+    }
+
+    /// Ambient occlusion of the top face of a block at the origin, with the
+    /// blocks at `occluders` around it.
+    fn top_face_ao(occluders: &[[i32; 3]]) -> u32 {
+        face_ao(3, glam::IVec3::ZERO, |pos| {
+            occluders.contains(&pos.to_array())
+        })
+    }
 
-        Some(Self { blocks, chunks })
+    #[test]
+    fn open_corners_are_not_darkened() {
+        assert_eq!(top_face_ao(&[]), 0);
+        // Blocks below or level with the face don't count.
+        assert_eq!(top_face_ao(&[[-1, 0, -1], [0, -1, 0]]), 0);
     }
 
-    pub fn chunks(&self) -> &[[u32; 2]] {
-        &self.chunks
+    #[test]
+    fn corners_are_darkened_by_the_blocks_around_them() {
+        // Corners go along z then x for a top face.
+        assert_eq!(top_face_ao(&[[-1, 1, -1]]), 1);
+        assert_eq!(top_face_ao(&[[1, 1, 1]]), 1 << 6);
+        // An edge darkens both its corners.
+        assert_eq!(top_face_ao(&[[0, 1, -1]]), 1 | 1 << 4);
     }
 
-    pub fn blocks(&self) -> &[u32] {
-        &self.blocks
+    #[test]
+    fn corners_between_two_edges_are_fully_darkened() {
+        assert_eq!(top_face_ao(&[[0, 1, -1], [-1, 1, 0]]), 3 | 1 << 2 | 1 << 4);
+
+        let around: Vec<_> = (-1..=1)
+            .flat_map(|x| (-1..=1).map(move |z| [x, 1, z]))
+            .filter(|&pos| pos != [0, 1, 0])
+            .collect();
+        assert_eq!(top_face_ao(&around), 0xff);
     }
 }
@@ -0,0 +1,695 @@
+use std::{fs::File, io, mem, ops::Range, path::Path};
+
+use crate::{
+    biomes::{self, Colormap},
+    blocks::BlocksPipeline,
+    camera::Camera,
+    chunks::{ChunksPipeline, CullCounts},
+    colors::{self, ColorOverrides},
+    faces::{FaceColors, FaceTextures, FacesPipeline, TranslucentTargets},
+    hiz::{DepthPyramid, HizPipeline},
+    models::{BlockModels, ModelBuffers},
+    palette::Palette,
+    region::RegionError,
+    resource_pack::{BlockTextures, ResourcePack},
+    shadows::{self, ShadowMaps, CASCADES},
+    sky::{Sky, SkyPipeline},
+    tiles::{self, TilesPipeline},
+    world::{OutOfMemory, World, WorldBuffers, MAX_FACE_PAGES},
+};
+
+/// Format of the images written by [`render_to_png`].
+const IMAGE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+/// Sun angle renderers start with, in the late morning.
+pub const DEFAULT_SUN_ANGLE: f32 = 75.0;
+
+/// How every block state of a [`World`] looks: its flat color, its face textures
+/// and its model if it is not a full cube. Extended as blocks are added to the
+/// world's palette.
+#[derive(Debug)]
+pub struct BlockAssets {
+    colors: Vec<u32>,
+    /// [`biomes::Tint`] of every block, indexed like `colors`.
+    tints: Vec<u32>,
+    /// `[grass, foliage, water]` colors of every biome id.
+    biome_colors: Vec<[u32; 3]>,
+    textures: BlockTextures,
+    models: BlockModels,
+    color_overrides: ColorOverrides,
+    resource_pack: Option<ResourcePack>,
+}
+
+impl BlockAssets {
+    /// Textures, models and biome colormaps come from `resource_pack` when
+    /// there is one, blocks being drawn with flat colors, built-in shapes and
+    /// colormaps otherwise.
+    pub fn new(color_overrides: ColorOverrides, mut resource_pack: Option<ResourcePack>) -> Self {
+        let mut colormap = |name| {
+            resource_pack
+                .as_mut()
+                .and_then(|resource_pack| resource_pack.colormap(name))
+        };
+        let grass = colormap("grass").unwrap_or(Colormap::GRASS);
+        let foliage = colormap("foliage").unwrap_or(Colormap::FOLIAGE);
+
+        Self {
+            colors: Vec::new(),
+            tints: Vec::new(),
+            biome_colors: biomes::biome_colors(&grass, &foliage),
+            textures: BlockTextures::default(),
+            models: BlockModels::default(),
+            color_overrides,
+            resource_pack,
+        }
+    }
+
+    /// Adds the blocks of `palette` that are missing, returning whether there
+    /// were any.
+    fn update(&mut self, palette: &Palette) -> bool {
+        if self.colors.len() == palette.len() {
+            return false;
+        }
+
+        colors::extend_block_colors(&mut self.colors, palette, &self.color_overrides);
+        biomes::extend_block_tints(&mut self.tints, palette, &self.color_overrides);
+
+        match &mut self.resource_pack {
+            Some(resource_pack) => {
+                resource_pack.extend_block_textures(&mut self.textures, palette);
+                resource_pack.extend_block_models(&mut self.models, &mut self.textures, palette);
+            }
+            None => {
+                self.textures.extend_untextured(palette);
+                self.models.extend_builtin(palette);
+            }
+        }
+
+        true
+    }
+}
+
+/// What [`Renderer::update`] had to leave out of the world, for the caller to
+/// report.
+#[derive(Debug, Default)]
+pub struct Skipped {
+    /// Columns that failed to load and were left empty, with
+    /// [`LoadOptions::lenient`](crate::LoadOptions::lenient) set.
+    pub regions: Vec<RegionError>,
+    /// Columns that didn't fit on the GPU, loaded again once the eye moves to
+    /// another column.
+    pub columns: Vec<OutOfMemory>,
+    /// Layer limit of the block texture array, once the block textures have
+    /// gone past it and the faces using the rest are drawn untextured.
+    pub texture_limit: Option<u32>,
+}
+
+impl Skipped {
+    /// Adds what `other` left out to this.
+    pub fn extend(&mut self, other: Skipped) {
+        self.regions.extend(other.regions);
+        self.columns.extend(other.columns);
+        self.texture_limit = self.texture_limit.or(other.texture_limit);
+    }
+}
+
+/// Views the chunks are culled and the faces generated for: the camera's,
+/// then each shadow cascade's.
+const VIEWS: usize = 1 + CASCADES;
+
+/// Runs the whole pipeline chain into a color target, either a window's surface
+/// or an offscreen texture. The device, queue and target all belong to the caller,
+/// so that it can be embedded in any wgpu application.
+#[derive(Debug)]
+pub struct Renderer {
+    size: glam::UVec2,
+    world_buffers: WorldBuffers,
+    face_colors: FaceColors,
+    depth_texture: wgpu::Texture,
+    far_field_texture: wgpu::Texture,
+    translucent_targets: TranslucentTargets,
+    depth_pyramid: DepthPyramid,
+    chunks_pipeline: ChunksPipeline,
+    draw_indirect_buffer: wgpu::Buffer,
+    blocks_pipeline: BlocksPipeline,
+    faces_pipeline: FacesPipeline,
+    shadow_maps: ShadowMaps,
+    shadow_draw_indirect_buffers: Vec<wgpu::Buffer>,
+    /// Never built, so that nothing is occluded in the shadow maps.
+    shadow_depth_pyramid: DepthPyramid,
+    face_textures: FaceTextures,
+    /// Returned by the next [`Renderer::update`], from uploading the textures
+    /// the renderer was created with.
+    texture_limit: Option<u32>,
+    model_buffers: ModelBuffers,
+    tiles_pipeline: TilesPipeline,
+    sky_pipeline: SkyPipeline,
+    hiz_pipeline: HizPipeline,
+    block_assets: BlockAssets,
+    /// [`World::far_field`] of the world last updated from.
+    far_field: Range<f32>,
+    /// [`World::draw_distance`] of the world last updated from.
+    draw_distance: f32,
+    daylight: f32,
+    sun_angle: f32,
+    sky: Sky,
+}
+
+impl Renderer {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        color_format: wgpu::TextureFormat,
+        size: glam::UVec2,
+        block_assets: BlockAssets,
+    ) -> Self {
+        let chunks_pipeline = ChunksPipeline::new(device, VIEWS);
+        let blocks_pipeline = BlocksPipeline::new(device, VIEWS);
+        let faces_pipeline = FacesPipeline::new(device, color_format);
+        let tiles_pipeline = TilesPipeline::new(device, color_format);
+        let sky_pipeline = SkyPipeline::new(device, color_format);
+        let hiz_pipeline = HizPipeline::new(device);
+
+        let mut face_textures = FaceTextures::new(device);
+        let texture_limit = face_textures.update(device, queue, &block_assets.textures);
+        let model_buffers = ModelBuffers::new(device, queue, &block_assets.models);
+        let world_buffers = WorldBuffers::new(device);
+        let face_colors = FaceColors::new(
+            device,
+            queue,
+            &block_assets.colors,
+            &block_assets.tints,
+            &block_assets.biome_colors,
+        );
+
+        let shadow_draw_indirect_buffers = (0..CASCADES)
+            .map(|_| create_draw_indirect_buffer(device))
+            .collect();
+
+        Self {
+            size,
+            world_buffers,
+            face_colors,
+            depth_texture: create_depth_texture(device, size),
+            far_field_texture: tiles::create_far_field_texture(device, size),
+            translucent_targets: TranslucentTargets::new(device, size.x, size.y),
+            depth_pyramid: DepthPyramid::new(device, size),
+            chunks_pipeline,
+            draw_indirect_buffer: create_draw_indirect_buffer(device),
+            blocks_pipeline,
+            faces_pipeline,
+            shadow_maps: ShadowMaps::new(device),
+            shadow_draw_indirect_buffers,
+            shadow_depth_pyramid: DepthPyramid::new(device, glam::UVec2::ONE),
+            face_textures,
+            texture_limit,
+            model_buffers,
+            tiles_pipeline,
+            sky_pipeline,
+            hiz_pipeline,
+            block_assets,
+            far_field: 0.0..0.0,
+            draw_distance: 0.0,
+            daylight: 1.0,
+            sun_angle: DEFAULT_SUN_ANGLE,
+            sky: Sky::default(),
+        }
+    }
+
+    /// Streams the columns `world` loaded since the last call into the GPU
+    /// buffers and drops the ones too far from `eye`, and grows the face buffer
+    /// when a past frame's faces didn't fit. Called between frames. Returns what
+    /// could not be drawn.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        world: &mut World,
+        eye: glam::DVec3,
+    ) -> Result<Skipped, RegionError> {
+        // Faces are dropped until the face pages have grown to fit them.
+        if let Some(counts) = self.blocks_pipeline.read_face_counts(device) {
+            self.world_buffers.reserve_faces(device, &counts);
+        }
+
+        let update = world.update(eye)?;
+        self.far_field = world.far_field();
+        self.draw_distance = world.draw_distance();
+
+        for pos in update.evicted {
+            self.world_buffers.remove(queue, pos);
+        }
+
+        let mut skipped = Skipped {
+            regions: update.skipped,
+            texture_limit: self.texture_limit.take(),
+            ..Default::default()
+        };
+
+        if update.loaded.is_empty() {
+            return Ok(skipped);
+        }
+
+        // Loaded columns only use blocks already added to the palette.
+        let palette = world.palette();
+        if self.block_assets.update(&palette) {
+            let texture_limit =
+                self.face_textures
+                    .update(device, queue, &self.block_assets.textures);
+            skipped.texture_limit = skipped.texture_limit.or(texture_limit);
+            self.model_buffers
+                .update(device, queue, &self.block_assets.models);
+            self.face_colors.update(
+                device,
+                queue,
+                &self.block_assets.colors,
+                &self.block_assets.tints,
+            );
+        }
+
+        for column in update.loaded {
+            let inserted =
+                self.world_buffers
+                    .insert(device, queue, &self.model_buffers, &palette, column);
+            if let Err(error) = inserted {
+                skipped.columns.push(error);
+            }
+        }
+        drop(palette);
+
+        for error in &skipped.columns {
+            world.forget(error.pos);
+        }
+
+        Ok(skipped)
+    }
+
+    /// Waits for the faces of the last frame to be read back, and grows the
+    /// face pages when they didn't fit. Returns whether they grew.
+    fn wait_for_faces(&mut self, device: &wgpu::Device) -> bool {
+        match self.blocks_pipeline.wait_face_counts(device) {
+            Some(counts) => self.world_buffers.reserve_faces(device, &counts),
+            None => false,
+        }
+    }
+
+    /// Gives back the assets the renderer was created with, extended with the
+    /// blocks it has seen since.
+    pub fn into_block_assets(self) -> BlockAssets {
+        self.block_assets
+    }
+
+    /// Resizes the renderer's own targets to match a new color target size.
+    pub fn resize(&mut self, device: &wgpu::Device, size: glam::UVec2) {
+        self.size = size;
+
+        self.depth_texture.destroy();
+        self.depth_texture = create_depth_texture(device, size);
+        self.far_field_texture.destroy();
+        self.far_field_texture = tiles::create_far_field_texture(device, size);
+        self.translucent_targets = TranslucentTargets::new(device, size.x, size.y);
+        self.depth_pyramid = DepthPyramid::new(device, size);
+    }
+
+    /// How bright the sky light is, from 0 at night to 1 at noon. Blocks keep
+    /// their own light.
+    pub fn daylight(&self) -> f32 {
+        self.daylight
+    }
+
+    pub fn set_daylight(&mut self, daylight: f32) {
+        self.daylight = daylight.clamp(0.0, 1.0);
+    }
+
+    /// Where the sun is along its path across the sky in degrees, from rising
+    /// in the east at 0 to setting in the west at 180. It casts the shadows of
+    /// the blocks it lights.
+    pub fn sun_angle(&self) -> f32 {
+        self.sun_angle
+    }
+
+    pub fn set_sun_angle(&mut self, sun_angle: f32) {
+        self.sun_angle = sun_angle.clamp(0.0, 180.0);
+    }
+
+    /// The sky drawn behind the world, dimmed like the sky light, and the fog
+    /// fading the world into it.
+    pub fn sky(&self) -> Sky {
+        self.sky
+    }
+
+    pub fn set_sky(&mut self, sky: Sky) {
+        self.sky = sky;
+    }
+
+    /// Sections culled in a past frame, once they have been read back. Never
+    /// waits for the GPU.
+    pub fn cull_counts(&self, device: &wgpu::Device) -> Option<CullCounts> {
+        self.chunks_pipeline.read_cull_counts(device)
+    }
+
+    /// Encodes a frame seen from `camera` into `color_view`, which has to be of
+    /// the size and format the renderer was created for.
+    pub fn encode(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        camera: &Camera,
+        color_view: &wgpu::TextureView,
+    ) {
+        let depth_view = self
+            .depth_texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let far_field_view = self
+            .far_field_texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let aspect_ratio = self.size.x as f32 / self.size.y as f32;
+        let origin = camera.origin();
+
+        let sun = shadows::sun_dir(self.sun_angle);
+        let cascades = shadows::cascades(camera, aspect_ratio, sun);
+        self.shadow_maps.write(queue, &cascades, sun);
+        self.sky_pipeline.write(
+            queue,
+            &self.sky,
+            camera.local_eye(),
+            camera.clip_from_world(aspect_ratio),
+            self.daylight,
+            sun,
+            self.draw_distance,
+        );
+
+        // Drawn first, as culling overwrites the chunks' flags. Sections keep
+        // the level of detail they are drawn with from the eye.
+        for (i, (cascade, draw_indirect_buffer)) in cascades
+            .iter()
+            .zip(&self.shadow_draw_indirect_buffers)
+            .enumerate()
+        {
+            self.chunks_pipeline.encode(
+                device,
+                queue,
+                encoder,
+                &self.world_buffers,
+                1 + i,
+                origin,
+                camera.local_eye(),
+                cascade.clip_from_world_with_margin(8.0 * 3.0f32.sqrt()),
+                &self.shadow_depth_pyramid,
+            );
+            self.blocks_pipeline.encode(
+                device,
+                queue,
+                encoder,
+                &self.world_buffers,
+                &self.model_buffers,
+                1 + i,
+                origin,
+                cascade.eye(),
+                cascade.clip_from_world_with_margin(0.5 * 3.0f32.sqrt()),
+                draw_indirect_buffer,
+            );
+            self.faces_pipeline.encode_shadow(
+                device,
+                queue,
+                encoder,
+                &self.world_buffers,
+                origin,
+                &self.face_colors,
+                &self.face_textures,
+                &self.model_buffers,
+                &self.shadow_maps,
+                i,
+                draw_indirect_buffer,
+            );
+        }
+
+        self.chunks_pipeline.encode(
+            device,
+            queue,
+            encoder,
+            &self.world_buffers,
+            0,
+            origin,
+            camera.local_eye(),
+            camera.clip_from_world_with_margin(aspect_ratio, 8.0 * 3.0f32.sqrt()),
+            &self.depth_pyramid,
+        );
+        self.blocks_pipeline.encode(
+            device,
+            queue,
+            encoder,
+            &self.world_buffers,
+            &self.model_buffers,
+            0,
+            origin,
+            camera.local_eye(),
+            camera.clip_from_world_with_margin(aspect_ratio, 0.5 * 3.0f32.sqrt()),
+            &self.draw_indirect_buffer,
+        );
+        self.blocks_pipeline
+            .encode_readback(encoder, &self.world_buffers);
+        let faces_bind_groups = self.faces_pipeline.encode_opaque(
+            device,
+            queue,
+            encoder,
+            &self.world_buffers,
+            origin,
+            &self.face_colors,
+            &self.face_textures,
+            &self.model_buffers,
+            camera.clip_from_world(aspect_ratio),
+            self.daylight,
+            &self.shadow_maps,
+            &self.sky_pipeline.view_buffer,
+            &self.draw_indirect_buffer,
+            color_view,
+            &depth_view,
+        );
+        // Built after culling, for the next frame.
+        self.hiz_pipeline.encode(
+            device,
+            queue,
+            encoder,
+            &depth_view,
+            &self.depth_pyramid,
+            origin,
+            camera.clip_from_world(aspect_ratio),
+        );
+        // Drawn behind the translucent faces, which don't write depth, and the
+        // sky behind the far field, which only draws where it hits a surface.
+        self.sky_pipeline
+            .encode(device, encoder, color_view, &depth_view);
+        self.tiles_pipeline.encode(
+            device,
+            queue,
+            encoder,
+            &self.world_buffers,
+            &self.face_colors,
+            origin,
+            camera.local_eye(),
+            camera.clip_from_world(aspect_ratio),
+            self.far_field.clone(),
+            self.daylight,
+            sun,
+            &self.sky_pipeline.view_buffer,
+            color_view,
+            &depth_view,
+            &far_field_view,
+            self.size,
+        );
+        self.faces_pipeline.encode_translucent(
+            device,
+            encoder,
+            &faces_bind_groups,
+            &self.draw_indirect_buffer,
+            color_view,
+            &depth_view,
+            &self.translucent_targets,
+        );
+    }
+}
+
+fn create_draw_indirect_buffer(device: &wgpu::Device) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("draw_indirect_buffer"),
+        // The opaque draw followed by the translucent one, for each face page.
+        size: (2 * MAX_FACE_PAGES * mem::size_of::<wgpu::util::DrawIndirectArgs>()) as u64,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDIRECT,
+        mapped_at_creation: false,
+    })
+}
+
+/// Format of the depth texture. Floats keep the reversed depth of
+/// [`Camera::clip_from_world`] precise all the way to the far plane, where 24-bit
+/// depth would leave distant faces fighting.
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+fn create_depth_texture(device: &wgpu::Device, size: glam::UVec2) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("depth_texture"),
+        size: wgpu::Extent3d {
+            width: size.x,
+            height: size.y,
+            ..Default::default()
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: DEPTH_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    })
+}
+
+/// Renders a single frame without a window and writes it to `path` as a PNG, once
+/// every column in view has been loaded, with the sky light scaled by `daylight`
+/// like [`Renderer::set_daylight`], the sun at `sun_angle` like
+/// [`Renderer::set_sun_angle`] and `sky` like [`Renderer::set_sky`]. Returns what
+/// could not be drawn, like [`Renderer::update`].
+///
+/// The adapter can be picked with the `WGPU_ADAPTER_NAME` and `WGPU_BACKEND`
+/// environment variables, e.g. to use a software one like lavapipe.
+#[allow(clippy::too_many_arguments)]
+pub fn render_to_png(
+    world: &mut World,
+    block_assets: BlockAssets,
+    camera: &Camera,
+    size: glam::UVec2,
+    daylight: f32,
+    sun_angle: f32,
+    sky: Sky,
+    path: impl AsRef<Path>,
+) -> io::Result<Skipped> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::util::backend_bits_from_env().unwrap_or_default(),
+        ..Default::default()
+    });
+    let adapter = pollster::block_on(wgpu::util::initialize_adapter_from_env_or_default(
+        &instance, None,
+    ))
+    .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no suitable adapter found"))?;
+
+    let (device, queue) = pollster::block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: None,
+            required_features: wgpu::Features::empty(),
+            required_limits: wgpu::Limits {
+                max_texture_array_layers: adapter.limits().max_texture_array_layers,
+                ..wgpu::Limits::default().using_resolution(adapter.limits())
+            },
+            memory_hints: wgpu::MemoryHints::MemoryUsage,
+        },
+        None,
+    ))
+    .map_err(io::Error::other)?;
+
+    let mut renderer = Renderer::new(&device, &queue, IMAGE_FORMAT, size, block_assets);
+    renderer.set_daylight(daylight);
+    renderer.set_sun_angle(sun_angle);
+    renderer.set_sky(sky);
+
+    let mut skipped = Skipped::default();
+    loop {
+        skipped.extend(
+            renderer
+                .update(&device, &queue, world, camera.eye)
+                .map_err(io::Error::other)?,
+        );
+
+        if !world.is_loading() {
+            break;
+        }
+
+        world.wait();
+    }
+
+    let extent = wgpu::Extent3d {
+        width: size.x,
+        height: size.y,
+        ..Default::default()
+    };
+    let color_texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("color_texture"),
+        size: extent,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: IMAGE_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let color_view = color_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    // Rows copied out of a texture have to be aligned, the padding is dropped
+    // when writing the image.
+    let row_len = size.x * 4;
+    let padded_row_len = row_len.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+    let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("readback_buffer"),
+        size: (padded_row_len * size.y) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    // Faces that don't fit in the face pages are only known once they have been
+    // generated, so the frame is drawn again until they all fit.
+    loop {
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        renderer.encode(&device, &queue, &mut encoder, camera, &color_view);
+        queue.submit(Some(encoder.finish()));
+
+        if !renderer.wait_for_faces(&device) {
+            break;
+        }
+    }
+
+    let mut encoder =
+        device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+    encoder.copy_texture_to_buffer(
+        color_texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &readback_buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_len),
+                rows_per_image: None,
+            },
+        },
+        extent,
+    );
+
+    queue.submit(Some(encoder.finish()));
+
+    let slice = readback_buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::Maintain::Wait);
+    receiver
+        .recv()
+        .map_err(io::Error::other)?
+        .map_err(io::Error::other)?;
+
+    let pixels: Vec<_> = slice
+        .get_mapped_range()
+        .chunks_exact(padded_row_len as usize)
+        .flat_map(|row| &row[..row_len as usize])
+        .copied()
+        .collect();
+
+    let mut encoder = png::Encoder::new(File::create(path)?, size.x, size.y);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&pixels)?;
+    writer.finish()?;
+
+    Ok(skipped)
+}
@@ -0,0 +1,784 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+use crate::{
+    biomes::Colormap,
+    models::{self, BlockModels, Element, ElementFace, ElementRotation, FACE_DIRS},
+    opacity::Opacity,
+    palette::Palette,
+};
+
+/// Marks a face without a texture, drawn with its block color instead.
+pub const NO_TEXTURE: u16 = u16::MAX;
+/// Set on a face's texture layer when the texture has to be multiplied by the
+/// block's color, like grass tops and leaves.
+pub const TINTED: u16 = 1 << 15;
+
+/// Model face names in the order of face indices used on the GPU: `-x`, `+x`,
+/// `-y`, `+y`, `-z`, `+z`.
+const FACE_NAMES: [&str; 6] = ["west", "east", "down", "up", "north", "south"];
+
+/// Bound on parent models followed, guarding against cycles in broken packs.
+const MAX_PARENTS: usize = 32;
+
+#[derive(Debug)]
+enum Source {
+    Dir(PathBuf),
+    Zip(zip::ZipArchive<BufReader<File>>),
+    #[cfg(test)]
+    Memory(HashMap<String, Vec<u8>>),
+}
+
+/// A resource pack read either from a directory, a `.zip` pack or the vanilla
+/// client `.jar`.
+#[derive(Debug)]
+pub struct ResourcePack {
+    source: Source,
+}
+
+/// Textures used by the blocks of a [`Palette`], ready to be uploaded as a texture
+/// array.
+#[derive(Debug)]
+pub struct BlockTextures {
+    /// Width and height of every layer.
+    pub size: u32,
+    /// RGBA8 pixels of every layer.
+    pub layers: Vec<Vec<u8>>,
+    /// Texture layer of each face of every palette block, possibly with
+    /// [`TINTED`] set, or [`NO_TEXTURE`].
+    pub faces: Vec<[u16; 6]>,
+    layer_ids: HashMap<String, u16>,
+}
+
+impl Default for BlockTextures {
+    fn default() -> Self {
+        Self {
+            size: 1,
+            layers: Vec::new(),
+            faces: Vec::new(),
+            layer_ids: HashMap::new(),
+        }
+    }
+}
+
+impl BlockTextures {
+    /// Adds the blocks of `palette` missing from these textures without a
+    /// resource pack, every face being drawn with its block color.
+    pub fn extend_untextured(&mut self, palette: &Palette) {
+        self.faces.resize(palette.len(), [NO_TEXTURE; 6]);
+    }
+}
+
+impl ResourcePack {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+
+        let source = if path.is_dir() {
+            Source::Dir(path.to_path_buf())
+        } else {
+            let archive = zip::ZipArchive::new(BufReader::new(File::open(path)?))
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+            Source::Zip(archive)
+        };
+
+        Ok(Self { source })
+    }
+
+    fn read(&mut self, path: &str) -> Option<Vec<u8>> {
+        let mut data = Vec::new();
+
+        match &mut self.source {
+            Source::Dir(dir) => {
+                File::open(dir.join(path))
+                    .ok()?
+                    .read_to_end(&mut data)
+                    .ok()?;
+            }
+            Source::Zip(archive) => {
+                archive.by_name(path).ok()?.read_to_end(&mut data).ok()?;
+            }
+            #[cfg(test)]
+            Source::Memory(files) => data.clone_from(files.get(path)?),
+        }
+
+        Some(data)
+    }
+
+    fn read_json(&mut self, path: &str) -> Option<Value> {
+        serde_json::from_slice(&self.read(path)?).ok()
+    }
+
+    /// Resolves the textures of the blocks of `palette` missing from `textures`.
+    /// Faces whose texture cannot be found fall back to the block color.
+    pub fn extend_block_textures(&mut self, textures: &mut BlockTextures, palette: &Palette) {
+        for id in textures.faces.len() as u16..palette.len() as u16 {
+            let faces =
+                self.face_textures(palette.state(id))
+                    .map_or([NO_TEXTURE; 6], |face_textures| {
+                        face_textures.map(|face_texture| {
+                            face_texture.map_or(NO_TEXTURE, |(texture, tinted)| {
+                                self.layer(textures, texture, tinted)
+                            })
+                        })
+                    });
+
+            textures.faces.push(faces);
+        }
+    }
+
+    /// Resolves the models of the blocks of `palette` missing from `models` that
+    /// aren't full cubes, adding the textures they use to `textures`. Blocks
+    /// missing from the pack get the shapes of `BlockModels::extend_builtin`.
+    pub fn extend_block_models(
+        &mut self,
+        models: &mut BlockModels,
+        textures: &mut BlockTextures,
+        palette: &Palette,
+    ) {
+        for id in models.len() as u16..palette.len() as u16 {
+            let state = palette.state(id);
+            let elements = if Opacity::of(state) == Opacity::NonCube {
+                self.model_elements(state, textures)
+                    .or_else(|| {
+                        models::builtin_elements(state)
+                            .map(|elements| elements.into_iter().map(|e| (e, 0, 0)).collect())
+                    })
+                    .unwrap_or_default()
+            } else {
+                Vec::new()
+            };
+
+            models.push(
+                id,
+                elements
+                    .iter()
+                    .map(|(element, rotation_x, rotation_y)| (element, *rotation_x, *rotation_y)),
+            );
+        }
+    }
+
+    /// Reads the colormap `name`, e.g. `grass` or `foliage`, that biomes look
+    /// their tint up in.
+    pub fn colormap(&mut self, name: &str) -> Option<Colormap> {
+        self.read_texture(&format!("minecraft:colormap/{name}"), &mut Some(256))
+            .map(Colormap::Image)
+    }
+
+    /// Returns the layer of `texture`, reading it if it is new.
+    fn layer(&mut self, textures: &mut BlockTextures, texture: String, tinted: bool) -> u16 {
+        let layer = match textures.layer_ids.get(&texture) {
+            Some(&layer) => layer,
+            None => {
+                let mut size = (!textures.layers.is_empty()).then_some(textures.size);
+                let layer = self
+                    .read_texture(&texture, &mut size)
+                    .map_or(NO_TEXTURE, |pixels| {
+                        textures.layers.push(pixels);
+                        textures.layers.len() as u16 - 1
+                    });
+
+                textures.size = size.unwrap_or(1);
+                textures.layer_ids.insert(texture, layer);
+
+                layer
+            }
+        };
+
+        if layer != NO_TEXTURE && tinted {
+            layer | TINTED
+        } else {
+            layer
+        }
+    }
+
+    /// Collects the elements of every model applied to a block state, along with
+    /// the rotation of the variant applying them.
+    fn model_elements(
+        &mut self,
+        state: &str,
+        textures: &mut BlockTextures,
+    ) -> Option<Vec<(Element, i64, i64)>> {
+        let (name, properties) = split_state(state);
+        let (namespace, name) = name.split_once(':').unwrap_or(("minecraft", name));
+
+        let blockstate = self.read_json(&format!("assets/{namespace}/blockstates/{name}.json"))?;
+        let mut elements = Vec::new();
+
+        for variant in find_variants(&blockstate, &properties) {
+            let Some(model) = variant.get("model").and_then(Value::as_str) else {
+                continue;
+            };
+            let rotation_x = variant.get("x").and_then(Value::as_i64).unwrap_or(0);
+            let rotation_y = variant.get("y").and_then(Value::as_i64).unwrap_or(0);
+
+            let Some((model_textures, model_elements)) = self.resolve_model(model) else {
+                continue;
+            };
+
+            for element in &model_elements {
+                if let Some(element) = self.parse_element(element, &model_textures, textures) {
+                    elements.push((element, rotation_x, rotation_y));
+                }
+            }
+        }
+
+        (!elements.is_empty()).then_some(elements)
+    }
+
+    /// Converts a model element to block space, reading the textures it uses.
+    fn parse_element(
+        &mut self,
+        element: &Value,
+        model_textures: &HashMap<String, String>,
+        textures: &mut BlockTextures,
+    ) -> Option<Element> {
+        let from = parse_vec3(element.get("from")?)? / 16.0;
+        let to = parse_vec3(element.get("to")?)? / 16.0;
+
+        let faces = element.get("faces")?;
+        let faces = FACE_NAMES.map(|face_name| {
+            let face = faces.get(face_name)?;
+            let texture = resolve_texture(model_textures, face.get("texture")?.as_str()?)?;
+            let uv = face.get("uv").and_then(Value::as_array).and_then(|uv| {
+                let uv: Vec<_> = uv.iter().filter_map(Value::as_f64).collect();
+
+                <[f64; 4]>::try_from(uv)
+                    .ok()
+                    .map(|uv| uv.map(|c| c as f32 / 16.0))
+            });
+            let cull_face = face
+                .get("cullface")
+                .and_then(Value::as_str)
+                .and_then(|cull_face| FACE_NAMES.iter().position(|&name| name == cull_face));
+
+            Some(ElementFace {
+                uv,
+                rotation: face.get("rotation").and_then(Value::as_i64).unwrap_or(0),
+                texture: self.layer(textures, texture, face.get("tintindex").is_some()),
+                cull_face,
+            })
+        });
+
+        let rotation = element.get("rotation").and_then(|rotation| {
+            let axis = rotation.get("axis")?.as_str()?;
+
+            Some(ElementRotation {
+                origin: parse_vec3(rotation.get("origin")?)? / 16.0,
+                axis: ["x", "y", "z"].iter().position(|&a| a == axis)?,
+                angle: rotation.get("angle")?.as_f64()? as f32,
+                rescale: rotation
+                    .get("rescale")
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false),
+            })
+        });
+
+        Some(Element {
+            from,
+            to,
+            faces,
+            rotation,
+        })
+    }
+
+    /// Finds the texture of every face of a block state given as
+    /// `namespace:name|prop=value,...`, along with whether it is tinted.
+    fn face_textures(&mut self, state: &str) -> Option<[Option<(String, bool)>; 6]> {
+        let (name, properties) = split_state(state);
+        let (namespace, name) = name.split_once(':').unwrap_or(("minecraft", name));
+
+        let blockstate = self.read_json(&format!("assets/{namespace}/blockstates/{name}.json"))?;
+        let variant = *find_variants(&blockstate, &properties).first()?;
+
+        let model = variant.get("model")?.as_str()?;
+        let rotation_x = variant.get("x").and_then(Value::as_i64).unwrap_or(0);
+        let rotation_y = variant.get("y").and_then(Value::as_i64).unwrap_or(0);
+
+        let (textures, elements) = self.resolve_model(model)?;
+
+        let model_faces: [Option<(String, bool)>; 6] = FACE_NAMES.map(|face_name| {
+            let face = elements
+                .iter()
+                .find_map(|element| element.get("faces")?.get(face_name))?;
+            let texture = resolve_texture(&textures, face.get("texture")?.as_str()?)?;
+
+            Some((texture, face.get("tintindex").is_some()))
+        });
+
+        // Every face on the GPU gets the texture of the model face that the
+        // variant's rotation turns towards it.
+        Some(FACE_DIRS.map(|dir| {
+            let model_face = FACE_DIRS.iter().position(|&model_dir| {
+                models::rotate(model_dir.as_vec3(), rotation_x, rotation_y)
+                    .round()
+                    .as_ivec3()
+                    == dir
+            })?;
+
+            model_faces[model_face].clone()
+        }))
+    }
+
+    /// Follows a model's parents, merging their texture variables and returning
+    /// the elements of the closest model defining any.
+    fn resolve_model(&mut self, model: &str) -> Option<(HashMap<String, String>, Vec<Value>)> {
+        let mut textures = HashMap::new();
+        let mut elements = None;
+        let mut model = Some(model.to_owned());
+        let mut found = false;
+
+        for _ in 0..MAX_PARENTS {
+            let Some(current) = model.take() else {
+                break;
+            };
+
+            let (namespace, path) = current.split_once(':').unwrap_or(("minecraft", &current));
+
+            // Built-in parents like `builtin/generated` have no file to read.
+            let Some(json) = self.read_json(&format!("assets/{namespace}/models/{path}.json"))
+            else {
+                break;
+            };
+
+            found = true;
+
+            if let Some(model_textures) = json.get("textures").and_then(Value::as_object) {
+                for (key, value) in model_textures {
+                    if let Some(value) = value.as_str() {
+                        textures
+                            .entry(key.clone())
+                            .or_insert_with(|| value.to_owned());
+                    }
+                }
+            }
+
+            if elements.is_none() {
+                elements = json.get("elements").and_then(Value::as_array).cloned();
+            }
+
+            model = json
+                .get("parent")
+                .and_then(Value::as_str)
+                .map(str::to_owned);
+        }
+
+        found.then(|| (textures, elements.unwrap_or_default()))
+    }
+
+    /// Reads a texture as RGBA8, keeping only the first frame of animated ones
+    /// and rescaling it to `size` if the pack mixes resolutions.
+    fn read_texture(&mut self, texture: &str, size: &mut Option<u32>) -> Option<Vec<u8>> {
+        let (namespace, path) = texture.split_once(':').unwrap_or(("minecraft", texture));
+        let data = self.read(&format!("assets/{namespace}/textures/{path}.png"))?;
+
+        let mut decoder = png::Decoder::new(data.as_slice());
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+        let mut reader = decoder.read_info().ok()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).ok()?;
+
+        if info.height < info.width {
+            return None;
+        }
+
+        let width = info.width as usize;
+        let pixels: Vec<u8> = match info.color_type {
+            png::ColorType::Rgba => buffer[..width * width * 4].to_vec(),
+            png::ColorType::Rgb => buffer[..width * width * 3]
+                .chunks_exact(3)
+                .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => buffer[..width * width * 2]
+                .chunks_exact(2)
+                .flat_map(|la| [la[0], la[0], la[0], la[1]])
+                .collect(),
+            png::ColorType::Grayscale => buffer[..width * width]
+                .iter()
+                .flat_map(|&l| [l, l, l, 255])
+                .collect(),
+            png::ColorType::Indexed => return None,
+        };
+
+        let size = *size.get_or_insert(info.width) as usize;
+
+        if size == width {
+            return Some(pixels);
+        }
+
+        Some(
+            (0..size * size)
+                .flat_map(|i| {
+                    let x = (i % size) * width / size;
+                    let y = (i / size) * width / size;
+                    let j = (x + y * width) * 4;
+
+                    [pixels[j], pixels[j + 1], pixels[j + 2], pixels[j + 3]]
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Picks the models applied to a block state from a blockstate file: the variant
+/// matching it, or every matching part of a multipart blockstate, whose
+/// conditions may be combined with `OR` and `AND`. The first model is taken when
+/// several are listed for random picking.
+fn find_variants<'a>(blockstate: &'a Value, properties: &HashMap<&str, &str>) -> Vec<&'a Value> {
+    // Properties missing from the state, like `waterlogged`, match any value.
+    let matches = |conditions: &str| {
+        conditions
+            .split(',')
+            .filter_map(|condition| condition.split_once('='))
+            .all(|(key, value)| properties.get(key).is_none_or(|&v| v == value))
+    };
+    let first = |value: &'a Value| match value {
+        Value::Array(values) => values.first(),
+        value => Some(value),
+    };
+
+    if let Some(variants) = blockstate.get("variants").and_then(Value::as_object) {
+        return variants
+            .iter()
+            .find(|(conditions, _)| matches(conditions))
+            .and_then(|(_, variant)| first(variant))
+            .into_iter()
+            .collect();
+    }
+
+    let Some(parts) = blockstate.get("multipart").and_then(Value::as_array) else {
+        return Vec::new();
+    };
+
+    parts
+        .iter()
+        .filter(|part| {
+            part.get("when")
+                .is_none_or(|when| when_matches(when, properties))
+        })
+        .filter_map(|part| first(part.get("apply")?))
+        .collect()
+}
+
+/// Whether a block state's properties meet the `when` condition of a multipart
+/// blockstate part, where values may list alternatives separated by `|`.
+fn when_matches(when: &Value, properties: &HashMap<&str, &str>) -> bool {
+    let Some(when) = when.as_object() else {
+        return false;
+    };
+
+    when.iter().all(|(key, value)| match key.as_str() {
+        "OR" => value.as_array().is_some_and(|conditions| {
+            conditions
+                .iter()
+                .any(|condition| when_matches(condition, properties))
+        }),
+        "AND" => value.as_array().is_some_and(|conditions| {
+            conditions
+                .iter()
+                .all(|condition| when_matches(condition, properties))
+        }),
+        key => {
+            let value = match value {
+                Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+
+            value
+                .split('|')
+                .any(|value| properties.get(key) == Some(&value))
+        }
+    })
+}
+
+/// Follows `#variable` references until reaching an actual texture path.
+fn resolve_texture(textures: &HashMap<String, String>, texture: &str) -> Option<String> {
+    let mut texture = texture;
+
+    for _ in 0..MAX_PARENTS {
+        match texture.strip_prefix('#') {
+            Some(variable) => texture = textures.get(variable)?,
+            None if texture.contains(':') => return Some(texture.to_owned()),
+            None => return Some(format!("minecraft:{texture}")),
+        }
+    }
+
+    None
+}
+
+/// Splits a block state given as `namespace:name|prop=value,...` into its name and
+/// properties.
+fn split_state(state: &str) -> (&str, HashMap<&str, &str>) {
+    let (name, properties) = state.split_once('|').unwrap_or((state, ""));
+    let properties = properties
+        .split(',')
+        .filter_map(|property| property.split_once('='))
+        .collect();
+
+    (name, properties)
+}
+
+fn parse_vec3(value: &Value) -> Option<glam::Vec3> {
+    let [x, y, z] = value.as_array()?.as_slice() else {
+        return None;
+    };
+
+    Some(glam::Vec3::new(
+        x.as_f64()? as f32,
+        y.as_f64()? as f32,
+        z.as_f64()? as f32,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn pack(files: &[(&str, Value)]) -> ResourcePack {
+        ResourcePack {
+            source: Source::Memory(
+                files
+                    .iter()
+                    .map(|(path, json)| (path.to_string(), json.to_string().into_bytes()))
+                    .collect(),
+            ),
+        }
+    }
+
+    fn models(blockstate: &Value, state: &str) -> Vec<String> {
+        let (_, properties) = split_state(state);
+
+        find_variants(blockstate, &properties)
+            .iter()
+            .map(|variant| variant["model"].as_str().unwrap().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn matches_variants() {
+        let blockstate = json!({
+            "variants": {
+                "facing=east,half=bottom": { "model": "stairs_east" },
+                "facing=west,half=bottom": [{ "model": "stairs_west" }, { "model": "other" }],
+                "facing=west,half=top": { "model": "stairs_west_top" },
+            }
+        });
+
+        assert_eq!(
+            models(&blockstate, "minecraft:oak_stairs|facing=east,half=bottom"),
+            ["stairs_east"]
+        );
+        assert_eq!(
+            models(&blockstate, "minecraft:oak_stairs|facing=west,half=bottom"),
+            ["stairs_west"]
+        );
+        assert_eq!(
+            models(&blockstate, "minecraft:oak_stairs|facing=west,half=top"),
+            ["stairs_west_top"]
+        );
+        assert!(models(&blockstate, "minecraft:oak_stairs|facing=north,half=top").is_empty());
+
+        // Properties missing from the state match any value.
+        let blockstate = json!({ "variants": { "waterlogged=false": { "model": "plain" } } });
+        assert_eq!(models(&blockstate, "minecraft:ladder"), ["plain"]);
+    }
+
+    #[test]
+    fn matches_multipart_parts() {
+        let blockstate = json!({
+            "multipart": [
+                { "apply": { "model": "post" } },
+                { "when": { "north": "true" }, "apply": { "model": "north" } },
+                { "when": { "east": "low|tall" }, "apply": { "model": "east" } },
+                { "when": { "up": true }, "apply": [{ "model": "up" }, { "model": "other" }] },
+                {
+                    "when": { "OR": [{ "north": "true" }, { "south": "true", "up": "true" }] },
+                    "apply": { "model": "or" },
+                },
+                {
+                    "when": { "AND": [{ "north": "true" }, { "east": "tall" }] },
+                    "apply": { "model": "and" },
+                },
+            ]
+        });
+
+        assert_eq!(
+            models(&blockstate, "minecraft:wall|east=none,north=false,up=false"),
+            ["post"]
+        );
+        assert_eq!(
+            models(&blockstate, "minecraft:wall|east=low,north=true,up=true"),
+            ["post", "north", "east", "up", "or"]
+        );
+        assert_eq!(
+            models(&blockstate, "minecraft:wall|east=tall,north=true,up=false"),
+            ["post", "north", "east", "or", "and"]
+        );
+        assert_eq!(
+            models(&blockstate, "minecraft:wall|north=false,south=true,up=true"),
+            ["post", "up", "or"]
+        );
+        assert_eq!(
+            models(
+                &blockstate,
+                "minecraft:wall|north=false,south=true,up=false"
+            ),
+            ["post"]
+        );
+    }
+
+    #[test]
+    fn resolves_texture_references() {
+        let textures = HashMap::from([
+            ("all".to_owned(), "block/stone".to_owned()),
+            ("side".to_owned(), "#all".to_owned()),
+            ("top".to_owned(), "mymod:block/top".to_owned()),
+            ("loop".to_owned(), "#other".to_owned()),
+            ("other".to_owned(), "#loop".to_owned()),
+        ]);
+
+        assert_eq!(
+            resolve_texture(&textures, "#side").as_deref(),
+            Some("minecraft:block/stone")
+        );
+        assert_eq!(
+            resolve_texture(&textures, "#top").as_deref(),
+            Some("mymod:block/top")
+        );
+        assert_eq!(
+            resolve_texture(&textures, "block/dirt").as_deref(),
+            Some("minecraft:block/dirt")
+        );
+        assert_eq!(resolve_texture(&textures, "#missing"), None);
+        assert_eq!(resolve_texture(&textures, "#loop"), None);
+    }
+
+    #[test]
+    fn resolves_model_parents() {
+        let mut pack = pack(&[
+            (
+                "assets/minecraft/models/block/cube.json",
+                json!({
+                    "parent": "block/block",
+                    "textures": { "particle": "#down" },
+                    "elements": [{ "from": [0, 0, 0], "to": [16, 16, 16] }],
+                }),
+            ),
+            (
+                "assets/minecraft/models/block/cube_all.json",
+                json!({
+                    "parent": "block/cube",
+                    "textures": { "down": "#all", "up": "#all" },
+                }),
+            ),
+            (
+                "assets/minecraft/models/block/stone.json",
+                json!({
+                    "parent": "minecraft:block/cube_all",
+                    "textures": { "all": "block/stone", "up": "block/stone_top" },
+                }),
+            ),
+        ]);
+
+        let (textures, elements) = pack.resolve_model("minecraft:block/stone").unwrap();
+
+        // Children override the texture variables of their parents.
+        assert_eq!(textures["up"], "block/stone_top");
+        assert_eq!(textures["down"], "#all");
+        assert_eq!(
+            resolve_texture(&textures, "#particle").as_deref(),
+            Some("minecraft:block/stone")
+        );
+        assert_eq!(elements.len(), 1);
+    }
+
+    #[test]
+    fn stops_at_missing_and_cyclic_parents() {
+        let mut pack = pack(&[
+            (
+                "assets/minecraft/models/item/generated.json",
+                json!({ "parent": "builtin/generated", "textures": { "layer0": "item/stick" } }),
+            ),
+            (
+                "assets/minecraft/models/block/a.json",
+                json!({ "parent": "block/b", "textures": { "a": "block/a" } }),
+            ),
+            (
+                "assets/minecraft/models/block/b.json",
+                json!({ "parent": "block/a", "textures": { "b": "block/b" } }),
+            ),
+        ]);
+
+        let (textures, elements) = pack.resolve_model("item/generated").unwrap();
+        assert_eq!(textures["layer0"], "item/stick");
+        assert!(elements.is_empty());
+
+        let (textures, elements) = pack.resolve_model("block/a").unwrap();
+        assert_eq!(textures.len(), 2);
+        assert!(elements.is_empty());
+
+        assert!(pack.resolve_model("block/missing").is_none());
+    }
+
+    #[test]
+    fn finds_rotated_face_textures() {
+        let mut pack = pack(&[
+            (
+                "assets/minecraft/blockstates/furnace.json",
+                json!({
+                    "variants": {
+                        "facing=north": { "model": "block/furnace" },
+                        "facing=east": { "model": "block/furnace", "y": 90 },
+                    }
+                }),
+            ),
+            (
+                "assets/minecraft/models/block/furnace.json",
+                json!({
+                    "textures": { "front": "block/furnace_front", "side": "block/furnace_side" },
+                    "elements": [{
+                        "from": [0, 0, 0],
+                        "to": [16, 16, 16],
+                        "faces": {
+                            "north": { "texture": "#front" },
+                            "east": { "texture": "#side", "tintindex": 0 },
+                            "up": { "texture": "#side" },
+                        },
+                    }],
+                }),
+            ),
+        ]);
+
+        let front = Some(("minecraft:block/furnace_front".to_owned(), false));
+        let side = Some(("minecraft:block/furnace_side".to_owned(), false));
+        let tinted_side = Some(("minecraft:block/furnace_side".to_owned(), true));
+
+        assert_eq!(
+            pack.face_textures("minecraft:furnace|facing=north"),
+            Some([
+                None,
+                tinted_side.clone(),
+                None,
+                side.clone(),
+                front.clone(),
+                None
+            ])
+        );
+        // Turned a quarter clockwise, the front faces east and the east side
+        // south.
+        assert_eq!(
+            pack.face_textures("minecraft:furnace|facing=east"),
+            Some([None, front, None, side, None, tinted_side])
+        );
+        assert_eq!(pack.face_textures("minecraft:stone"), None);
+    }
+}
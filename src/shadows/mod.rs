@@ -0,0 +1,259 @@
+use std::mem;
+
+use crate::camera::Camera;
+
+/// Number of shadow maps, each covering the view up to one of
+/// `CASCADE_DISTANCES` with a coarser resolution than the one before.
+pub const CASCADES: usize = 3;
+/// Distances from the eye along the view direction up to which each cascade
+/// reaches. Faces farther than the last one are never in shadow.
+const CASCADE_DISTANCES: [f32; CASCADES] = [24.0, 96.0, 384.0];
+const SHADOW_MAP_SIZE: u32 = 2048;
+pub const SHADOW_MAP_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+/// How far towards the sun from the part of the view a cascade covers blocks
+/// still cast shadows into it.
+const CASTER_DISTANCE: f32 = 384.0;
+/// Angle in degrees between the sun's path across the sky and the zenith,
+/// towards the south.
+const SUN_TILT: f32 = 30.0;
+
+/// Direction towards the sun at `angle` degrees along its path, rising in the
+/// east at 0, highest at 90 and setting in the west at 180.
+pub fn sun_dir(angle: f32) -> glam::Vec3 {
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let (tilt_sin, tilt_cos) = SUN_TILT.to_radians().sin_cos();
+
+    glam::Vec3::new(cos * tilt_cos, sin * tilt_cos, tilt_sin)
+}
+
+/// The box seen from the sun that one shadow map is drawn from, around the
+/// part of the view it covers.
+#[derive(Clone, Copy, Debug)]
+pub struct Cascade {
+    /// Relative to [`Camera::origin`].
+    light_from_world: glam::Mat4,
+    min: glam::Vec3,
+    max: glam::Vec3,
+    sun: glam::Vec3,
+    texel_size: f32,
+}
+
+impl Cascade {
+    fn new(camera: &Camera, aspect_ratio: f32, sun: glam::Vec3, near: f32, far: f32) -> Self {
+        let light_from_world = glam::Mat4::look_to_rh(glam::Vec3::ZERO, -sun, glam::Vec3::Y);
+
+        // The sphere keeps the size of the box, and moving it by whole texels
+        // keeps the shadows' edges from crawling as the camera moves.
+        let (center, radius) = camera.bounding_sphere(aspect_ratio, near, far);
+        let radius = radius.ceil();
+        let texel_size = 2.0 * radius / SHADOW_MAP_SIZE as f32;
+
+        let light_center = light_from_world.transform_point3(center);
+        let light_center = (light_center / texel_size).round() * texel_size;
+
+        Self {
+            light_from_world,
+            min: light_center - radius,
+            max: light_center + glam::Vec3::new(radius, radius, radius + CASTER_DISTANCE),
+            sun,
+            texel_size,
+        }
+    }
+
+    /// Projects the box onto its shadow map, with depths reversed like the
+    /// camera's.
+    pub fn clip_from_world(&self) -> glam::Mat4 {
+        let proj = glam::Mat4::orthographic_rh(
+            self.min.x,
+            self.max.x,
+            self.min.y,
+            self.max.y,
+            -self.min.z,
+            -self.max.z,
+        );
+
+        proj * self.light_from_world
+    }
+
+    /// Like [`Camera::clip_from_world_with_margin`], for culling what lies
+    /// outside the box grown by `margin`.
+    pub fn clip_from_world_with_margin(&self, margin: f32) -> glam::Mat4 {
+        let proj = glam::Mat4::orthographic_rh_gl(
+            self.min.x - margin,
+            self.max.x + margin,
+            self.min.y - margin,
+            self.max.y + margin,
+            -self.max.z - margin,
+            -self.min.z + margin,
+        );
+
+        proj * self.light_from_world
+    }
+
+    /// A point so far away from the sun that the faces turned towards it are
+    /// the ones turned away from the sun, which are drawn into the shadow maps
+    /// so that the faces lit by the sun never shadow themselves.
+    pub fn eye(&self) -> glam::Vec3 {
+        -1.0e6 * self.sun
+    }
+}
+
+/// The cascades covering the view of `camera`, for sunlight coming from `sun`.
+pub fn cascades(camera: &Camera, aspect_ratio: f32, sun: glam::Vec3) -> [Cascade; CASCADES] {
+    std::array::from_fn(|i| {
+        let near = if i == 0 {
+            0.0
+        } else {
+            CASCADE_DISTANCES[i - 1]
+        };
+
+        Cascade::new(camera, aspect_ratio, sun, near, CASCADE_DISTANCES[i])
+    })
+}
+
+/// The sun and the cascades its shadows are looked up in, laid out like
+/// `ShadowView` in `faces.wgsl`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShadowView {
+    clip_from_world: [glam::Mat4; CASCADES],
+    distances: [f32; CASCADES],
+    _padding: f32,
+    sun: glam::Vec3,
+    _padding_2: f32,
+    texel_sizes: [f32; CASCADES],
+    _padding_3: f32,
+}
+
+/// One shadow map per cascade, as the layers of a depth texture.
+#[derive(Debug)]
+pub struct ShadowMaps {
+    /// Drawn into one at a time.
+    pub layer_views: Vec<wgpu::TextureView>,
+    pub view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+    pub clip_from_world_buffers: Vec<wgpu::Buffer>,
+    pub view_buffer: wgpu::Buffer,
+}
+
+impl ShadowMaps {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("shadow_map_texture"),
+            size: wgpu::Extent3d {
+                width: SHADOW_MAP_SIZE,
+                height: SHADOW_MAP_SIZE,
+                depth_or_array_layers: CASCADES as u32,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: SHADOW_MAP_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let layer_views = (0..CASCADES as u32)
+            .map(|layer| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("shadow_map_layer_view"),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("shadow_map_view"),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+
+        // Filtered comparisons blend the shadows' edges over a texel. Depths
+        // are reversed, so what the sun reaches is at least as near as what
+        // was drawn.
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("shadow_sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            compare: Some(wgpu::CompareFunction::GreaterEqual),
+            ..Default::default()
+        });
+
+        let clip_from_world_buffers = (0..CASCADES)
+            .map(|_| {
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("shadow_clip_from_world_buffer"),
+                    size: mem::size_of::<glam::Mat4>() as u64,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                })
+            })
+            .collect();
+        let view_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("shadow_view_buffer"),
+            size: mem::size_of::<ShadowView>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            layer_views,
+            view,
+            sampler,
+            clip_from_world_buffers,
+            view_buffer,
+        }
+    }
+
+    /// Sets the cascades the shadow maps are drawn from in a frame, and the
+    /// sun lighting it.
+    pub fn write(&self, queue: &wgpu::Queue, cascades: &[Cascade; CASCADES], sun: glam::Vec3) {
+        for (cascade, buffer) in cascades.iter().zip(&self.clip_from_world_buffers) {
+            queue.write_buffer(
+                buffer,
+                0,
+                bytemuck::cast_slice(cascade.clip_from_world().as_ref()),
+            );
+        }
+
+        let view = ShadowView {
+            clip_from_world: cascades.map(|cascade| cascade.clip_from_world()),
+            distances: CASCADE_DISTANCES,
+            sun,
+            texel_sizes: cascades.map(|cascade| cascade.texel_size),
+            ..Default::default()
+        };
+        queue.write_buffer(&self.view_buffer, 0, bytemuck::bytes_of(&view));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sun_crosses_the_sky_from_east_to_west() {
+        let rising = sun_dir(0.0);
+        let noon = sun_dir(90.0);
+        let setting = sun_dir(180.0);
+
+        assert!(rising.x > 0.0 && rising.y.abs() < 1e-6);
+        assert!(noon.x.abs() < 1e-6 && noon.y > 0.0);
+        assert!(setting.x < 0.0 && setting.y.abs() < 1e-6);
+        assert!(sun_dir(270.0).y < 0.0);
+    }
+
+    #[test]
+    fn sun_is_tilted_towards_the_south() {
+        for angle in [0.0, 45.0, 90.0, 135.0, 180.0] {
+            let sun = sun_dir(angle);
+
+            assert!((sun.length() - 1.0).abs() < 1e-6);
+            assert!((sun.z - SUN_TILT.to_radians().sin()).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use std::{borrow::Cow, mem};
+
+use crate::renderer::DEPTH_FORMAT;
+
+/// Light left in the sky at night, like `MIN_LIGHT` in `faces.wgsl`.
+const MIN_DAYLIGHT: f32 = 0.03;
+
+/// What is seen where nothing is drawn, and the fog faces fade into it with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sky {
+    /// Linear color of the sky around and below the horizon in full daylight.
+    pub horizon: glam::Vec3,
+    /// Linear color of the sky straight up in full daylight.
+    pub zenith: glam::Vec3,
+    /// Whether the sun is drawn in the sky.
+    pub sun_disc: bool,
+    /// How thick the fog is over the draw distance, along the ground: what is
+    /// drawn farthest away keeps `exp(-fog_density²)` of its color, and 0
+    /// turns the fog off.
+    pub fog_density: f32,
+}
+
+impl Default for Sky {
+    fn default() -> Self {
+        Self {
+            horizon: glam::Vec3::new(0.55, 0.7, 1.0),
+            zenith: glam::Vec3::new(0.18, 0.38, 1.0),
+            sun_disc: true,
+            fog_density: 2.0,
+        }
+    }
+}
+
+/// The sky as seen in a frame, laid out like `SkyView` in `sky.wgsl`,
+/// `faces.wgsl` and `far_field.wgsl`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct SkyView {
+    world_from_clip: glam::Mat4,
+    horizon: glam::Vec3,
+    /// Fog per block along the ground from the eye.
+    fog_density: f32,
+    zenith: glam::Vec3,
+    sun_disc: u32,
+    sun: glam::Vec3,
+    _padding: f32,
+    eye: glam::Vec3,
+    _padding_2: f32,
+}
+
+/// Fills what is left undrawn with the sky, whose view is also used to fade
+/// faces into it.
+#[derive(Debug)]
+pub struct SkyPipeline {
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+    pub view_buffer: wgpu::Buffer,
+}
+
+impl SkyPipeline {
+    pub fn new(device: &wgpu::Device, color_format: wgpu::TextureFormat) -> Self {
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("sky_shader_module"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("sky.wgsl"))),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("sky_bind_group_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("sky_pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("sky_pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "vs_main",
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: "fs_main",
+                targets: &[Some(color_format.into())],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            // Drawn on the far plane, so that only the pixels still at the
+            // cleared depth are drawn on.
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Equal,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let view_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("sky_view_buffer"),
+            size: mem::size_of::<SkyView>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            bind_group_layout,
+            pipeline,
+            view_buffer,
+        }
+    }
+
+    /// Sets the sky of a frame, dimmed by `daylight`, with its fog spread over
+    /// `draw_distance` blocks.
+    #[allow(clippy::too_many_arguments)]
+    pub fn write(
+        &self,
+        queue: &wgpu::Queue,
+        sky: &Sky,
+        eye: glam::Vec3,
+        clip_from_world: glam::Mat4,
+        daylight: f32,
+        sun: glam::Vec3,
+        draw_distance: f32,
+    ) {
+        let brightness = daylight.max(MIN_DAYLIGHT);
+
+        let view = SkyView {
+            world_from_clip: clip_from_world.inverse(),
+            horizon: sky.horizon * brightness,
+            fog_density: sky.fog_density.max(0.0) / draw_distance.max(1.0),
+            zenith: sky.zenith * brightness,
+            sun_disc: sky.sun_disc as u32,
+            sun,
+            eye,
+            ..Default::default()
+        };
+        queue.write_buffer(&self.view_buffer, 0, bytemuck::bytes_of(&view));
+    }
+
+    /// Draws the sky into `color_view` where nothing was drawn yet.
+    pub fn encode(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        color_view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+    ) {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("sky_bind_group"),
+            layout: &self.bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: self.view_buffer.as_entire_binding(),
+            }],
+        });
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("sky_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+
+        pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,56 @@
+@group(0)
+@binding(0)
+var<uniform> sky: SkyView;
+
+// Angular radius of the sun, and of the blurred edge around it, in radians.
+const SUN_RADIUS = 0.035;
+const SUN_EDGE = 0.004;
+const SUN_COLOR = vec3(1.0, 0.95, 0.8);
+
+// Laid out like `SkyView` in `sky/mod.rs`.
+struct SkyView {
+    world_from_clip: mat4x4<f32>,
+    horizon: vec3<f32>,
+    fog_density: f32,
+    zenith: vec3<f32>,
+    sun_disc: u32,
+    sun: vec3<f32>,
+    eye: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+}
+
+// A triangle covering the screen, on the far plane so that the depth test
+// only lets through the pixels nothing was drawn on.
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2(f32((vertex_index << 1) & 2), f32(vertex_index & 2));
+    let ndc = uv * 2.0 - 1.0;
+
+    return VertexOutput(vec4(ndc, 0.0, 1.0), ndc);
+}
+
+// Matches `skyColor` in `faces.wgsl` and `far_field.wgsl`.
+fn skyColor(dir: vec3<f32>) -> vec3<f32> {
+    return mix(sky.horizon, sky.zenith, smoothstep(-0.1, 0.7, dir.y));
+}
+
+@fragment
+fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
+    let pos = sky.world_from_clip * vec4(vertex.ndc, 0.5, 1.0);
+    let dir = normalize(pos.xyz / pos.w - sky.eye);
+
+    var color = skyColor(dir);
+
+    if bool(sky.sun_disc) {
+        let angle = acos(clamp(dot(dir, sky.sun), -1.0, 1.0));
+        let disc = 1.0 - smoothstep(SUN_RADIUS - SUN_EDGE, SUN_RADIUS + SUN_EDGE, angle);
+
+        color = mix(color, SUN_COLOR, disc);
+    }
+
+    return vec4(color, 1.0);
+}
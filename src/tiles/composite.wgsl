@@ -0,0 +1,21 @@
+@group(0)
+@binding(0)
+var far_field: texture_2d<f32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2(f32((vertex_index << 1) & 2), f32(vertex_index & 2));
+
+    return vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    let color = textureLoad(far_field, vec2<i32>(pos.xy), 0);
+
+    if color.a == 0.0 {
+        discard;
+    }
+
+    return vec4(color.rgb, 1.0);
+}
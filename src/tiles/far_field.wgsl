@@ -0,0 +1,238 @@
+@group(0)
+@binding(0)
+var<storage> active_tiles: array<u32>;
+@group(0)
+@binding(1)
+var<storage> surfaces: array<array<vec2<u32>, 16>>;
+@group(0)
+@binding(2)
+var<storage> colors: array<u32>;
+@group(0)
+@binding(3)
+var<storage> block_tints: array<u32>;
+@group(0)
+@binding(4)
+var<storage> biome_colors: array<array<u32, 3>>;
+@group(0)
+@binding(5)
+var<uniform> view: FarFieldView;
+@group(0)
+@binding(6)
+var<uniform> sky: SkyView;
+@group(0)
+@binding(7)
+var depth_texture: texture_depth_2d;
+@group(0)
+@binding(8)
+var depth_compare: sampler_comparison;
+@group(0)
+@binding(9)
+var far_field: texture_storage_2d<rgba16float, write>;
+
+const TILE_SIZE = 16u;
+// Matches `FAR_GRID_SIZE` in `world.rs`.
+const FAR_GRID_SIZE = 256;
+// Matches `SURFACE_CELL_SIZE` in `region.rs`.
+const CELL_SIZE = 4.0;
+const COLUMN_CELLS = 4;
+const MAX_STEPS = 2048u;
+
+// Matches the lighting in `faces.wgsl`, without shadows or ambient occlusion.
+const AMBIENT = 0.3;
+const DIFFUSE = 0.7;
+const BLOCK_LIGHT = vec3(1.0, 0.9, 0.75);
+const MIN_LIGHT = 0.03;
+const MAX_LIGHT_LEVEL = 15.0;
+
+struct FarFieldView {
+    world_from_clip: mat4x4<f32>,
+    origin: vec3<i32>,
+    start: f32,
+    eye: vec3<f32>,
+    end: f32,
+    size: vec2<u32>,
+    top: f32,
+    daylight: f32,
+    sun: vec3<f32>,
+}
+
+// Laid out like `SkyView` in `sky/mod.rs`.
+struct SkyView {
+    world_from_clip: mat4x4<f32>,
+    horizon: vec3<f32>,
+    fog_density: f32,
+    zenith: vec3<f32>,
+    sun_disc: u32,
+    sun: vec3<f32>,
+    eye: vec3<f32>,
+}
+
+// Surface cell at cell coordinates relative to the origin, i.e. in units of
+// `CELL_SIZE` blocks, packed like `Column::surface` in `region.rs`.
+fn loadCell(cell: vec2<i32>) -> vec2<u32> {
+    let world_cell = cell + view.origin.xz * COLUMN_CELLS;
+    let column = world_cell >> vec2(2u);
+    let slot = column & vec2(FAR_GRID_SIZE - 1);
+    let local_cell = world_cell & vec2(COLUMN_CELLS - 1);
+
+    return surfaces[slot.y * FAR_GRID_SIZE + slot.x][local_cell.y * COLUMN_CELLS + local_cell.x];
+}
+
+struct Hit {
+    cell: vec2<u32>,
+    norm: vec3<f32>,
+    // Distance from the eye along the ray.
+    dist: f32,
+}
+
+// Walks the surface cells crossed by a ray from the eye between the start and
+// end distances, and stops at the first one whose column of blocks it enters,
+// either through its top or one of its sides.
+fn march(dir: vec3<f32>) -> Hit {
+    var hit = Hit(vec2(0u), vec3(0.0), 0.0);
+
+    let horizontal_len = length(dir.xz);
+    if horizontal_len < 1e-6 {
+        return hit;
+    }
+
+    var t = view.start / horizontal_len;
+    let t_end = view.end / horizontal_len;
+
+    let start_pos = fma(dir.xz, vec2(t), view.eye.xz) / CELL_SIZE;
+    var cell = vec2<i32>(floor(start_pos));
+    let step = vec2<i32>(sign(dir.xz));
+
+    // Distances along the ray to cross a cell, and to the next cell along each
+    // axis, never reached along an axis the ray doesn't move on.
+    let nonzero = dir.xz != vec2(0.0);
+    let safe_dir = select(vec2(1.0), dir.xz, nonzero);
+    let t_delta = select(vec2(3.4e38), abs(CELL_SIZE / safe_dir), nonzero);
+    let boundary = vec2<f32>(cell + max(step, vec2(0))) * CELL_SIZE;
+    var t_next = select(vec2(3.4e38), (boundary - view.eye.xz) / safe_dir, nonzero);
+
+    // The ray enters the first cell through the side facing the eye the most.
+    var norm = select(
+        vec3(0.0, 0.0, -f32(step.y)),
+        vec3(-f32(step.x), 0.0, 0.0),
+        abs(dir.x) > abs(dir.z),
+    );
+
+    for (var i = 0u; i < MAX_STEPS && t < t_end; i++) {
+        let y = fma(dir.y, t, view.eye.y);
+        if dir.y >= 0.0 && y > view.top {
+            break;
+        }
+
+        let t_exit = min(min(t_next.x, t_next.y), t_end);
+        let surface_cell = loadCell(cell);
+
+        if extractBits(surface_cell.x, 0u, 16u) != 0 {
+            let height = f32((i32(surface_cell.x) >> 16) - view.origin.y * 16);
+
+            if y <= height {
+                hit = Hit(surface_cell, norm, t);
+                break;
+            }
+            if fma(dir.y, t_exit, view.eye.y) <= height {
+                hit = Hit(surface_cell, vec3(0.0, 1.0, 0.0), (height - view.eye.y) / dir.y);
+                break;
+            }
+        }
+
+        if t_next.x < t_next.y {
+            cell.x += step.x;
+            t = t_next.x;
+            t_next.x += t_delta.x;
+            norm = vec3(-f32(step.x), 0.0, 0.0);
+        } else {
+            cell.y += step.y;
+            t = t_next.y;
+            t_next.y += t_delta.y;
+            norm = vec3(0.0, 0.0, -f32(step.y));
+        }
+    }
+
+    return hit;
+}
+
+// Matches `faceColor` in `faces.wgsl`, with the biome of the surface cell.
+fn cellColor(cell: vec2<u32>) -> u32 {
+    let block_id = extractBits(cell.x, 0u, 16u) - 1;
+    let color = colors[block_id];
+    let tint = block_tints[block_id];
+
+    if tint == 0u {
+        return color;
+    }
+
+    return insertBits(color, biome_colors[extractBits(cell.y, 0u, 8u)][tint - 1], 0u, 24u);
+}
+
+// Matches `lightLevelBrightness` in `faces.wgsl`.
+fn lightLevelBrightness(level: u32) -> f32 {
+    let fraction = f32(level) / MAX_LIGHT_LEVEL;
+    return fraction / (4.0 - 3.0 * fraction);
+}
+
+// Matches `skyColor` in `sky.wgsl`.
+fn skyColor(dir: vec3<f32>) -> vec3<f32> {
+    return mix(sky.horizon, sky.zenith, smoothstep(-0.1, 0.7, dir.y));
+}
+
+// Like `fog` in `faces.wgsl`, for a color seen `dist` blocks away along `dir`.
+fn fog(color: vec3<f32>, dir: vec3<f32>, dist: f32) -> vec3<f32> {
+    let depth = sky.fog_density * dist * length(dir.xz);
+
+    return mix(skyColor(dir), color, exp(-depth * depth));
+}
+
+// Colors the pixel of the far field texture at `global_id`, one workgroup per
+// tile, leaving it transparent unless nothing was drawn on it (its depth is
+// still the cleared 0) and a ray from the eye through it hits a surface.
+@compute
+@workgroup_size(16, 16)
+fn marchFarField(
+    @builtin(workgroup_id) tile: vec3<u32>,
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+) {
+    let pixel = global_id.xy;
+    if any(pixel >= view.size) {
+        return;
+    }
+
+    let width_in_tiles = (view.size.x + TILE_SIZE - 1) / TILE_SIZE;
+    let tile_index = tile.y * width_in_tiles + tile.x;
+
+    let coords = (vec2<f32>(pixel) + vec2(0.5)) / vec2<f32>(view.size);
+
+    var color = vec4(0.0);
+    if extractBits(active_tiles[tile_index / 32], tile_index % 32, 1u) != 0
+        && textureSampleCompareLevel(depth_texture, depth_compare, coords, 0.0) == 1.0 {
+        color = shade(coords);
+    }
+
+    textureStore(far_field, pixel, color);
+}
+
+// Color of the far field seen through `coords` of the screen, or transparent.
+fn shade(coords: vec2<f32>) -> vec4<f32> {
+    let ndc = fma(coords, vec2(2.0, -2.0), vec2(-1.0, 1.0));
+    let pos = view.world_from_clip * vec4(ndc, 0.5, 1.0);
+    let dir = normalize(pos.xyz / pos.w - view.eye);
+
+    let hit = march(dir);
+    if hit.cell.x == 0 {
+        return vec4(0.0);
+    }
+
+    let block_color = unpack4x8unorm(cellColor(hit.cell));
+    let sun = max(0.0, dot(hit.norm, view.sun));
+
+    // Lit by the light above the cell's block, like its top face.
+    let block = lightLevelBrightness(extractBits(hit.cell.y, 8u, 4u));
+    let sky = lightLevelBrightness(extractBits(hit.cell.y, 12u, 4u)) * view.daylight;
+    let light = max(max(vec3(sky * (AMBIENT + DIFFUSE * sun)), block * BLOCK_LIGHT), vec3(MIN_LIGHT));
+
+    return vec4(fog(light * block_color.rgb, dir, hit.dist), 1.0);
+}
@@ -1,16 +1,50 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, mem, ops::Range};
 
 use wgpu::util::DeviceExt;
 
+use crate::{faces::FaceColors, world::WorldBuffers};
+
+/// Format of the far field texture, with the precision of the color target
+/// before it is encoded to sRGB.
+const FAR_FIELD_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// What the far field is drawn from, laid out like `FarFieldView` in
+/// `far_field.wgsl`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct FarFieldView {
+    world_from_clip: glam::Mat4,
+    origin: glam::IVec3,
+    /// Horizontal distances from the eye between which the far field is drawn.
+    start: f32,
+    eye: glam::Vec3,
+    end: f32,
+    size: glam::UVec2,
+    /// Height of the highest surface cell, above which rays going up can stop.
+    top: f32,
+    /// Scale of the sky light the surfaces are lit by, like in `faces.wgsl`.
+    daylight: f32,
+    sun: glam::Vec3,
+    _padding: f32,
+}
+
+/// Finds the 16×16 screen tiles where something is left undrawn once the opaque
+/// faces are, ray-marches the surfaces of the columns beyond the view distance
+/// there into a far field texture, and composites it into the color target.
 #[derive(Debug)]
 pub struct TilesPipeline {
     activate_tiles_bind_group_layout: wgpu::BindGroupLayout,
     activate_tiles_pipeline: wgpu::ComputePipeline,
     depth_compare_sampler: wgpu::Sampler,
+    far_field_bind_group_layout: wgpu::BindGroupLayout,
+    far_field_pipeline: wgpu::ComputePipeline,
+    far_field_view_buffer: wgpu::Buffer,
+    composite_bind_group_layout: wgpu::BindGroupLayout,
+    composite_pipeline: wgpu::RenderPipeline,
 }
 
 impl TilesPipeline {
-    pub fn new(device: &wgpu::Device) -> Self {
+    pub fn new(device: &wgpu::Device, color_format: wgpu::TextureFormat) -> Self {
         let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("activate_tiles_shader_module"),
             source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("tiles.wgsl"))),
@@ -36,26 +70,159 @@ impl TilesPipeline {
 
         let activate_tiles_bind_group_layout = activate_tiles_pipeline.get_bind_group_layout(0);
 
+        let far_field_shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("far_field_shader_module"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("far_field.wgsl"))),
+        });
+
+        // The GL backend binds the buffers of a derived layout to the wrong slots
+        // when the shader doesn't use them in the order of their bindings.
+        let buffer_entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let far_field_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("far_field_bind_group_layout"),
+                entries: &[
+                    buffer_entry(0, wgpu::BufferBindingType::Storage { read_only: true }),
+                    buffer_entry(1, wgpu::BufferBindingType::Storage { read_only: true }),
+                    buffer_entry(2, wgpu::BufferBindingType::Storage { read_only: true }),
+                    buffer_entry(3, wgpu::BufferBindingType::Storage { read_only: true }),
+                    buffer_entry(4, wgpu::BufferBindingType::Storage { read_only: true }),
+                    buffer_entry(5, wgpu::BufferBindingType::Uniform),
+                    buffer_entry(6, wgpu::BufferBindingType::Uniform),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 7,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Depth,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 8,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 9,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::StorageTexture {
+                            access: wgpu::StorageTextureAccess::WriteOnly,
+                            format: FAR_FIELD_FORMAT,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+        let far_field_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("far_field_pipeline_layout"),
+                bind_group_layouts: &[&far_field_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let far_field_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("far_field_pipeline"),
+            layout: Some(&far_field_pipeline_layout),
+            module: &far_field_shader_module,
+            entry_point: "marchFarField",
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+
+        let composite_shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("far_field_composite_shader_module"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("composite.wgsl"))),
+        });
+
+        let composite_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("far_field_composite_pipeline"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &composite_shader_module,
+                entry_point: "vs_main",
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &composite_shader_module,
+                entry_point: "fs_main",
+                targets: &[Some(color_format.into())],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let composite_bind_group_layout = composite_pipeline.get_bind_group_layout(0);
+
+        let far_field_view_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("far_field_view_buffer"),
+            size: mem::size_of::<FarFieldView>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
         Self {
             activate_tiles_bind_group_layout,
             activate_tiles_pipeline,
             depth_compare_sampler,
+            far_field_bind_group_layout,
+            far_field_pipeline,
+            far_field_view_buffer,
+            composite_bind_group_layout,
+            composite_pipeline,
         }
     }
 
+    /// Draws the far field between `far_field.start` and `far_field.end` blocks
+    /// from the eye along the ground into `color_view`, where nothing was drawn
+    /// yet, through a texture from [`create_far_field_texture`]. Does nothing
+    /// when the range is empty.
+    #[allow(clippy::too_many_arguments)]
     pub fn encode(
         &self,
         device: &wgpu::Device,
+        queue: &wgpu::Queue,
         encoder: &mut wgpu::CommandEncoder,
+        world: &WorldBuffers,
+        face_colors: &FaceColors,
+        origin: glam::IVec3,
+        eye: glam::Vec3,
+        clip_from_world: glam::Mat4,
+        far_field: Range<f32>,
+        daylight: f32,
+        sun: glam::Vec3,
+        sky_view_buffer: &wgpu::Buffer,
+        color_view: &wgpu::TextureView,
         depth_view: &wgpu::TextureView,
-        config: &wgpu::SurfaceConfiguration,
-    ) -> wgpu::Buffer {
-        let size = glam::UVec2::new(config.width, config.height);
-        let tiles = glam::UVec2::new(config.width.div_ceil(16), config.height.div_ceil(16));
+        far_field_view: &wgpu::TextureView,
+        size: glam::UVec2,
+    ) {
+        if far_field.is_empty() {
+            return;
+        }
+
+        let tiles = glam::UVec2::new(size.x.div_ceil(16), size.y.div_ceil(16));
 
         let active_tile_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("active_tile_buffer"),
-            size: (tiles.x * tiles.y).div_ceil(32) as u64,
+            size: (tiles.x * tiles.y).div_ceil(32) as u64 * mem::size_of::<u32>() as u64,
             usage: wgpu::BufferUsages::STORAGE,
             mapped_at_creation: false,
         });
@@ -100,6 +267,126 @@ impl TilesPipeline {
             pass.dispatch_workgroups(tiles.x.div_ceil(2), tiles.y.div_ceil(2), 1);
         }
 
-        active_tile_buffer
+        let view = FarFieldView {
+            world_from_clip: clip_from_world.inverse(),
+            origin,
+            start: far_field.start,
+            eye,
+            end: far_field.end,
+            size,
+            top: (world.surface_top() - origin.y * 16) as f32,
+            daylight,
+            sun,
+            _padding: 0.0,
+        };
+        queue.write_buffer(&self.far_field_view_buffer, 0, bytemuck::bytes_of(&view));
+
+        let far_field_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("far_field_bind_group"),
+            layout: &self.far_field_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: active_tile_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: world.far_field_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: face_colors.block_color_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: face_colors.block_tint_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: face_colors.biome_color_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: self.far_field_view_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: sky_view_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: wgpu::BindingResource::TextureView(depth_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 8,
+                    resource: wgpu::BindingResource::Sampler(&self.depth_compare_sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 9,
+                    resource: wgpu::BindingResource::TextureView(far_field_view),
+                },
+            ],
+        });
+
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("far_field_pass"),
+                timestamp_writes: None,
+            });
+
+            pass.set_pipeline(&self.far_field_pipeline);
+            pass.set_bind_group(0, &far_field_bind_group, &[]);
+
+            pass.dispatch_workgroups(tiles.x, tiles.y, 1);
+        }
+
+        let composite_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("far_field_composite_bind_group"),
+            layout: &self.composite_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(far_field_view),
+            }],
+        });
+
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("far_field_composite_pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: color_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            pass.set_pipeline(&self.composite_pipeline);
+            pass.set_bind_group(0, &composite_bind_group, &[]);
+
+            pass.draw(0..3, 0..1);
+        }
     }
 }
+
+/// Creates the texture the far field is ray-marched into, as big as the color target.
+pub fn create_far_field_texture(device: &wgpu::Device, size: glam::UVec2) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("far_field_texture"),
+        size: wgpu::Extent3d {
+            width: size.x,
+            height: size.y,
+            ..Default::default()
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: FAR_FIELD_FORMAT,
+        usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    })
+}
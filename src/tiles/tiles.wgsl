@@ -38,10 +38,12 @@ fn activateTiles(
 
     workgroupBarrier();
 
-    if all(spaced_global_id.xy % vec2(8) == vec2(0)) {
-        let width_in_tiles = (size.x + 15) / 16; 
-        let local_is_active = atomicLoad(&local_active_tiles[local_index]);
-        let tile_index = tile.x + tile.y * width_in_tiles;
+    // The first invocation of each tile writes it out, unless the tile is past
+    // the screen's edge.
+    let tiles = (size + vec2(15u)) / 16;
+    if all(spaced_global_id.xy % vec2(16) == vec2(0)) && all(tile < tiles) {
+        let local_is_active = atomicLoad(&local_active_tiles[local_tile_index]);
+        let tile_index = tile.x + tile.y * tiles.x;
 
         atomicOr(&active_tiles[tile_index / 32], local_is_active << (tile_index % 32));
     }
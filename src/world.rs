@@ -0,0 +1,1121 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt,
+    fs::{self, File},
+    io, mem,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Condvar, Mutex, MutexGuard},
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    models::{self, ModelBuffers},
+    opacity::Opacity,
+    palette::Palette,
+    region::{
+        self, Column, LoadOptions, Region, RegionError, Sides, BIOME_CELLS, MAX_LOD,
+        SURFACE_CELL_SIZE, SURFACE_LOD,
+    },
+};
+
+/// Extra distance in chunks beyond the view distance before loaded columns are
+/// dropped, so that moving back and forth across a chunk border doesn't reload
+/// them.
+const EVICTION_MARGIN: u32 = 4;
+/// Width in columns of the grid the surfaces of columns are kept in on the GPU,
+/// each column going to its coordinates modulo the width. Matches
+/// `FAR_GRID_SIZE` in `far_field.wgsl`.
+const FAR_GRID_SIZE: u32 = 256;
+/// Largest [`LoadOptions::far_distance`], beyond which loaded columns would
+/// share cells of the grid.
+pub const MAX_FAR_DISTANCE: u32 = FAR_GRID_SIZE / 2 - EVICTION_MARGIN - 1;
+/// Regions kept open by each loader thread.
+const MAX_OPEN_REGIONS: usize = 16;
+/// Most [`FacePage`]s faces are split across once they don't fit in a single
+/// storage buffer binding.
+pub const MAX_FACE_PAGES: usize = 16;
+
+#[derive(Debug, Default)]
+struct LoadQueue {
+    /// Sorted with the nearest column last.
+    columns: Vec<(glam::IVec2, u32)>,
+    closed: bool,
+}
+
+#[derive(Debug)]
+struct Loader {
+    region_paths: HashMap<[i32; 2], PathBuf>,
+    options: LoadOptions,
+    palette: Mutex<Palette>,
+    queue: Mutex<LoadQueue>,
+    queued: Condvar,
+}
+
+impl Loader {
+    /// Waits for the nearest queued column, or returns `None` once the world is
+    /// dropped.
+    fn next(&self) -> Option<(glam::IVec2, u32)> {
+        let mut queue = self.queue.lock().unwrap();
+
+        loop {
+            if queue.closed {
+                return None;
+            }
+            if let Some(column) = queue.columns.pop() {
+                return Some(column);
+            }
+
+            queue = self.queued.wait(queue).unwrap();
+        }
+    }
+
+    fn run(&self, sender: mpsc::Sender<(glam::IVec2, u32, Result<Column, RegionError>)>) {
+        // Regions that failed to open are kept as `None` so that the error is only
+        // reported for the first of their columns.
+        let mut regions: HashMap<[i32; 2], Option<Region<File>>> = HashMap::new();
+
+        while let Some((pos, lod)) = self.next() {
+            let region_pos = [pos.x.div_euclid(32), pos.y.div_euclid(32)];
+
+            if regions.len() >= MAX_OPEN_REGIONS && !regions.contains_key(&region_pos) {
+                regions.clear();
+            }
+
+            let mut open_error = None;
+            let region = regions.entry(region_pos).or_insert_with(|| {
+                File::open(&self.region_paths[&region_pos])
+                    .map_err(|error| RegionError::Io {
+                        region: region_pos,
+                        error,
+                    })
+                    .and_then(|file| Region::new(file, region_pos))
+                    .map_err(|error| open_error = Some(error))
+                    .ok()
+            });
+
+            let column = match (region, open_error) {
+                (Some(region), _) => {
+                    let local_pos = [pos.x.rem_euclid(32) as usize, pos.y.rem_euclid(32) as usize];
+                    region.column(local_pos, self.options, lod, &self.palette)
+                }
+                (None, Some(error)) => Err(error),
+                (None, None) => Ok(Column {
+                    pos,
+                    sections: Vec::new(),
+                    lods: lod..lod + 1,
+                    sides: Sides::default(),
+                    surface: Default::default(),
+                }),
+            };
+
+            if sender.send((pos, lod, column)).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// Columns loaded and dropped by [`World::update`].
+#[derive(Debug, Default)]
+pub struct WorldUpdate {
+    pub loaded: Vec<Column>,
+    pub evicted: Vec<glam::IVec2>,
+    /// Columns that failed to load and were left empty, with
+    /// [`LoadOptions::lenient`] set.
+    pub skipped: Vec<RegionError>,
+}
+
+impl WorldUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.loaded.is_empty() && self.evicted.is_empty() && self.skipped.is_empty()
+    }
+}
+
+/// A world whose chunks are loaded by background threads around the eye, the
+/// nearest first.
+#[derive(Debug)]
+pub struct World {
+    loader: Arc<Loader>,
+    threads: Vec<JoinHandle<()>>,
+    receiver: mpsc::Receiver<(glam::IVec2, u32, Result<Column, RegionError>)>,
+    /// Received by [`World::wait`] for the next [`World::update`].
+    received: Vec<(glam::IVec2, u32, Result<Column, RegionError>)>,
+    requested: HashSet<glam::IVec2>,
+    /// Loaded columns with the finest level of detail they were loaded with.
+    loaded: HashMap<glam::IVec2, u32>,
+    /// Column containing the eye when the queue was last built.
+    center: Option<glam::IVec2>,
+}
+
+impl World {
+    /// Finds every `r.X.Z.mca` file either in `path/region` or, if that does not
+    /// exist, directly in `path`. Chunks are only read once [`World::update`]
+    /// asks for them.
+    pub fn open(path: impl AsRef<Path>, options: LoadOptions) -> io::Result<Self> {
+        let path = path.as_ref();
+        let region_dir = path.join("region");
+        let region_dir = if region_dir.is_dir() {
+            region_dir
+        } else {
+            path.to_path_buf()
+        };
+
+        let mut region_paths = HashMap::new();
+        for entry in fs::read_dir(&region_dir)? {
+            let entry = entry?;
+
+            if let Some(coords) = entry.file_name().to_str().and_then(parse_region_file_name) {
+                region_paths.insert(coords, entry.path());
+            }
+        }
+
+        let loader = Arc::new(Loader {
+            region_paths,
+            options,
+            palette: Mutex::default(),
+            queue: Mutex::default(),
+            queued: Condvar::new(),
+        });
+
+        let (sender, receiver) = mpsc::channel();
+        let thread_count = thread::available_parallelism()
+            .map_or(1, |count| count.get().saturating_sub(1))
+            .max(1);
+        let threads = (0..thread_count)
+            .map(|_| {
+                let loader = loader.clone();
+                let sender = sender.clone();
+
+                thread::spawn(move || loader.run(sender))
+            })
+            .collect();
+
+        Ok(Self {
+            loader,
+            threads,
+            receiver,
+            received: Vec::new(),
+            requested: HashSet::new(),
+            loaded: HashMap::new(),
+            center: None,
+        })
+    }
+
+    /// Block states of every column loaded so far. Loader threads wait while it
+    /// is held.
+    pub fn palette(&self) -> MutexGuard<'_, Palette> {
+        self.loader.palette.lock().unwrap()
+    }
+
+    /// Whether columns near the eye given to the last [`World::update`] are still
+    /// being loaded.
+    pub fn is_loading(&self) -> bool {
+        !self.requested.is_empty() || !self.received.is_empty()
+    }
+
+    /// Blocks until a column has been loaded, unless none is being loaded.
+    pub fn wait(&mut self) {
+        if self.received.is_empty() && !self.requested.is_empty() {
+            if let Ok(column) = self.receiver.recv() {
+                self.received.push(column);
+            }
+        }
+    }
+
+    /// Forgets which columns were loaded so that the next [`World::update`] loads
+    /// them again, e.g. for a new [`Renderer`](crate::Renderer).
+    pub fn reload(&mut self) {
+        self.loaded.clear();
+        self.center = None;
+    }
+
+    fn far_distance(&self) -> u32 {
+        self.loader.options.far_distance.min(MAX_FAR_DISTANCE)
+    }
+
+    /// Forgets that the column at chunk coordinates `pos` was loaded, e.g. when
+    /// it could not be drawn, so that it is loaded again once the eye moves to
+    /// another column.
+    pub fn forget(&mut self, pos: glam::IVec2) {
+        self.loaded.remove(&pos);
+    }
+
+    /// Horizontal distance in blocks from the eye up to which columns are drawn,
+    /// in full or as part of the far field.
+    pub fn draw_distance(&self) -> f32 {
+        let view_distance = self.loader.options.view_distance;
+
+        (view_distance.max(self.far_distance()) * 16) as f32
+    }
+
+    /// Horizontal distances in blocks from the eye between which the surfaces of
+    /// the columns beyond the view distance are drawn, starting a column short of
+    /// the view distance since the eye can be anywhere in its column. Empty when
+    /// only columns within the view distance are loaded.
+    pub fn far_field(&self) -> Range<f32> {
+        let view_distance = self.loader.options.view_distance;
+        let far_distance = self.far_distance();
+        if far_distance <= view_distance {
+            return 0.0..0.0;
+        }
+
+        (view_distance.saturating_sub(1) * 16) as f32..(far_distance * 16) as f32
+    }
+
+    /// Collects the columns loaded since the last call, and when `eye` moved to
+    /// another column, drops the ones now too far and queues the ones now in
+    /// view, nearest first, along with the ones now close enough to need a finer
+    /// level of detail. Columns beyond the view distance but within the far
+    /// distance only have their surface loaded, and are loaded again in full
+    /// once in view.
+    ///
+    /// Fails with the first column that could not be loaded, leaving it empty
+    /// and the other columns received to the next call, unless
+    /// [`LoadOptions::lenient`] is set, in which case the column is left empty
+    /// and the error returned in `WorldUpdate::skipped`.
+    pub fn update(&mut self, eye: glam::DVec3) -> Result<WorldUpdate, RegionError> {
+        let center = (glam::DVec2::new(eye.x, eye.z) / 16.0).floor().as_ivec2();
+        let view_distance = self.loader.options.view_distance;
+        let far_distance = self.far_distance().max(view_distance);
+        // Columns loaded in full are dropped at the view distance, to be loaded
+        // again with only their surface if still within the far distance.
+        let load_distance = |lod: u32| match lod {
+            SURFACE_LOD => far_distance,
+            _ => view_distance,
+        };
+        let in_range = |pos: glam::IVec2, distance: u32| {
+            (pos - center).length_squared() <= (distance * distance) as i32
+        };
+        // The eye can be anywhere in its column, so distances are rounded down
+        // by a column.
+        let lod_enabled = self.loader.options.lod;
+        let needed_lod = |pos: glam::IVec2| {
+            if !in_range(pos, view_distance) {
+                return SURFACE_LOD;
+            }
+
+            let distance = ((pos - center).as_vec2().length() - 1.0).max(0.0) * 16.0;
+
+            if lod_enabled {
+                region::lod(distance)
+            } else {
+                0
+            }
+        };
+
+        // Returned once every received column is handled, so that none is left
+        // requested forever.
+        let mut first_error = None;
+        let mut loaded = Vec::new();
+        let mut skipped = Vec::new();
+
+        self.received.extend(self.receiver.try_iter());
+        for (pos, lod, column) in self.received.drain(..) {
+            self.requested.remove(&pos);
+
+            let column = match column {
+                Ok(column) => Some(column),
+                Err(error) if self.loader.options.lenient => {
+                    skipped.push(error);
+                    None
+                }
+                Err(error) => {
+                    first_error.get_or_insert(error);
+                    None
+                }
+            };
+
+            if in_range(pos, load_distance(lod) + EVICTION_MARGIN) {
+                self.loaded.insert(pos, lod);
+                loaded.extend(
+                    column
+                        .filter(|column| {
+                            !column.sections.is_empty()
+                                || column.surface.iter().any(|&[cell, _]| cell != 0)
+                        })
+                        .map(|column| (lod, column)),
+                );
+            }
+        }
+
+        // The columns loaded along with a failed one are kept for the next call.
+        if let Some(error) = first_error {
+            self.received.extend(
+                loaded
+                    .into_iter()
+                    .map(|(lod, column)| (column.pos, lod, Ok(column))),
+            );
+            return Err(error);
+        }
+
+        let mut update = WorldUpdate {
+            loaded: loaded.into_iter().map(|(_, column)| column).collect(),
+            evicted: Vec::new(),
+            skipped,
+        };
+
+        if self.center == Some(center) {
+            return Ok(update);
+        }
+
+        self.center = Some(center);
+
+        self.loaded.retain(|&pos, &mut lod| {
+            let keep = in_range(pos, load_distance(lod) + EVICTION_MARGIN);
+            if !keep {
+                update.evicted.push(pos);
+            }
+
+            keep
+        });
+
+        let mut queue = self.loader.queue.lock().unwrap();
+
+        // Columns that no loader has started yet are queued again if still in view.
+        for (pos, _) in queue.columns.drain(..) {
+            self.requested.remove(&pos);
+        }
+
+        let radius = far_distance as i32;
+        for x in -radius..=radius {
+            for z in -radius..=radius {
+                let pos = center + glam::IVec2::new(x, z);
+                let region_pos = [pos.x.div_euclid(32), pos.y.div_euclid(32)];
+
+                let lod = needed_lod(pos);
+
+                if in_range(pos, far_distance)
+                    && self.loader.region_paths.contains_key(&region_pos)
+                    && self.loaded.get(&pos).is_none_or(|&loaded| lod < loaded)
+                    && !self.requested.contains(&pos)
+                {
+                    queue.columns.push((pos, lod));
+                }
+            }
+        }
+
+        queue
+            .columns
+            .sort_unstable_by_key(|&(pos, _)| -(pos - center).length_squared());
+        self.requested
+            .extend(queue.columns.iter().map(|&(pos, _)| pos));
+
+        drop(queue);
+        self.loader.queued.notify_all();
+
+        Ok(update)
+    }
+}
+
+impl Drop for World {
+    fn drop(&mut self) {
+        self.loader.queue.lock().unwrap().closed = true;
+        self.loader.queued.notify_all();
+
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Parses region coordinates out of a `r.X.Z.mca` file name.
+fn parse_region_file_name(name: &str) -> Option<[i32; 2]> {
+    let mut parts = name.strip_prefix("r.")?.strip_suffix(".mca")?.split('.');
+
+    let x = parts.next()?.parse().ok()?;
+    let z = parts.next()?.parse().ok()?;
+
+    parts.next().is_none().then_some([x, z])
+}
+
+/// A 16×16×16 section of blocks as stored on the GPU, laid out like `Chunk` in
+/// the shaders.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct ChunkRecord {
+    /// Section coordinates.
+    pos: [i32; 3],
+    /// Whether the section is culled and the level of detail it is drawn at, set
+    /// on the GPU every frame, then the range of levels it has blocks for.
+    flags: u32,
+    block_start: u32,
+    /// Number of blocks at level of detail 0, which is 0 for unused records.
+    block_len: u32,
+    /// Number of cells at the coarser levels, which follow the blocks.
+    lod_lens: u32,
+    _padding: u32,
+}
+
+const MIN_LOD_BIT: u32 = 3;
+const MAX_LOD_BIT: u32 = 5;
+const LOD_LEN_BITS: u32 = 10;
+
+/// Faces of a block towards the neighbouring columns, and their bits, in the
+/// order of [`Sides::cells`].
+const SIDE_FACES: [usize; 4] = [0, 1, 4, 5];
+const SIDE_FACE_BITS: [u32; 4] = [1 << 12, 1 << 13, 1 << 16, 1 << 17];
+
+/// Where a column lives in [`WorldBuffers`].
+#[derive(Debug)]
+struct ColumnAllocation {
+    chunks: Vec<u32>,
+    blocks: Range<u32>,
+    face_capacity: [usize; 2],
+    sides: Sides,
+    /// `(index in the block buffer, lod, y, block)` of the blocks drawing a
+    /// face towards a neighbouring column.
+    border_blocks: Vec<(u32, u32, i32, [u32; 5])>,
+}
+
+impl ColumnAllocation {
+    /// Hides the faces on `side` covered by `neighbor` and relights the others.
+    /// A coarser cell's face is hidden only when every block it covers is.
+    fn cull(
+        &mut self,
+        queue: &wgpu::Queue,
+        block_buffer: &wgpu::Buffer,
+        palette: &Palette,
+        side: usize,
+        neighbor: &Sides,
+    ) {
+        let face_bit = SIDE_FACE_BITS[side];
+
+        self.border_blocks.retain_mut(|(index, lod, y, block)| {
+            let size = 1 << *lod;
+            let x = block[0] & 0b1111;
+            let z = (block[0] >> 8) & 0b1111;
+            let (on_side, i) = match side {
+                0 => (x == 0, z),
+                1 => (x + size == 16, z),
+                2 => (z == 0, x),
+                _ => (z + size == 16, x),
+            };
+
+            if on_side && block[0] & face_bit != 0 {
+                let hidden = (*y..*y + size as i32).all(|y| {
+                    (i..i + size).all(|i| match neighbor.get(side ^ 1, y, i) {
+                        Ok(id) => Opacity::of(palette.state(id))
+                            .hides(palette.name(id) == palette.name(block[1] as u16)),
+                        Err(hidden) => hidden,
+                    })
+                });
+
+                let old_block = *block;
+                if hidden {
+                    block[0] &= !face_bit;
+                } else if let (0, Some(light)) = (*lod, neighbor.light(side ^ 1, *y, i)) {
+                    let face = SIDE_FACES[side];
+                    let shift = face % 2 * 16;
+                    let shading = &mut block[2 + face / 2];
+                    let ao = (*shading >> shift) & 0xff;
+
+                    *shading =
+                        (*shading & !(0xffff << shift)) | region::shading(ao, light) << shift;
+                }
+
+                if *block != old_block {
+                    queue.write_buffer(
+                        block_buffer,
+                        *index as u64 * mem::size_of::<[u32; 5]>() as u64,
+                        bytemuck::cast_slice(block),
+                    );
+                }
+            }
+
+            SIDE_FACE_BITS.iter().any(|&bit| block[0] & bit != 0)
+        });
+    }
+}
+
+/// Ranges of a buffer, allocated first-fit.
+#[derive(Debug, Default)]
+struct RangeAllocator {
+    /// Unused ranges before `end`, sorted and never touching each other.
+    free: Vec<Range<u32>>,
+    end: u32,
+}
+
+impl RangeAllocator {
+    fn alloc(&mut self, len: u32) -> Range<u32> {
+        let Some(i) = self
+            .free
+            .iter()
+            .position(|range| range.len() >= len as usize)
+        else {
+            self.end += len;
+            return self.end - len..self.end;
+        };
+
+        let range = &mut self.free[i];
+        let start = range.start;
+        range.start += len;
+        if range.start == range.end {
+            self.free.remove(i);
+        }
+
+        start..start + len
+    }
+
+    fn free(&mut self, range: Range<u32>) {
+        let i = self.free.partition_point(|free| free.start < range.start);
+        self.free.insert(i, range);
+
+        if i + 1 < self.free.len() && self.free[i].end == self.free[i + 1].start {
+            self.free[i].end = self.free.remove(i + 1).end;
+        }
+        if i > 0 && self.free[i - 1].end == self.free[i].start {
+            self.free[i - 1].end = self.free.remove(i).end;
+        }
+        if self.free.last().is_some_and(|last| last.end == self.end) {
+            self.end = self.free.pop().unwrap().start;
+        }
+    }
+}
+
+/// A buffer the faces of a range of chunk records are generated into, opaque
+/// ones from its start and translucent ones down from its end. Each kind is
+/// bounded by its own length, so that faces which don't fit are dropped rather
+/// than written over the other kind.
+#[derive(Debug)]
+pub struct FacePage {
+    pub buffer: wgpu::Buffer,
+    pub opaque_len: u32,
+    pub translucent_len: u32,
+}
+
+impl FacePage {
+    fn new(device: &wgpu::Device, opaque_len: u32, translucent_len: u32) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("face_buffer"),
+            // Storage buffers can't be empty.
+            size: (opaque_len + translucent_len).max(1) as u64 * mem::size_of::<[u32; 3]>() as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            buffer,
+            opaque_len,
+            translucent_len,
+        }
+    }
+}
+
+/// A column left out of the GPU buffers because its blocks or chunk records
+/// would not fit in a storage buffer binding.
+#[derive(Debug)]
+pub struct OutOfMemory {
+    /// Chunk coordinates of the column.
+    pub pos: glam::IVec2,
+}
+
+impl fmt::Display for OutOfMemory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "chunk {}, {}: out of GPU memory", self.pos.x, self.pos.y)
+    }
+}
+
+impl Error for OutOfMemory {}
+
+/// The loaded columns of a [`World`] on the GPU, along with the face pages sized
+/// for them. Columns are written to the buffers as they are loaded, which grow
+/// when they run out of room.
+#[derive(Debug)]
+pub struct WorldBuffers {
+    pub chunk_buffer: wgpu::Buffer,
+    pub chunks_len_buffer: wgpu::Buffer,
+    pub block_buffer: wgpu::Buffer,
+    /// Pages of the same size the chunk records are split evenly across, see
+    /// [`WorldBuffers::face_page_chunks`].
+    pub face_pages: Vec<FacePage>,
+    /// [`region::Section::biomes`] of every chunk record, at the same index.
+    pub biome_buffer: wgpu::Buffer,
+    /// [`Column::surface`] of every loaded column, in a grid of
+    /// `FAR_GRID_SIZE`² columns wrapping around.
+    pub far_field_buffer: wgpu::Buffer,
+    /// Highest y above a surface cell written so far.
+    surface_top: i32,
+    chunks_len: u32,
+    free_chunks: Vec<u32>,
+    blocks: RangeAllocator,
+    face_capacity: [usize; 2],
+    columns: HashMap<glam::IVec2, ColumnAllocation>,
+}
+
+impl WorldBuffers {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let buffer = |label, size, usage| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size,
+                usage: wgpu::BufferUsages::STORAGE | usage,
+                mapped_at_creation: false,
+            })
+        };
+        let copy = wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST;
+
+        Self {
+            chunk_buffer: buffer(
+                "chunk_buffer",
+                256 * mem::size_of::<ChunkRecord>() as u64,
+                copy,
+            ),
+            chunks_len_buffer: buffer(
+                "chunks_len_buffer",
+                mem::size_of::<u32>() as u64,
+                wgpu::BufferUsages::COPY_DST,
+            ),
+            block_buffer: buffer("block_buffer", 1 << 16, copy),
+            face_pages: vec![FacePage::new(device, 4096, 1024)],
+            biome_buffer: buffer("biome_buffer", 256 * BIOME_CELLS as u64, copy),
+            far_field_buffer: buffer(
+                "far_field_buffer",
+                (FAR_GRID_SIZE * FAR_GRID_SIZE) as u64 * SURFACE_SIZE,
+                wgpu::BufferUsages::COPY_DST,
+            ),
+            surface_top: i16::MIN as i32,
+            chunks_len: 0,
+            free_chunks: Vec::new(),
+            blocks: RangeAllocator::default(),
+            face_capacity: [0; 2],
+            columns: HashMap::new(),
+        }
+    }
+
+    /// Highest y above the surface of any column written so far, which columns
+    /// dropped since may have been.
+    pub fn surface_top(&self) -> i32 {
+        self.surface_top
+    }
+
+    /// Range of the chunk records whose faces are generated into `page` of
+    /// [`WorldBuffers::face_pages`].
+    pub fn face_page_chunks(&self, page: usize) -> Range<u32> {
+        let pages = self.face_pages.len() as u64;
+        let bound = |page| (self.chunks_len as u64 * page as u64 / pages) as u32;
+
+        bound(page)..bound(page + 1)
+    }
+
+    /// Grows the face pages, splitting them into more pages if needed, when the
+    /// `[opaque, translucent]` faces each of them needed in a past frame didn't
+    /// fit. Returns whether they were replaced.
+    pub fn reserve_faces(&mut self, device: &wgpu::Device, needed: &[[u32; 2]]) -> bool {
+        // Read back before the pages last changed.
+        if needed.len() != self.face_pages.len() {
+            return false;
+        }
+
+        // Chunks don't spread their faces evenly, so every page is sized for the
+        // fullest one.
+        let pages = needed.len() as u64;
+        let [opaque, translucent] = needed.iter().fold([0; 2], |most, &needed| {
+            [most[0].max(needed[0]), most[1].max(needed[1])]
+        });
+
+        self.fit_faces(
+            device,
+            opaque as u64 * pages,
+            translucent as u64 * pages,
+            MAX_FACE_PAGES,
+        )
+    }
+
+    /// Replaces the face pages with up to `max_pages` pages holding twice
+    /// `opaque` and `translucent` faces between them if they hold fewer.
+    fn fit_faces(
+        &mut self,
+        device: &wgpu::Device,
+        opaque: u64,
+        translucent: u64,
+        max_pages: usize,
+    ) -> bool {
+        let max_len = device.limits().max_storage_buffer_binding_size as u64
+            / mem::size_of::<[u32; 3]>() as u64;
+
+        let pages = self.face_pages.len() as u64;
+        let page = &self.face_pages[0];
+        let (old_opaque, old_translucent) = (
+            page.opaque_len as u64 * pages,
+            page.translucent_len as u64 * pages,
+        );
+        if opaque <= old_opaque && translucent <= old_translucent {
+            return false;
+        }
+
+        let opaque = (opaque * 2).max(old_opaque);
+        let translucent = (translucent * 2).max(old_translucent);
+        let pages = (opaque + translucent)
+            .div_ceil(max_len)
+            .clamp(1, max_pages as u64);
+
+        let mut opaque_len = opaque.div_ceil(pages);
+        let mut translucent_len = translucent.div_ceil(pages);
+        // Out of pages, the faces that don't fit keep being dropped.
+        if opaque_len + translucent_len > max_len {
+            opaque_len = opaque_len * max_len / (opaque_len + translucent_len);
+            translucent_len = max_len - opaque_len;
+        }
+
+        if pages == self.face_pages.len() as u64
+            && opaque_len == page.opaque_len as u64
+            && translucent_len == page.translucent_len as u64
+        {
+            return false;
+        }
+
+        self.face_pages = (0..pages)
+            .map(|_| FacePage::new(device, opaque_len as u32, translucent_len as u32))
+            .collect();
+
+        true
+    }
+
+    /// Number of chunk records, some of which may be unused.
+    pub fn chunks_len(&self) -> u32 {
+        self.chunks_len
+    }
+
+    /// Writes the sections and surface of `column`, replacing the column if it
+    /// was already there, and hides the faces between it and the neighbouring
+    /// columns. Fails when the column doesn't fit, leaving it out entirely.
+    pub fn insert(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        models: &ModelBuffers,
+        palette: &Palette,
+        column: Column,
+    ) -> Result<(), OutOfMemory> {
+        self.remove(queue, column.pos);
+
+        queue.write_buffer(
+            &self.far_field_buffer,
+            far_field_offset(column.pos),
+            bytemuck::cast_slice(&column.surface),
+        );
+        for &[cell, _] in &column.surface {
+            if cell != 0 {
+                self.surface_top = self.surface_top.max(cell as i32 >> 16);
+            }
+        }
+        if column.sections.is_empty() {
+            return Ok(());
+        }
+
+        let max_size = device.limits().max_storage_buffer_binding_size as u64;
+        let block_size = mem::size_of::<[u32; 5]>() as u64;
+
+        // The levels of detail of each section are laid out back to back, and
+        // only one of them is drawn in a frame.
+        let lods = column.lods.clone();
+        let blocks: Vec<_> = column
+            .sections
+            .iter()
+            .flat_map(|section| {
+                lods.clone()
+                    .flat_map(|lod| section.lod(lod).iter().copied())
+            })
+            .collect();
+        let face_capacity = column
+            .sections
+            .iter()
+            .map(|section| {
+                lods.clone()
+                    .map(|lod| match lod {
+                        0 => models.face_capacity(&section.blocks),
+                        _ => section
+                            .lod(lod)
+                            .iter()
+                            .fold([0; 2], |mut capacity, &[block, ..]| {
+                                capacity[(block >> region::TRANSLUCENT_BIT) as usize & 1] +=
+                                    models::cube_face_capacity(block);
+                                capacity
+                            }),
+                    })
+                    .fold([0; 2], |most, capacity| {
+                        [most[0].max(capacity[0]), most[1].max(capacity[1])]
+                    })
+            })
+            .fold([0; 2], |sum, capacity| {
+                [sum[0] + capacity[0], sum[1] + capacity[1]]
+            });
+
+        let new_chunks = column.sections.len().saturating_sub(self.free_chunks.len());
+        let chunks_len = self.chunks_len as u64 + new_chunks as u64;
+
+        let block_range = self.blocks.alloc(blocks.len() as u32);
+        if block_range.end as u64 * block_size > max_size
+            || chunks_len * mem::size_of::<ChunkRecord>() as u64 > max_size
+            || chunks_len * BIOME_CELLS as u64 > max_size
+        {
+            self.blocks.free(block_range);
+            self.remove(queue, column.pos);
+            return Err(OutOfMemory { pos: column.pos });
+        }
+
+        grow(
+            device,
+            queue,
+            &mut self.block_buffer,
+            "block_buffer",
+            block_range.end as u64 * block_size,
+        );
+        queue.write_buffer(
+            &self.block_buffer,
+            block_range.start as u64 * block_size,
+            bytemuck::cast_slice(&blocks),
+        );
+
+        let mut chunks = Vec::with_capacity(column.sections.len());
+        let mut border_blocks = Vec::new();
+        let mut block_start = block_range.start;
+        for section in &column.sections {
+            let mut index = block_start;
+            for lod in lods.clone() {
+                let size = 1 << lod;
+
+                for &block in section.lod(lod) {
+                    let x = block[0] & 0b1111;
+                    let z = (block[0] >> 8) & 0b1111;
+                    let on_side = [x == 0, x + size == 16, z == 0, z + size == 16];
+
+                    if (0..4).any(|side| on_side[side] && block[0] & SIDE_FACE_BITS[side] != 0) {
+                        let y = section.y * 16 + ((block[0] >> 4) & 0b1111) as i32;
+                        border_blocks.push((index, lod, y, block));
+                    }
+                    index += 1;
+                }
+            }
+
+            let chunk = self.free_chunks.pop().unwrap_or_else(|| {
+                self.chunks_len += 1;
+                self.chunks_len - 1
+            });
+
+            let lod_lens = (1..=MAX_LOD)
+                .filter(|lod| lods.contains(lod))
+                .fold(0, |lens, lod| {
+                    lens | (section.lod(lod).len() as u32) << ((lod - 1) * LOD_LEN_BITS)
+                });
+            let record = ChunkRecord {
+                pos: [column.pos.x, section.y, column.pos.y],
+                flags: (lods.start << MIN_LOD_BIT) | ((lods.end - 1) << MAX_LOD_BIT),
+                block_start,
+                block_len: section.blocks.len() as u32,
+                lod_lens,
+                ..Default::default()
+            };
+
+            grow(
+                device,
+                queue,
+                &mut self.chunk_buffer,
+                "chunk_buffer",
+                self.chunks_len as u64 * mem::size_of::<ChunkRecord>() as u64,
+            );
+            queue.write_buffer(
+                &self.chunk_buffer,
+                chunk as u64 * mem::size_of::<ChunkRecord>() as u64,
+                bytemuck::bytes_of(&record),
+            );
+
+            grow(
+                device,
+                queue,
+                &mut self.biome_buffer,
+                "biome_buffer",
+                self.chunks_len as u64 * BIOME_CELLS as u64,
+            );
+            queue.write_buffer(
+                &self.biome_buffer,
+                chunk as u64 * BIOME_CELLS as u64,
+                &section.biomes,
+            );
+
+            chunks.push(chunk);
+            block_start = index;
+        }
+
+        queue.write_buffer(
+            &self.chunks_len_buffer,
+            0,
+            bytemuck::bytes_of(&self.chunks_len),
+        );
+
+        // Faces are regenerated every frame, so the old ones don't have to be
+        // kept. Capacities are far more than what is drawn, so only the faces
+        // read back split the pages further.
+        self.face_capacity[0] += face_capacity[0];
+        self.face_capacity[1] += face_capacity[1];
+        self.fit_faces(
+            device,
+            self.face_capacity[0] as u64,
+            self.face_capacity[1] as u64,
+            self.face_pages.len(),
+        );
+
+        let mut allocation = ColumnAllocation {
+            chunks,
+            blocks: block_range,
+            face_capacity,
+            sides: column.sides,
+            border_blocks,
+        };
+
+        let offsets = [
+            glam::IVec2::NEG_X,
+            glam::IVec2::X,
+            glam::IVec2::NEG_Y,
+            glam::IVec2::Y,
+        ];
+        for (side, offset) in offsets.into_iter().enumerate() {
+            if let Some(neighbor) = self.columns.get_mut(&(column.pos + offset)) {
+                allocation.cull(queue, &self.block_buffer, palette, side, &neighbor.sides);
+                neighbor.cull(
+                    queue,
+                    &self.block_buffer,
+                    palette,
+                    side ^ 1,
+                    &allocation.sides,
+                );
+            }
+        }
+
+        self.columns.insert(column.pos, allocation);
+
+        Ok(())
+    }
+
+    /// Drops the column at chunk coordinates `pos` if it is there.
+    pub fn remove(&mut self, queue: &wgpu::Queue, pos: glam::IVec2) {
+        queue.write_buffer(
+            &self.far_field_buffer,
+            far_field_offset(pos),
+            &[0; SURFACE_SIZE as usize],
+        );
+
+        let Some(allocation) = self.columns.remove(&pos) else {
+            return;
+        };
+
+        for chunk in allocation.chunks {
+            queue.write_buffer(
+                &self.chunk_buffer,
+                chunk as u64 * mem::size_of::<ChunkRecord>() as u64,
+                bytemuck::bytes_of(&ChunkRecord::default()),
+            );
+            self.free_chunks.push(chunk);
+        }
+
+        self.blocks.free(allocation.blocks);
+        self.face_capacity[0] -= allocation.face_capacity[0];
+        self.face_capacity[1] -= allocation.face_capacity[1];
+    }
+}
+
+const SURFACE_SIZE: u64 =
+    (16 / SURFACE_CELL_SIZE).pow(2) as u64 * mem::size_of::<[u32; 2]>() as u64;
+
+fn far_field_offset(pos: glam::IVec2) -> u64 {
+    let cell = pos.rem_euclid(glam::IVec2::splat(FAR_GRID_SIZE as i32));
+
+    (cell.y as u64 * FAR_GRID_SIZE as u64 + cell.x as u64) * SURFACE_SIZE
+}
+
+/// Replaces `buffer` with one at least twice as large when it is smaller than
+/// `size`, copying its contents over. `size` must fit in a storage buffer
+/// binding, which callers check beforehand, and `buffer` must be usable as the
+/// source and destination of copies.
+pub fn grow(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    buffer: &mut wgpu::Buffer,
+    label: &str,
+    size: u64,
+) {
+    if size <= buffer.size() {
+        return;
+    }
+
+    let max_size = device.limits().max_storage_buffer_binding_size as u64;
+    debug_assert!(size <= max_size, "{label} can't hold {size} bytes");
+
+    let new_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: size.max(buffer.size() * 2).min(max_size),
+        usage: buffer.usage(),
+        mapped_at_creation: false,
+    });
+
+    // Submitted right away so that the writes queued after this land in the new
+    // buffer after the copy, not before it.
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("grow_buffer_encoder"),
+    });
+    encoder.copy_buffer_to_buffer(buffer, 0, &new_buffer, 0, buffer.size());
+    queue.submit(Some(encoder.finish()));
+
+    *buffer = new_buffer;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_region_file_names() {
+        assert_eq!(parse_region_file_name("r.0.0.mca"), Some([0, 0]));
+        assert_eq!(parse_region_file_name("r.-1.12.mca"), Some([-1, 12]));
+        assert_eq!(parse_region_file_name("r.3.-40.mca"), Some([3, -40]));
+    }
+
+    #[test]
+    fn rejects_other_file_names() {
+        assert_eq!(parse_region_file_name("r.0.0.mcr"), None);
+        assert_eq!(parse_region_file_name("r.0.mca"), None);
+        assert_eq!(parse_region_file_name("r.0.0.0.mca"), None);
+        assert_eq!(parse_region_file_name("r.a.0.mca"), None);
+        assert_eq!(parse_region_file_name("c.0.0.mca"), None);
+        assert_eq!(parse_region_file_name("r.0.0.mca.tmp"), None);
+    }
+
+    #[test]
+    fn allocates_ranges_first_fit() {
+        let mut allocator = RangeAllocator::default();
+
+        let a = allocator.alloc(4);
+        let b = allocator.alloc(8);
+        let c = allocator.alloc(2);
+        assert_eq!([a.clone(), b.clone(), c], [0..4, 4..12, 12..14]);
+
+        allocator.free(b);
+        assert_eq!(allocator.alloc(3), 4..7);
+        assert_eq!(allocator.alloc(6), 14..20);
+        assert_eq!(allocator.alloc(5), 7..12);
+        assert!(allocator.free.is_empty());
+
+        allocator.free(a);
+        assert_eq!(allocator.free.as_slice(), &[Range { start: 0, end: 4 }]);
+    }
+
+    #[test]
+    fn merges_freed_ranges() {
+        let mut allocator = RangeAllocator::default();
+        let ranges: Vec<_> = (0..4).map(|_| allocator.alloc(4)).collect();
+
+        allocator.free(ranges[0].clone());
+        allocator.free(ranges[2].clone());
+        assert_eq!(allocator.free, [0..4, 8..12]);
+
+        allocator.free(ranges[1].clone());
+        assert_eq!(allocator.free.as_slice(), &[Range { start: 0, end: 12 }]);
+
+        // Freeing the last range gives everything back.
+        allocator.free(ranges[3].clone());
+        assert!(allocator.free.is_empty());
+        assert_eq!(allocator.end, 0);
+    }
+}